@@ -0,0 +1,321 @@
+use crate::history::fuzzy_score;
+use crate::language::repr::Representation;
+use crate::language::vm::VM;
+
+/// At most this many suggestions are offered at once.
+pub const MAX_SUGGESTIONS: usize = 8;
+
+pub const KEYWORDS: &[&str] = &[
+    "while",
+    "try",
+    "catch",
+    "throw",
+    "return",
+    "break",
+    "when",
+    "true",
+    "false",
+    "struct",
+    "enum",
+    "interface",
+    "impl",
+    "for",
+    "module",
+    "import",
+    "use",
+    "unit",
+    "to",
+    "List",
+    "Set",
+    "Map",
+    "Vec",
+    "Mat",
+    "Poly",
+    "Quantity",
+    "Int",
+    "Rat",
+    "Complex",
+    "Bool",
+    "Quote",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Keyword,
+    Var,
+    Definition,
+    Protocol,
+    Type,
+    Variant,
+}
+
+impl Kind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Kind::Keyword => "keyword",
+            Kind::Var => "var",
+            Kind::Definition => "def",
+            Kind::Protocol => "protocol",
+            Kind::Type => "type",
+            Kind::Variant => "variant",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub label: String,
+    pub detail: String,
+    pub kind: Kind,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == ':'
+}
+
+/// Byte offset in `text` of the UTF-16 offset the browser reports for the caret.
+pub fn byte_offset(text: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (idx, c) in text.char_indices() {
+        if units >= utf16 {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn clamp(text: &str, cursor: usize) -> usize {
+    let mut cursor = cursor.min(text.len());
+    while !text.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+    cursor
+}
+
+/// Start and text of the word ending at byte offset `cursor`.
+pub fn word_at(text: &str, cursor: usize) -> (usize, &str) {
+    let cursor = clamp(text, cursor);
+    let start = text[..cursor]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(cursor, |(idx, _)| idx);
+    (start, &text[start..cursor])
+}
+
+/// Variables of the `| x y |{ ... }` take blocks still open at the end of
+/// `prefix`, innermost last.
+pub fn take_vars(prefix: &str) -> Vec<String> {
+    let mut frames: Vec<Vec<String>> = vec![];
+    // Names seen since an opening `|`, while they could still be a take header.
+    let mut header: Option<Vec<String>> = None;
+    // A closed header waiting for its `{`.
+    let mut pending: Option<Vec<String>> = None;
+    let mut chars = prefix.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if is_word_char(c) {
+            let mut end = start + c.len_utf8();
+            while let Some((idx, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+                end = idx + c.len_utf8();
+            }
+            let word = &prefix[start..end];
+            pending = None;
+            match header.as_mut() {
+                Some(vars) if word.starts_with(|c: char| c.is_lowercase() || c == '_') => {
+                    vars.push(word.to_string())
+                }
+                _ => header = None,
+            }
+            continue;
+        }
+        match c {
+            '|' => match header.take() {
+                Some(vars) if !vars.is_empty() => pending = Some(vars),
+                _ => header = Some(vec![]),
+            },
+            '{' => {
+                frames.push(pending.take().unwrap_or_default());
+                header = None;
+            }
+            '}' => {
+                frames.pop();
+                header = None;
+                pending = None;
+            }
+            _ => {
+                header = None;
+                pending = None;
+            }
+        }
+    }
+    frames.into_iter().flatten().collect()
+}
+
+fn candidates(vm: &VM, prefix: &str) -> Vec<Suggestion> {
+    let ctx = &vm.parse_ctx;
+    let mut ret: Vec<Suggestion> = take_vars(prefix)
+        .into_iter()
+        .rev()
+        .map(|label| Suggestion {
+            label,
+            detail: "take variable".to_string(),
+            kind: Kind::Var,
+        })
+        .collect();
+
+    for (name, body) in vm.get_definition_bodies() {
+        ret.push(Suggestion {
+            label: name,
+            detail: body.join(" "),
+            kind: Kind::Definition,
+        });
+    }
+    for (name, signatures) in vm.get_protocol_signatures() {
+        ret.push(Suggestion {
+            label: name,
+            detail: signatures.join(" | "),
+            kind: Kind::Protocol,
+        });
+    }
+    let mut types: Vec<Suggestion> = vm
+        .env
+        .typ_data
+        .iter()
+        .map(|((name, tag), fields)| {
+            let type_name = ctx.lookup_type_name(*name);
+            let fields = fields
+                .iter()
+                .map(|x| x.get_repr(ctx))
+                .collect::<Vec<_>>()
+                .join(" ");
+            match tag {
+                Some(tag) => Suggestion {
+                    label: format!("{type_name}::{}", ctx.lookup_tag_name(*tag)),
+                    detail: format!("({fields})"),
+                    kind: Kind::Variant,
+                },
+                None => Suggestion {
+                    label: type_name,
+                    detail: format!("struct ({fields})"),
+                    kind: Kind::Type,
+                },
+            }
+        })
+        .collect();
+    for name in vm.env.type_variants.keys() {
+        types.push(Suggestion {
+            label: ctx.lookup_type_name(*name),
+            detail: "enum".to_string(),
+            kind: Kind::Type,
+        });
+    }
+    types.sort_by(|a, b| a.label.cmp(&b.label));
+    ret.extend(types);
+    ret.extend(KEYWORDS.iter().map(|keyword| Suggestion {
+        label: keyword.to_string(),
+        detail: String::new(),
+        kind: Kind::Keyword,
+    }));
+    ret
+}
+
+/// Completions for the word being typed at byte offset `cursor`, best first.
+/// Prefix matches rank above fuzzy ones; nothing is offered for an empty word
+/// or when the word is already the only match.
+pub fn suggestions(vm: &VM, text: &str, cursor: usize) -> Vec<Suggestion> {
+    let (start, word) = word_at(text, cursor);
+    if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+        return vec![];
+    }
+    let mut seen = std::collections::HashSet::new();
+    let mut matches: Vec<(i64, usize, Suggestion)> = candidates(vm, &text[..start])
+        .into_iter()
+        .filter(|x| seen.insert(x.label.clone()))
+        .enumerate()
+        .filter_map(|(idx, x)| {
+            let prefix_bonus = if x.label.starts_with(word) { 100 } else { 0 };
+            fuzzy_score(word, &x.label).map(|score| (score + prefix_bonus, idx, x))
+        })
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    if matches.len() == 1 && matches[0].2.label == word {
+        return vec![];
+    }
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, x)| x)
+        .collect()
+}
+
+/// Signature of the function, protocol or constructor at the caret, or of the
+/// last word before it, e.g. `add: Int Int -> Int | Rat Rat -> Rat`.
+pub fn signature(vm: &VM, text: &str, cursor: usize) -> Option<String> {
+    let cursor = clamp(text, cursor);
+    let end = text[..cursor].trim_end().len();
+    let (start, word) = word_at(text, end);
+    if word.is_empty() {
+        return None;
+    }
+    candidates(vm, &text[..start])
+        .into_iter()
+        .find(|x| x.label == word && x.kind != Kind::Keyword && !x.detail.is_empty())
+        .map(|x| match x.kind {
+            Kind::Variant => format!("{}{}", x.label, x.detail),
+            Kind::Definition => format!("{} = {}", x.label, x.detail),
+            _ => format!("{}: {}", x.label, x.detail),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(vm: &VM, text: &str) -> Vec<String> {
+        suggestions(vm, text, text.len())
+            .into_iter()
+            .map(|x| x.label)
+            .collect()
+    }
+
+    #[test]
+    fn open_take_vars() {
+        assert_eq!(take_vars("|a b| { a "), ["a", "b"]);
+        assert_eq!(take_vars("|a| { |b| { b "), ["a", "b"]);
+        assert_eq!(take_vars("|a| { |b| { b } "), ["a"]);
+        assert!(take_vars("|a| { 1 } ").is_empty());
+        assert!(take_vars("|a b| 3 { ").is_empty());
+        assert!(take_vars("x | Some(y) => { ").is_empty());
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(word_at("1 ad", 4), (2, "ad"));
+        assert_eq!(word_at("1 geo::sq", 9), (2, "geo::sq"));
+        assert_eq!(word_at("add ", 4), (4, ""));
+        assert_eq!(word_at("dup add", 3), (0, "dup"));
+        assert_eq!(word_at("é ab", 100), (3, "ab"));
+        assert_eq!(word_at("éa", 1), (0, ""));
+    }
+
+    #[test]
+    fn suggested() {
+        let mut vm = VM::default();
+        vm.parse_full_program("sq_total = 1;").unwrap();
+        assert!(labels(&vm, "1 ").is_empty());
+        assert!(labels(&vm, "12").is_empty());
+        assert_eq!(labels(&vm, "sq_tot"), ["sq_total"]);
+        assert!(labels(&vm, "sq_total").is_empty());
+        let vars = suggestions(&vm, "|total| { tot", 13);
+        assert_eq!((vars[0].label.as_str(), vars[0].kind), ("total", Kind::Var));
+        let prefixed = labels(&vm, "1 2 ad");
+        assert!(prefixed.len() <= MAX_SUGGESTIONS);
+        assert!(prefixed.contains(&"add".to_string()));
+        assert!(prefixed[0].starts_with("ad"));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::language::format::format_source;
+use crate::language::repr::Representation;
+use crate::language::vm::VM;
+use std::path::{Path, PathBuf};
+
+/// Evaluates the given source files in order and prints the resulting stack.
+/// Modules are looked up next to each file and in the directories listed in
+/// `STACKULATOR_PATH`.
+pub fn run(files: &[String]) -> i32 {
+    let mut vm = VM::default();
+    if let Some(paths) = std::env::var_os("STACKULATOR_PATH") {
+        vm.modules.search_path.extend(std::env::split_paths(&paths));
+    }
+
+    for file in files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                return 1;
+            }
+        };
+        let dir = Path::new(file)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."));
+        if !vm.modules.search_path.contains(&dir) {
+            vm.modules.search_path.insert(0, dir);
+        }
+
+        if let Err(err) = vm.parse_full_program(&source) {
+            eprintln!("{file}:\n{err}");
+            return 1;
+        }
+        if let Err(err) = vm.eval() {
+            eprintln!("{file}: {}", err.get_repr(&vm.parse_ctx));
+            return 1;
+        }
+    }
+
+    for value in vm.stack.iter() {
+        println!("{}", vm.display(value));
+    }
+    0
+}
+
+/// Prints the given source files canonically formatted, or reads standard
+/// input when there are none.
+pub fn format(files: &[String]) -> i32 {
+    let sources: Vec<(String, std::io::Result<String>)> = if files.is_empty() {
+        vec![(
+            "<stdin>".to_string(),
+            std::io::read_to_string(std::io::stdin()),
+        )]
+    } else {
+        files
+            .iter()
+            .map(|file| (file.clone(), std::fs::read_to_string(file)))
+            .collect()
+    };
+    for (file, source) in sources {
+        let source = match source {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                return 1;
+            }
+        };
+        match format_source(&source) {
+            Ok(formatted) => print!("{formatted}"),
+            Err(err) => {
+                eprintln!("{file}:\n{err}");
+                return 1;
+            }
+        }
+    }
+    0
+}
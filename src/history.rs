@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+/// Oldest entries are dropped beyond this.
+pub const MAX_ENTRIES: usize = 500;
+
+/// Appends `entry` as the most recent one. An identical older entry is moved
+/// instead of duplicated.
+pub fn push(history: &mut Vec<Arc<String>>, entry: Arc<String>) {
+    history.retain(|x| x.trim() != entry.trim());
+    history.push(entry);
+    if history.len() > MAX_ENTRIES {
+        history.drain(..history.len() - MAX_ENTRIES);
+    }
+}
+
+/// Scores `candidate` if every character of `query` appears in it in order
+/// (case insensitive). Consecutive runs and matches at word starts score
+/// higher, gaps cost a little.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut last_match: Option<usize> = None;
+    let mut prev = ' ';
+    let mut chars = candidate.chars().enumerate();
+    for q in query.chars().filter(|x| !x.is_whitespace()) {
+        let q = q.to_lowercase().next().unwrap_or(q);
+        loop {
+            let (idx, c) = chars.next()?;
+            let before = std::mem::replace(&mut prev, c);
+            if c.to_lowercase().next() != Some(q) {
+                continue;
+            }
+            score += match last_match {
+                Some(last) if last + 1 == idx => 8,
+                Some(last) => 1 - (idx - last) as i64 / 4,
+                None => 2,
+            };
+            if !before.is_alphanumeric() && before != '_' {
+                score += 4;
+            }
+            last_match = Some(idx);
+            break;
+        }
+    }
+    Some(score)
+}
+
+/// Indices into `history` matching `query`, best first. Ties go to the more
+/// recent entry, so an empty query lists everything newest first.
+pub fn search(history: &[Arc<String>], query: &str) -> Vec<usize> {
+    let mut matches: Vec<(i64, usize)> = history
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| fuzzy_score(query, entry).map(|score| (score, idx)))
+        .collect();
+    matches.sort_by(|a, b| b.cmp(a));
+    matches.into_iter().map(|(_, idx)| idx).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(texts: &[&str]) -> Vec<Arc<String>> {
+        texts.iter().map(|x| Arc::new(x.to_string())).collect()
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(fuzzy_score("", "add"), Some(0));
+        assert_eq!(fuzzy_score("da", "add"), None);
+        assert_eq!(fuzzy_score("AD", "add"), fuzzy_score("ad", "add"));
+        assert_eq!(fuzzy_score("a d", "add"), fuzzy_score("ad", "add"));
+        assert!(fuzzy_score("ab", "abc") > fuzzy_score("ab", "axxb"));
+        assert!(fuzzy_score("b", "a b") > fuzzy_score("b", "ab"));
+        assert!(fuzzy_score("b", "a_b") < fuzzy_score("b", "a b"));
+    }
+
+    #[test]
+    fn pushed() {
+        let mut history = entries(&["1 2 add", "dup", "3"]);
+        push(&mut history, Arc::new(" dup ".to_string()));
+        assert_eq!(history, entries(&["1 2 add", "3", " dup "]));
+
+        let mut history = vec![];
+        for i in 0..MAX_ENTRIES + 10 {
+            push(&mut history, Arc::new(i.to_string()));
+        }
+        assert_eq!(history.len(), MAX_ENTRIES);
+        assert_eq!(*history[0], "10");
+        assert_eq!(*history[MAX_ENTRIES - 1], (MAX_ENTRIES + 9).to_string());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::language::ast::stack::Stack;
 use crate::language::env::Env;
-use crate::language::eval::{ChainMap, Eval, EvalError, Flow, Values};
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values};
 
 #[derive(Debug, Clone, Default, PartialEq, PartialOrd, Ord, Eq)]
 pub struct IfTrue {
@@ -16,7 +16,7 @@ impl From<Stack> for IfTrue {
 impl Eval<Flow> for IfTrue {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -35,6 +35,10 @@ impl Eval<Flow> for IfTrue {
         self.elems.get_free_vars(vars)
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elems.get_calls(calls)
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.elems.get_vars(vars)
     }
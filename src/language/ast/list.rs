@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::language::{
     env::Env,
-    eval::{ChainMap, Eval, EvalError, Flow, Values},
+    eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values},
     parse::{Parse, ParseCtx, Rule},
 };
 
@@ -49,11 +49,11 @@ impl Representation<(), ParseCtx> for List {
 impl Eval<Flow> for List {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
-        let mut result = vec![];
+        let mut result = ValueStack::default();
         let _res = self.elements.eval(&mut result, env, vars)?;
         let ret = Values::List(result.into_iter().collect());
         values.push(ret);
@@ -64,6 +64,10 @@ impl Eval<Flow> for List {
         self.elements.get_free_vars(vars);
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elements.get_calls(calls);
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.elements.get_free_vars(vars);
     }
@@ -2,7 +2,7 @@ use std::{collections::BTreeMap, sync::Arc};
 
 use crate::language::{
     env::Env,
-    eval::{ChainMap, Eval, EvalError, Flow, Values},
+    eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values},
     parse::{Parse, ParseCtx, Rule},
 };
 
@@ -36,7 +36,7 @@ use crate::language::repr::Representation;
 impl Representation<(), ParseCtx> for Map {
     fn get_repr(&self, context: &ParseCtx) -> String {
         let mut result = String::new();
-        result.push_str("Set(");
+        result.push_str("Map(");
         self.elements
             .elems
             .iter()
@@ -49,14 +49,14 @@ impl Representation<(), ParseCtx> for Map {
 impl Eval<Flow> for Map {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
-        let mut result = vec![];
+        let mut result = ValueStack::default();
         let _ = self.elements.eval(&mut result, env, vars)?;
 
-        for res in &result {
+        for res in result.iter() {
             match res {
                 Values::List(l) if l.len() == 2 => (),
                 _ => return Err(EvalError::MapExprMustHaveListOfLen2),
@@ -84,6 +84,10 @@ impl Eval<Flow> for Map {
         self.elements.get_free_vars(vars);
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elements.get_calls(calls);
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.elements.get_vars(vars);
     }
@@ -1,6 +1,6 @@
 use super::stack::Stack;
 use crate::language::env::Env;
-use crate::language::eval::{ChainMap, Eval, EvalError, Flow, Values};
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values};
 
 use malachite::{Integer, Rational};
 
@@ -34,6 +34,8 @@ pub enum Pattern {
     TypeInt(Option<usize>),
     Float(Rational),
     TypeFloat(Option<usize>),
+    Complex(Rational, Rational),
+    TypeComplex(Option<usize>),
 
     TypeStack(Option<usize>),
 
@@ -55,6 +57,7 @@ impl Pattern {
             TypeBool(Some(i)) => vec![*i],
             TypeInt(Some(i)) => vec![*i],
             TypeFloat(Some(i)) => vec![*i],
+            TypeComplex(Some(i)) => vec![*i],
             TypeStack(Some(i)) => vec![*i],
             NamedStruct(_, TypePattern::All(Some(i))) => vec![*i],
             NamedStruct(_, TypePattern::FullList(v)) => v
@@ -114,6 +117,7 @@ impl Pattern {
             (Pattern::DontCare, _) => Flow::Ok,
             (Pattern::Int(x), Values::Int(y)) if *x == y => Flow::Ok,
             (Pattern::Float(x), Values::Float(y)) if *x == y => Flow::Ok,
+            (Pattern::Complex(a, b), Values::Complex(c, d)) if *a == c && *b == d => Flow::Ok,
             (Pattern::Bool(x), Values::Bool(y)) if *x == y => Flow::Ok,
             (Pattern::Variable(var), x) => {
                 vars.insert(*var, x.to_owned());
@@ -129,6 +133,11 @@ impl Pattern {
                 Flow::Ok
             }
             (Pattern::TypeFloat(None), Values::Float(_)) => Flow::Ok,
+            (Pattern::TypeComplex(Some(var)), x @ Values::Complex(..)) => {
+                vars.insert(*var, x.to_owned());
+                Flow::Ok
+            }
+            (Pattern::TypeComplex(None), Values::Complex(..)) => Flow::Ok,
             (Pattern::TypeBool(Some(var)), x @ Values::Bool(_)) => {
                 vars.insert(*var, x.to_owned());
                 Flow::Ok
@@ -530,6 +539,23 @@ impl Pattern {
                 Flow::Ok
             }
 
+            (
+                Pattern::NamedStruct(pat_name, TypePattern::FullList(pats)),
+                Values::Custom {
+                    name,
+                    tag: None,
+                    values: None,
+                },
+            ) if pat_name == &name && pats.is_empty() => Flow::Ok,
+            (
+                Pattern::NamedVariant(pat_name, var_name, TypePattern::FullList(pats)),
+                Values::Custom {
+                    name,
+                    tag: Some(var),
+                    values: None,
+                },
+            ) if pat_name == &name && var_name == &var && pats.is_empty() => Flow::Ok,
+
             (_, _) => Flow::Cont,
         }
     }
@@ -549,7 +575,7 @@ pub struct Match {
 impl Eval<Flow> for MatchElem {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -564,7 +590,7 @@ impl Eval<Flow> for MatchElem {
         }
 
         if !self.cond.elems.is_empty() {
-            let mut cond = vec![];
+            let mut cond = ValueStack::default();
 
             match self.cond.eval(&mut cond, env, vars) {
                 Ok(_) => {}
@@ -605,6 +631,11 @@ impl Eval<Flow> for MatchElem {
         *vars = vars.difference(&defined_vars).cloned().collect();
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.cond.get_calls(calls);
+        self.body.get_calls(calls);
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.cond.get_vars(vars);
         self.body.get_vars(vars);
@@ -629,7 +660,7 @@ impl Eval<Flow> for MatchElem {
 impl Eval<Flow> for Match {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -668,6 +699,10 @@ impl Eval<Flow> for Match {
             .fold(ret, |x, y| x.union(&y).cloned().collect());
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elems.iter().for_each(|x| x.get_calls(calls));
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         let ret: HashSet<usize> = HashSet::new();
         *vars = self
@@ -691,16 +726,20 @@ impl Eval<Flow> for Match {
     }
 }
 
-use crate::language::ast::Ast;
+use crate::language::ast::{Ast, parse_complex, parse_integer, parse_rational, rational_repr};
+use crate::language::number::NumberFormat;
 use crate::language::parse::{Parse, ParseCtx, Rule};
 use std::collections::{BTreeSet, HashSet};
-use std::str::FromStr;
 
 impl Parse for Pattern {
     fn parse(pairs: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> Self {
         match pairs.as_rule() {
-            Rule::integer => Pattern::Int(Integer::from_str(pairs.as_str()).unwrap()),
-            Rule::float => Pattern::Float(Rational::from_str(pairs.as_str()).unwrap()),
+            Rule::integer => Pattern::Int(parse_integer(pairs)),
+            Rule::float | Rule::fraction => Pattern::Float(parse_rational(pairs)),
+            Rule::complex => {
+                let (re, im) = parse_complex(pairs);
+                Pattern::Complex(re, im)
+            }
             Rule::bools => Pattern::Bool("true" == pairs.as_str()),
             Rule::varName => Pattern::Variable(ctx.insert_var(pairs.as_str())),
             Rule::dontCare => Pattern::DontCare,
@@ -712,6 +751,10 @@ impl Parse for Pattern {
                 ctx.insert_var(pairs.into_inner().next().unwrap().as_str()),
             )),
             Rule::ratDontCarePattern => Pattern::TypeFloat(None),
+            Rule::complexPattern => Pattern::TypeComplex(Some(
+                ctx.insert_var(pairs.into_inner().next().unwrap().as_str()),
+            )),
+            Rule::complexDontCarePattern => Pattern::TypeComplex(None),
             Rule::boolPattern => Pattern::TypeBool(Some(
                 ctx.insert_var(pairs.into_inner().next().unwrap().as_str()),
             )),
@@ -721,48 +764,51 @@ impl Parse for Pattern {
             )),
             Rule::stackDontCarePattern => Pattern::TypeStack(None),
             Rule::listAllpattern => Pattern::TypeList(ListPattern::All(Some(
-                ctx.insert_var(pairs.into_inner().next().unwrap().as_str()),
+                ctx.insert_var(&pairs.into_inner().next().unwrap().as_str()[1..]),
             ))),
             Rule::listAllDontCarepattern => Pattern::TypeList(ListPattern::All(None)),
+            Rule::setAllpattern => Pattern::TypeSet(SetPattern::All(Some(
+                ctx.insert_var(&pairs.into_inner().next().unwrap().as_str()[1..]),
+            ))),
             Rule::setAllDontCarepattern => Pattern::TypeSet(SetPattern::All(None)),
 
             Rule::structAllDontCarepattern => {
                 let mut pairs = pairs.into_inner();
-                let name = ctx.insert_type(pairs.next().unwrap().as_str());
+                let name = ctx.lookup_type(pairs.next().unwrap().as_str());
 
                 Pattern::NamedStruct(name, TypePattern::All(None))
             }
             Rule::enumAllDontCarepattern => {
                 let mut pairs = pairs.into_inner();
                 let mut pairs = pairs.next().unwrap().into_inner();
-                let name = ctx.insert_type(dbg!(pairs.next().unwrap().as_str()));
+                let name = ctx.lookup_type(pairs.next().unwrap().as_str());
 
-                let tag = ctx.insert_tag(dbg!(pairs.next().unwrap().as_str()));
+                let tag = ctx.insert_tag(pairs.next().unwrap().as_str());
 
                 Pattern::NamedVariant(name, tag, TypePattern::All(None))
             }
 
             Rule::structAllpattern => {
                 let mut pairs = pairs.into_inner();
-                let name = ctx.insert_type(pairs.next().unwrap().as_str());
-                let var = ctx.insert_var(pairs.next().unwrap().as_str());
+                let name = ctx.lookup_type(pairs.next().unwrap().as_str());
+                let var = ctx.insert_var(&pairs.next().unwrap().as_str()[1..]);
 
                 Pattern::NamedStruct(name, TypePattern::All(Some(var)))
             }
             Rule::enumAllpattern => {
                 let mut pair = pairs.into_inner();
                 let mut pairs = pair.next().unwrap().into_inner();
-                let name = ctx.insert_type(dbg!(pairs.next().unwrap().as_str()));
+                let name = ctx.lookup_type(pairs.next().unwrap().as_str());
 
-                let tag = ctx.insert_tag(dbg!(pairs.next().unwrap().as_str()));
-                let var = ctx.insert_var(pairs.next().unwrap().as_str());
+                let tag = ctx.insert_tag(pairs.next().unwrap().as_str());
+                let var = ctx.insert_var(&pair.next().unwrap().as_str()[1..]);
 
                 Pattern::NamedVariant(name, tag, TypePattern::All(Some(var)))
             }
 
             Rule::structFullPattern => {
                 let mut pairs = pairs.into_inner();
-                let name = ctx.insert_type(pairs.next().unwrap().as_str());
+                let name = ctx.lookup_type(pairs.next().unwrap().as_str());
                 Pattern::NamedStruct(
                     name,
                     TypePattern::FullList(pairs.map(|x| Self::parse(x, ctx)).collect()),
@@ -772,9 +818,9 @@ impl Parse for Pattern {
             Rule::enumFullPattern => {
                 let mut pairs = pairs.into_inner();
                 let mut names = pairs.next().unwrap().into_inner();
-                let name = ctx.insert_type(dbg!(names.next().unwrap().as_str()));
+                let name = ctx.lookup_type(names.next().unwrap().as_str());
 
-                let tag = ctx.insert_tag(dbg!(names.next().unwrap().as_str()));
+                let tag = ctx.insert_tag(names.next().unwrap().as_str());
 
                 Pattern::NamedVariant(
                     name,
@@ -785,7 +831,7 @@ impl Parse for Pattern {
 
             Rule::structStartEnd => {
                 let mut inner = pairs.into_inner();
-                let struct_name = ctx.insert_type(inner.next().unwrap().as_str());
+                let struct_name = ctx.lookup_type(inner.next().unwrap().as_str());
                 let mut start = vec![];
                 let mut end = vec![];
                 let mut name = None;
@@ -820,9 +866,9 @@ impl Parse for Pattern {
             Rule::enumStartEnd => {
                 let mut inner = pairs.into_inner();
                 let mut names = inner.next().unwrap().into_inner();
-                let enum_name = ctx.insert_type(dbg!(names.next().unwrap().as_str()));
+                let enum_name = ctx.lookup_type(names.next().unwrap().as_str());
 
-                let tag = ctx.insert_tag(dbg!(names.next().unwrap().as_str()));
+                let tag = ctx.insert_tag(names.next().unwrap().as_str());
 
                 let mut start = vec![];
                 let mut end = vec![];
@@ -858,7 +904,7 @@ impl Parse for Pattern {
 
             Rule::structStartEndDontCare => {
                 let mut inner = pairs.into_inner();
-                let struct_name = ctx.insert_type(inner.next().unwrap().as_str());
+                let struct_name = ctx.lookup_type(inner.next().unwrap().as_str());
                 let mut start = vec![];
                 let mut end = vec![];
                 let mut has_seen = false;
@@ -884,9 +930,9 @@ impl Parse for Pattern {
             Rule::enumStartEndDontCare => {
                 let mut inner = pairs.into_inner();
                 let mut names = inner.next().unwrap().into_inner();
-                let enum_name = ctx.insert_type(dbg!(names.next().unwrap().as_str()));
+                let enum_name = ctx.lookup_type(names.next().unwrap().as_str());
 
-                let tag = ctx.insert_tag(dbg!(names.next().unwrap().as_str()));
+                let tag = ctx.insert_tag(names.next().unwrap().as_str());
 
                 let mut start = vec![];
                 let mut end = vec![];
@@ -1071,7 +1117,7 @@ impl Parse for Pattern {
 
                 for val in inner {
                     match val.as_rule() {
-                        Rule::manyvar => {}
+                        Rule::many => {}
                         _ => {
                             start.push(Self::parse(val, ctx));
                         }
@@ -1149,36 +1195,91 @@ impl Parse for Match {
 }
 
 use crate::language::repr::Representation;
+fn patterns_repr(patterns: &[Pattern], context: &ParseCtx) -> Vec<String> {
+    patterns.iter().map(|x| x.get_repr(context)).collect()
+}
+
+fn many_repr(name: &Option<usize>, context: &ParseCtx) -> String {
+    match name {
+        Some(name) => format!("${}", context.lookup_var_name(*name)),
+        None => "$".to_string(),
+    }
+}
+
+fn start_end_repr(
+    start: &[Pattern],
+    name: &Option<usize>,
+    end: &[Pattern],
+    context: &ParseCtx,
+) -> String {
+    let mut ret = patterns_repr(start, context);
+    ret.push(many_repr(name, context));
+    ret.extend(patterns_repr(end, context));
+    format!("({})", ret.join(" "))
+}
+
+fn binding_repr(kind: &str, name: &Option<usize>, context: &ParseCtx) -> String {
+    match name {
+        Some(name) => format!("{kind}({})", context.lookup_var_name(*name)),
+        None => format!("{kind}(_)"),
+    }
+}
+
 impl Representation<(), ParseCtx> for Pattern {
     fn get_repr(&self, context: &ParseCtx) -> String {
         match self {
             Pattern::DontCare => "_".to_string(),
             Pattern::Int(i) => format!("{i}"),
-            Pattern::Float(i) => format!("{i}"),
+            Pattern::Float(i) => rational_repr(i),
+            Pattern::Complex(re, im) => NumberFormat::Fraction.format_complex(re, im),
             Pattern::Bool(i) => format!("{i}"),
             Pattern::Variable(i) => context.lookup_var_name(*i),
-            Pattern::TypeBool(None) => "todo!()".to_string(),
-            Pattern::TypeBool(Some(_i)) => "todo!()".to_string(),
-            Pattern::TypeInt(None) => "todo!()".to_string(),
-            Pattern::TypeInt(Some(_i)) => "todo!()".to_string(),
-            Pattern::TypeFloat(None) => "todo!()".to_string(),
-            Pattern::TypeFloat(Some(_i)) => "todo!()".to_string(),
-            Pattern::TypeStack(None) => "todo!()".to_string(),
-            Pattern::TypeStack(Some(_i)) => "todo!()".to_string(),
-            Pattern::TypeList(_) => "todo!()".to_string(),
-            Pattern::TypeSet(_) => "todo!()".to_string(),
-            Pattern::TypeMap(_) => "todo!()".to_string(),
+            Pattern::TypeBool(name) => binding_repr("Bool", name, context),
+            Pattern::TypeInt(name) => binding_repr("Int", name, context),
+            Pattern::TypeFloat(name) => binding_repr("Rat", name, context),
+            Pattern::TypeComplex(name) => binding_repr("Complex", name, context),
+            Pattern::TypeStack(name) => binding_repr("Quote", name, context),
+            Pattern::TypeList(ListPattern::All(name)) => {
+                format!("List({})", many_repr(name, context))
+            }
+            Pattern::TypeList(ListPattern::FullList(patterns)) => {
+                format!("List({})", patterns_repr(patterns, context).join(" "))
+            }
+            Pattern::TypeList(ListPattern::StartEnd(start, name, end)) => {
+                format!("List{}", start_end_repr(start, name, end, context))
+            }
+            Pattern::TypeSet(SetPattern::All(name)) => {
+                format!("Set({})", many_repr(name, context))
+            }
+            Pattern::TypeSet(SetPattern::FullSet(patterns)) => {
+                format!("Set({})", patterns_repr(patterns, context).join(" "))
+            }
+            Pattern::TypeSet(SetPattern::Front(start, name)) => {
+                format!("Set{}", start_end_repr(start, name, &[], context))
+            }
+            Pattern::TypeMap(name) => format!("Map({})", many_repr(name, context)),
             Pattern::NamedStruct(t, p) => {
-                format!("{},({})", context.lookup_type_name(*t), p.get_repr(context))
+                format!("{}{}", context.lookup_type_name(*t), p.get_repr(context))
             }
-            Pattern::NamedVariant(_, _, _) => "todo!()".to_string(),
+            Pattern::NamedVariant(t, tag, p) => format!(
+                "{}::{}{}",
+                context.lookup_type_name(*t),
+                context.lookup_tag_name(*tag),
+                p.get_repr(context)
+            ),
         }
     }
 }
 
 impl Representation<(), ParseCtx> for TypePattern {
     fn get_repr(&self, context: &ParseCtx) -> String {
-        "todo!()".to_string()
+        match self {
+            TypePattern::All(name) => format!("({})", many_repr(name, context)),
+            TypePattern::FullList(patterns) => {
+                format!("({})", patterns_repr(patterns, context).join(" "))
+            }
+            TypePattern::StartEnd(start, name, end) => start_end_repr(start, name, end, context),
+        }
     }
 }
 
@@ -1188,23 +1289,29 @@ impl Representation<(), ParseCtx> for MatchElem {
         result.push_str("| ");
         self.pattern
             .iter()
-            .for_each(|x| result.push_str(&format!(" {} ", x.get_repr(context))));
+            .for_each(|x| result.push_str(&format!("{} ", x.get_repr(context))));
+        if !self.cond.elems.is_empty() {
+            result.push_str("when ");
+            self.cond
+                .elems
+                .iter()
+                .for_each(|x| result.push_str(&format!("{} ", x.get_repr(context))));
+        }
         result.push_str("=> ");
         self.body
             .elems
             .iter()
-            .for_each(|x| result.push_str(&format!(" {} ", x.get_repr(context))));
-        result.push_str(", ");
+            .for_each(|x| result.push_str(&format!("{} ", x.get_repr(context))));
+        result.push(',');
         result
     }
 }
 impl Representation<(), ParseCtx> for Match {
     fn get_repr(&self, context: &ParseCtx) -> String {
-        let mut result = String::new();
-        result.push_str("match ");
         self.elems
             .iter()
-            .for_each(|x| result.push_str(&format!(" {} ", x.get_repr(context))));
-        result
+            .map(|x| x.get_repr(context))
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 }
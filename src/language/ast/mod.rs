@@ -1,13 +1,22 @@
-use malachite::{Integer, Rational};
+use malachite::num::arithmetic::traits::Pow;
+use malachite::num::conversion::traits::FromStringBase;
+use malachite::{Integer, Natural, Rational};
+
+use super::number::NumberFormat;
+use super::units;
 
 pub mod iftrue;
 pub mod list;
 pub mod map;
 pub mod match_block;
+pub mod matrix;
+pub mod poly;
 pub mod primitives;
 pub mod set;
 pub mod stack;
 pub mod take;
+pub mod try_catch;
+pub mod vector;
 pub mod while_block;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
@@ -15,6 +24,11 @@ pub enum Type {
     Bool,
     Integer,
     Float,
+    Complex,
+    Vector,
+    Matrix,
+    Poly,
+    Quantity,
     Stack,
     List,
     Set,
@@ -22,18 +36,35 @@ pub enum Type {
 
     CustomType(usize),
     GenericTyp(usize),
+    Applied(usize, Vec<Type>),
+    Constrained(usize, usize),
 }
 
 impl Parse for Type {
     fn parse(pairs: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> Self {
-        dbg!(pairs.as_str());
         match pairs.as_rule() {
-            Rule::typName => Type::CustomType(ctx.insert_type(pairs.as_str())),
+            Rule::typName => Type::CustomType(ctx.lookup_type(pairs.as_str())),
             Rule::genericName => Type::GenericTyp(ctx.insert_type(pairs.as_str())),
+            Rule::constrainedGeneric => {
+                let mut inner = pairs.into_inner();
+                let generic = ctx.insert_type(inner.next().unwrap().as_str());
+                let interface = ctx.lookup_type(inner.next().unwrap().as_str());
+                Type::Constrained(generic, interface)
+            }
+            Rule::typeApp => {
+                let mut inner = pairs.into_inner();
+                let name = ctx.lookup_type(inner.next().unwrap().as_str());
+                Type::Applied(name, inner.map(|x| Type::parse(x, ctx)).collect())
+            }
             Rule::primTyps => match pairs.as_str() {
                 "Bool" => Type::Bool,
                 "Int" => Type::Integer,
                 "Rat" => Type::Float,
+                "Complex" => Type::Complex,
+                "Vec" => Type::Vector,
+                "Mat" => Type::Matrix,
+                "Poly" => Type::Poly,
+                "Quantity" => Type::Quantity,
                 "Stack" => Type::Stack,
                 "List" => Type::List,
                 "Set" => Type::Set,
@@ -49,29 +80,76 @@ impl Parse for Type {
 }
 
 impl Type {
-    pub fn match_values(&self, val: &Values, generics: &mut HashMap<usize, Values>) -> bool {
-        match (self, val) {
-            (Type::Bool, Values::Bool(_))
-            | (Type::Integer, Values::Int(_))
-            | (Type::Float, Values::Float(_))
-            | (Type::Stack, Values::Stack(_))
-            | (Type::List, Values::List(_))
-            | (Type::Set, Values::Set(_))
-            | (Type::Map, Values::Map(_)) => true,
-            (Type::CustomType(name1), Values::Custom { name, .. }) => name1 == name,
-            (Type::GenericTyp(name), other) => match generics.entry(*name) {
-                std::collections::hash_map::Entry::Occupied(x) => {
-                    x.get().get_real_type() == other.get_real_type()
-                }
+    pub fn match_values(
+        &self,
+        val: &Values,
+        generics: &mut HashMap<usize, Type>,
+        env: &super::env::Env,
+    ) -> bool {
+        self.bind(&env.type_of(val), generics, env)
+    }
+
+    /// Checks `actual` against `self`, recording what each generic parameter
+    /// stands for. `GenericTyp(usize::MAX)` is a wildcard and never binds.
+    pub fn bind(
+        &self,
+        actual: &Type,
+        generics: &mut HashMap<usize, Type>,
+        env: &super::env::Env,
+    ) -> bool {
+        match (self, actual) {
+            (Type::GenericTyp(usize::MAX), _) => true,
+            (Type::Constrained(name, interface), actual) => {
+                env.implements(actual, *interface)
+                    && Type::GenericTyp(*name).bind(actual, generics, env)
+            }
+            (Type::GenericTyp(name), actual) => match generics.entry(*name) {
+                std::collections::hash_map::Entry::Occupied(mut x) => match x.get().unify(actual) {
+                    Some(unified) => {
+                        x.insert(unified);
+                        true
+                    }
+                    None => false,
+                },
                 std::collections::hash_map::Entry::Vacant(pos) => {
-                    pos.insert(other.clone());
+                    pos.insert(actual.clone());
                     true
                 }
             },
-            x => {
-                dbg!(x);
-                false
+            (Type::CustomType(name1), Type::CustomType(name2) | Type::Applied(name2, _)) => {
+                name1 == name2
             }
+            (Type::Applied(name1, args1), Type::Applied(name2, args2)) => {
+                name1 == name2
+                    && args1.len() == args2.len()
+                    && args1
+                        .iter()
+                        .zip(args2.iter())
+                        .all(|(formal, actual)| formal.bind(actual, generics, env))
+            }
+            (formal, actual) => formal.unify(actual).is_some(),
+        }
+    }
+
+    /// Returns the most specific type compatible with both sides, treating
+    /// `GenericTyp(usize::MAX)` as a not yet known type.
+    pub fn unify(&self, other: &Type) -> Option<Type> {
+        match (self, other) {
+            (Type::GenericTyp(usize::MAX), x) | (x, Type::GenericTyp(usize::MAX)) => {
+                Some(x.clone())
+            }
+            (Type::Applied(name1, args1), Type::Applied(name2, args2))
+                if name1 == name2 && args1.len() == args2.len() =>
+            {
+                let args = args1
+                    .iter()
+                    .zip(args2.iter())
+                    .map(|(a, b)| a.unify(b))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Type::Applied(*name1, args))
+            }
+            (a, b) if a == b => Some(a.clone()),
+            _ => None,
         }
     }
 }
@@ -80,6 +158,7 @@ impl Type {
 pub enum Ast {
     Return,
     Break,
+    Throw,
 
     PrimitiveCall(primitives::Primitives),
     Call(usize),
@@ -93,40 +172,124 @@ pub enum Ast {
     Take(take::Take),
     IfTrue(iftrue::IfTrue),
     Match(match_block::Match),
+    TryCatch(try_catch::TryCatch),
 
     Bool(bool),
     Int(Integer),
     Float(Rational),
+    Complex(Rational, Rational),
+    Quantity(Box<units::Quantity>),
 
     Stack(stack::Stack),
     List(list::List),
+    Vector(vector::Vector),
+    Matrix(matrix::Matrix),
+    Poly(poly::Poly),
     Set(set::Set),
     Map(map::Map),
 
     Var(usize),
+    /// Multiplies the top of the stack by a unit raised to a power, `km` or
+    /// `s^-2`.
+    Unit(usize, i32),
+    /// `to km`, shows a quantity in the product of the units.
+    Convert(Vec<(usize, i32)>),
 }
 
 use crate::language::ast::iftrue::IfTrue;
 use crate::language::ast::list::List;
 use crate::language::ast::match_block::Match;
+use crate::language::ast::matrix::Matrix;
+use crate::language::ast::poly::Poly;
 use crate::language::ast::primitives::Primitives;
 use crate::language::ast::stack::Stack;
 use crate::language::ast::take::Take;
+use crate::language::ast::try_catch::TryCatch;
+use crate::language::ast::vector::Vector;
 use crate::language::ast::while_block::While;
 use crate::language::parse::{Parse, ParseCtx, Rule};
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 
+/// Parses a `float` (`1.25`) or `fraction` (`5/4`) literal.
+/// Reads an `integer` literal, which may be written in hex, octal or binary
+/// and have `_` between digits.
+pub fn parse_integer(pair: pest::iterators::Pair<'_, Rule>) -> Integer {
+    let text = pair.as_str().replace('_', "");
+    let (negative, digits) = match text.strip_prefix(['+', '-']) {
+        Some(rest) => (text.starts_with('-'), rest),
+        None => (false, text.as_str()),
+    };
+    let (base, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    let n = Integer::from(Natural::from_string_base(base, digits).unwrap());
+    if negative { -n } else { n }
+}
+
+pub fn parse_rational(pair: pest::iterators::Pair<'_, Rule>) -> Rational {
+    let text = pair.as_str().replace('_', "");
+    match pair.as_rule() {
+        Rule::float => {
+            let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+                Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().unwrap()),
+                None => (text.as_str(), 0),
+            };
+            let r: Vec<_> = mantissa.split(".").chain([""]).collect();
+            let l = r[1].len();
+            let nom = format!("{}{}/1{}", r[0], r[1], "0".repeat(l));
+            Rational::from_str(&nom).unwrap() * Rational::from(10).pow(exponent)
+        }
+        Rule::fraction => Rational::from_str(&text).unwrap(),
+        Rule::integer => Rational::from(parse_integer(pair)),
+        _ => unreachable!(),
+    }
+}
+
+/// Reads a `complex` literal, `3+4i` or just the imaginary part `4i`.
+pub fn parse_complex(pair: pest::iterators::Pair<'_, Rule>) -> (Rational, Rational) {
+    let parts: Vec<_> = pair.into_inner().map(parse_rational).collect();
+    match &parts[..] {
+        [im] => (Rational::from(0), im.clone()),
+        [re, im] => (re.clone(), im.clone()),
+        _ => unreachable!(),
+    }
+}
+
+/// A `unitFactor` or `unitPower`, `km` or `s^-2`, as unit id and power.
+fn parse_unit_factor(pair: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> (usize, i32) {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str();
+    let unit = ctx
+        .lookup_unit(name)
+        .unwrap_or_else(|| ctx.insert_fun(name));
+    let power = inner.next().map_or(1, |x| x.as_str().parse().unwrap());
+    (unit, power)
+}
+
+/// Prints a rational as a literal that parses back to the same `Rat`:
+/// integral values keep a `.0` so they don't come back as `Int`.
+pub fn rational_repr(r: &Rational) -> String {
+    let ret = format!("{r}");
+    if ret.contains('/') {
+        ret
+    } else {
+        format!("{ret}.0")
+    }
+}
+
 impl Parse for Ast {
     fn parse(pairs: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> Self {
         match pairs.as_rule() {
-            Rule::integer => Ast::Int(Integer::from_str(pairs.as_str()).unwrap()),
-            Rule::float => {
-                let r: Vec<_> = pairs.as_str().split(".").collect();
-                let l = r[1].len();
-                let nom = format!("{}{}/1{}", r[0], r[1], "0".repeat(l));
-                Ast::Float(Rational::from_str(&nom).unwrap())
+            Rule::integer => Ast::Int(parse_integer(pairs)),
+            Rule::float | Rule::fraction => Ast::Float(parse_rational(pairs)),
+            Rule::complex => {
+                let (re, im) = parse_complex(pairs);
+                Ast::Complex(re, im)
             }
             Rule::bools => Ast::Bool("true" == pairs.as_str()),
             Rule::primitives => Ast::PrimitiveCall(Primitives::parse(pairs, ctx)),
@@ -134,10 +297,25 @@ impl Parse for Ast {
                 let name = pairs.as_str();
                 if let Some(var_id) = ctx.lookup_var_in_scope(name) {
                     Ast::Var(var_id)
+                } else if let Some(unit) = ctx.lookup_unit(name) {
+                    Ast::Unit(unit, 1)
                 } else {
-                    Ast::Call(ctx.insert_fun(name))
+                    Ast::Call(ctx.lookup_fun(name))
                 }
             }
+            Rule::qualifiedName => Ast::Call(ctx.insert_fun(pairs.as_str())),
+            Rule::upperUnit => Ast::Unit(ctx.insert_fun(pairs.as_str()), 1),
+            Rule::unitPower => {
+                let (unit, power) = parse_unit_factor(pairs, ctx);
+                Ast::Unit(unit, power)
+            }
+            Rule::convert => Ast::Convert(
+                pairs
+                    .into_inner()
+                    .skip(1)
+                    .map(|x| parse_unit_factor(x, ctx))
+                    .collect(),
+            ),
             Rule::take => Ast::Take(Take::parse(pairs, ctx)),
             Rule::whileLoop => Ast::While(While::parse(pairs, ctx)),
             Rule::ifTrue => Ast::IfTrue(IfTrue::parse(pairs, ctx)),
@@ -145,13 +323,18 @@ impl Parse for Ast {
             Rule::matchBlock => Ast::Match(Match::parse(pairs, ctx)),
             Rule::ret => Ast::Return,
             Rule::brek => Ast::Break,
+            Rule::throw => Ast::Throw,
+            Rule::tryCatch => Ast::TryCatch(TryCatch::parse(pairs, ctx)),
             Rule::list => Ast::List(List::parse(pairs, ctx)),
+            Rule::vector => Ast::Vector(Vector::parse(pairs, ctx)),
+            Rule::matrix => Ast::Matrix(Matrix::parse(pairs, ctx)),
+            Rule::poly => Ast::Poly(Poly::parse(pairs, ctx)),
             Rule::set => Ast::Set(Set::parse(pairs, ctx)),
             Rule::map => Ast::Map(Map::parse(pairs, ctx)),
             Rule::variantInst => {
                 let mut def = pairs.into_inner();
                 let mut names = def.next().unwrap().into_inner();
-                let type_name = ctx.insert_type(names.next().unwrap().as_str());
+                let type_name = ctx.lookup_type(names.next().unwrap().as_str());
                 let tag_name = ctx.insert_tag(names.next().unwrap().as_str());
                 let elements = Stack {
                     elems: def.map(|x| Ast::parse(x, ctx)).collect::<Arc<_>>(),
@@ -160,7 +343,7 @@ impl Parse for Ast {
             }
             Rule::typeInst => {
                 let mut def = pairs.into_inner();
-                let type_name = ctx.insert_type(def.next().unwrap().as_str());
+                let type_name = ctx.lookup_type(def.next().unwrap().as_str());
                 let elements = Stack {
                     elems: def.map(|x| Ast::parse(x, ctx)).collect::<Arc<_>>(),
                 };
@@ -181,7 +364,7 @@ use crate::language::repr::Representation;
 use self::map::Map;
 use self::set::Set;
 
-use super::eval::{Eval, EvalError, Flow, Values};
+use super::eval::{Eval, EvalError, Flow, ValueStack, Values};
 
 impl Representation<(), ParseCtx> for Type {
     fn get_repr(&self, context: &ParseCtx) -> String {
@@ -189,12 +372,32 @@ impl Representation<(), ParseCtx> for Type {
             Type::Bool => "Bool".to_string(),
             Type::Integer => "Int".to_string(),
             Type::Float => "Rat".to_string(),
+            Type::Complex => "Complex".to_string(),
+            Type::Vector => "Vec".to_string(),
+            Type::Matrix => "Mat".to_string(),
+            Type::Poly => "Poly".to_string(),
+            Type::Quantity => "Quantity".to_string(),
             Type::Stack => "Stack".to_string(),
             Type::List => "List".to_string(),
             Type::Set => "Set".to_string(),
             Type::Map => "Map".to_string(),
             Type::CustomType(t) => context.lookup_type_name(*t),
-            Type::GenericTyp(_t) => format!("??"),
+            Type::GenericTyp(t) => context
+                .lookup_type_name_maybe(*t)
+                .unwrap_or_else(|| "??".to_string()),
+            Type::Constrained(t, interface) => format!(
+                "{}: {}",
+                context.lookup_type_name(*t),
+                context.lookup_type_name(*interface)
+            ),
+            Type::Applied(t, args) => format!(
+                "{}<{}>",
+                context.lookup_type_name(*t),
+                args.iter()
+                    .map(|x| x.get_repr(context))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -203,6 +406,9 @@ impl Representation<(), ParseCtx> for Ast {
     fn get_repr(&self, context: &ParseCtx) -> String {
         match self {
             Ast::List(l) => l.get_repr(context),
+            Ast::Vector(l) => l.get_repr(context),
+            Ast::Matrix(l) => l.get_repr(context),
+            Ast::Poly(l) => l.get_repr(context),
             Ast::Set(l) => l.get_repr(context),
             Ast::Map(l) => l.get_repr(context),
 
@@ -211,25 +417,43 @@ impl Representation<(), ParseCtx> for Ast {
             Ast::Stack(s) => s.get_repr(context),
             Ast::IfTrue(s) => s.get_repr(context),
             Ast::Match(s) => s.get_repr(context),
+            Ast::TryCatch(s) => s.get_repr(context),
             Ast::Call(x) => context.lookup_call_name(*x),
             Ast::Var(x) => context.lookup_var_name(*x),
+            Ast::Unit(x, power) => units::power_repr(&context.lookup_call_name(*x), *power),
+            Ast::Convert(target) => {
+                let factors: Vec<_> = target
+                    .iter()
+                    .map(|(x, power)| units::power_repr(&context.lookup_call_name(*x), *power))
+                    .collect();
+                match &factors[..] {
+                    [factor] => format!("to {factor}"),
+                    _ => format!("to ({})", factors.join(" ")),
+                }
+            }
             Ast::PrimitiveCall(p) => p.get_repr(context),
-            Ast::Float(i) => format!("{i}"),
+            Ast::Float(i) => rational_repr(i),
+            Ast::Complex(re, im) => NumberFormat::Fraction.format_complex(re, im),
+            Ast::Quantity(q) => NumberFormat::Fraction.format_quantity(q),
             Ast::Int(i) => format!("{i}"),
-            Ast::Bool(true) => "false".to_string(),
-            Ast::Bool(false) => "true".to_string(),
+            Ast::Bool(i) => format!("{i}"),
             Ast::Break => "break".to_string(),
             Ast::Return => "return".to_string(),
+            Ast::Throw => "throw".to_string(),
             Ast::TypeCall(name, Some(variant), data) => {
                 let mut ret = data.get_repr(context);
                 ret.pop();
-                format!("{name}::{variant}( {} )", &ret[1..])
+                format!(
+                    "{}::{}({})",
+                    context.lookup_type_name(*name),
+                    context.lookup_tag_name(*variant),
+                    &ret[1..]
+                )
             }
             Ast::TypeCall(name, None, data) => {
                 let mut ret = data.get_repr(context);
                 ret.pop();
-
-                format!("{name}( {} )", &ret[1..])
+                format!("{}({})", context.lookup_type_name(*name), &ret[1..])
             }
         }
     }
@@ -238,7 +462,7 @@ impl Representation<(), ParseCtx> for Ast {
 impl Eval<Flow> for Ast {
     fn eval(
         &self,
-        values: &mut Vec<super::eval::Values>,
+        values: &mut super::eval::ValueStack,
         env: &super::env::Env,
         vars: &mut super::eval::ChainMap,
     ) -> Result<Flow, super::eval::EvalError> {
@@ -247,7 +471,11 @@ impl Eval<Flow> for Ast {
             Ast::IfTrue(block) => block.eval(values, env, vars),
             Ast::Take(take) => take.eval(values, env, vars),
             Ast::Match(arms) => arms.eval(values, env, vars),
+            Ast::TryCatch(block) => block.eval(values, env, vars),
             Ast::List(l) => l.eval(values, env, vars),
+            Ast::Vector(l) => l.eval(values, env, vars),
+            Ast::Matrix(l) => l.eval(values, env, vars),
+            Ast::Poly(l) => l.eval(values, env, vars),
             Ast::Set(s) => s.eval(values, env, vars),
             Ast::Map(s) => s.eval(values, env, vars),
 
@@ -269,47 +497,27 @@ impl Eval<Flow> for Ast {
 
                 match arity {
                     Some(arity) => {
-                        if values.len() < arity.0 {
-                            dbg!(values.len());
-                            dbg!(arity);
-
-                            return Err(EvalError::Underflow);
-                        }
-                        let mut temp = vec![];
-
-                        for i in 0..(arity.0) {
-                            temp.push(values[values.len() - i - 1].to_owned());
-                        }
-                        temp.reverse();
                         let arms = env.protocol_data.get(fun_name).unwrap();
-                        for tmp in arms {
-                            let (types, act) = tmp;
-                            if types.iter().zip(temp.clone().into_iter()).all(|(a, b)| {
-                                match (a, b) {
-                                    (Type::Bool, Values::Bool(_)) => true,
-                                    (Type::Integer, Values::Int(_)) => true,
-                                    (Type::Float, Values::Float(_)) => true,
-                                    (Type::Stack, Values::Stack(_)) => true,
-                                    (Type::List, Values::List(_)) => true,
-                                    (Type::Set, Values::Set(_)) => true,
-                                    (Type::Map, Values::Map(_)) => true,
-                                    (Type::CustomType(name1), Values::Custom { name, .. })
-                                        if name1 == &name =>
-                                    {
-                                        true
-                                    }
-                                    (Type::GenericTyp(_), _) => true,
-                                    (a, b) => {
-                                        dbg!((a, b));
-                                        false
-                                    }
-                                }
-                            }) {
+                        // Overloads defined in the language always take the
+                        // full arity, but built-ins may take fewer arguments,
+                        // like `min` of a `List`; full ones are tried first.
+                        let full = arms.iter().filter(|(types, _)| types.len() == arity.0);
+                        let short = arms.iter().filter(|(types, _)| types.len() != arity.0);
+                        for (types, act) in full.chain(short) {
+                            if values.len() < types.len() {
+                                continue;
+                            }
+                            let args = values.top(types.len());
+                            let mut generics = HashMap::new();
+                            if types
+                                .iter()
+                                .zip(args)
+                                .all(|(a, b)| a.match_values(b, &mut generics, env))
+                            {
                                 let (_, act) = act;
                                 return act.eval(values, env, vars);
                             }
                         }
-                        dbg!("UNDERFLOOOOOOOW");
                         Err(EvalError::Underflow)
                     }
 
@@ -328,6 +536,32 @@ impl Eval<Flow> for Ast {
                 values.push(Values::Float(f.clone()));
                 Ok(Flow::Ok)
             }
+            Ast::Complex(re, im) => {
+                values.push(Values::Complex(re.clone(), im.clone()));
+                Ok(Flow::Ok)
+            }
+            Ast::Quantity(q) => {
+                values.push(Values::Quantity(q.clone()));
+                Ok(Flow::Ok)
+            }
+            Ast::Unit(unit, power) => {
+                let value = values.pop().ok_or(EvalError::Underflow)?;
+                let unit = env.units.get(unit).ok_or(EvalError::UndefinedCall(*unit))?;
+                values.push(units::apply(value, unit, *power)?);
+                Ok(Flow::Ok)
+            }
+            Ast::Convert(target) => {
+                let value = values.pop().ok_or(EvalError::Underflow)?;
+                let target = target
+                    .iter()
+                    .map(|(unit, power)| match env.units.get(unit) {
+                        Some(unit) => Ok((unit, *power)),
+                        None => Err(EvalError::UndefinedCall(*unit)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                values.push(units::convert(value, units::target(target.into_iter())?)?);
+                Ok(Flow::Ok)
+            }
             Ast::Int(i) => {
                 values.push(Values::Int(i.clone()));
                 Ok(Flow::Ok)
@@ -345,9 +579,13 @@ impl Eval<Flow> for Ast {
             }
             Ast::Return => Ok(Flow::Ret),
             Ast::Break => Ok(Flow::Break),
+            Ast::Throw => match values.pop() {
+                Some(value) => Err(EvalError::Thrown(value)),
+                None => Err(EvalError::Underflow),
+            },
 
             Ast::TypeCall(typ_name, tag, constructor) => {
-                let mut temp = vec![];
+                let mut temp = ValueStack::default();
                 dbg!(&constructor);
                 constructor.eval(&mut temp, env, vars)?;
                 match env.typ_data.get(&(*typ_name, *tag)) {
@@ -358,7 +596,7 @@ impl Eval<Flow> for Ast {
                             ret = types
                                 .iter()
                                 .zip(temp.iter())
-                                .all(|(ty, val)| ty.match_values(val, &mut generics));
+                                .all(|(ty, val)| ty.match_values(val, &mut generics, env));
                         }
                         if ret {
                             if temp.is_empty() {
@@ -378,7 +616,7 @@ impl Eval<Flow> for Ast {
                         } else {
                             dbg!(&types);
                             dbg!(&temp);
-                            Err(EvalError::TypeDoesntExist(*typ_name))
+                            Err(EvalError::TypeConstructorTypeMismatch(*typ_name))
                         }
                     }
                     Some(types) => {
@@ -386,8 +624,8 @@ impl Eval<Flow> for Ast {
                         dbg!(&temp);
                         Err(EvalError::TypeConstructorLenMismatch(
                             *typ_name,
-                            temp.len(),
                             types.len(),
+                            temp.len(),
                         ))
                     }
                     None => {
@@ -407,23 +645,63 @@ impl Eval<Flow> for Ast {
             Ast::Stack(w) => w.get_free_vars(vars),
             Ast::IfTrue(w) => w.get_free_vars(vars),
             Ast::Match(w) => w.get_free_vars(vars),
+            Ast::TryCatch(w) => w.get_free_vars(vars),
             Ast::Call(_) => (),
             Ast::Var(w) => {
                 vars.insert(*w);
             }
             Ast::PrimitiveCall(w) => w.get_free_vars(vars),
             Ast::Float(_) => (),
+            Ast::Complex(..) => (),
+            Ast::Quantity(_) | Ast::Unit(..) | Ast::Convert(_) => (),
             Ast::Int(_) => (),
             Ast::Bool(_) => (),
             Ast::List(w) => w.get_free_vars(vars),
+            Ast::Vector(w) => w.get_free_vars(vars),
+            Ast::Matrix(w) => w.get_free_vars(vars),
+            Ast::Poly(w) => w.get_free_vars(vars),
             Ast::Set(w) => w.get_free_vars(vars),
             Ast::Map(w) => w.get_free_vars(vars),
             Ast::Return => (),
             Ast::Break => (),
+            Ast::Throw => (),
             Ast::TypeCall(_, _, w) => w.get_free_vars(vars),
         }
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        match self {
+            Ast::While(w) => w.get_calls(calls),
+            Ast::Take(w) => w.get_calls(calls),
+            Ast::Stack(w) => w.get_calls(calls),
+            Ast::IfTrue(w) => w.get_calls(calls),
+            Ast::Match(w) => w.get_calls(calls),
+            Ast::TryCatch(w) => w.get_calls(calls),
+            Ast::Call(w) => {
+                calls.insert(*w);
+            }
+            Ast::List(w) => w.get_calls(calls),
+            Ast::Vector(w) => w.get_calls(calls),
+            Ast::Matrix(w) => w.get_calls(calls),
+            Ast::Poly(w) => w.get_calls(calls),
+            Ast::Set(w) => w.get_calls(calls),
+            Ast::Map(w) => w.get_calls(calls),
+            Ast::TypeCall(_, _, w) => w.get_calls(calls),
+            Ast::PrimitiveCall(_)
+            | Ast::Float(_)
+            | Ast::Complex(..)
+            | Ast::Quantity(_)
+            | Ast::Unit(..)
+            | Ast::Convert(_)
+            | Ast::Int(_)
+            | Ast::Bool(_)
+            | Ast::Var(_)
+            | Ast::Return
+            | Ast::Break
+            | Ast::Throw => (),
+        }
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         match self {
             Ast::TypeCall(_, _, w) => w.get_vars(vars),
@@ -432,19 +710,26 @@ impl Eval<Flow> for Ast {
             Ast::Stack(w) => w.get_vars(vars),
             Ast::IfTrue(w) => w.get_vars(vars),
             Ast::Match(w) => w.get_vars(vars),
+            Ast::TryCatch(w) => w.get_vars(vars),
             Ast::Call(_) => (),
             Ast::Var(w) => {
                 vars.insert(*w);
             }
             Ast::PrimitiveCall(w) => w.get_vars(vars),
             Ast::Float(_) => (),
+            Ast::Complex(..) => (),
+            Ast::Quantity(_) | Ast::Unit(..) | Ast::Convert(_) => (),
             Ast::Int(_) => (),
             Ast::Bool(_) => (),
             Ast::List(w) => w.get_vars(vars),
+            Ast::Vector(w) => w.get_vars(vars),
+            Ast::Matrix(w) => w.get_vars(vars),
+            Ast::Poly(w) => w.get_vars(vars),
             Ast::Set(w) => w.get_vars(vars),
             Ast::Map(w) => w.get_vars(vars),
             Ast::Return => (),
             Ast::Break => (),
+            Ast::Throw => (),
         }
     }
 
@@ -462,6 +747,7 @@ impl Eval<Flow> for Ast {
             Ast::Stack(x) => Ast::Stack(x.replace_vars(free_vars, vars)),
             Ast::IfTrue(x) => Ast::IfTrue(x.replace_vars(free_vars, vars)),
             Ast::Match(x) => Ast::Match(x.replace_vars(free_vars, vars)),
+            Ast::TryCatch(x) => Ast::TryCatch(x.replace_vars(free_vars, vars)),
             Ast::Var(i) if free_vars.contains(&i) => {
                 match vars.lookup(&i) {
                     Some(val) => val.into(),
@@ -473,6 +759,9 @@ impl Eval<Flow> for Ast {
             }
             Ast::PrimitiveCall(x) => Ast::PrimitiveCall(x.replace_vars(free_vars, vars)),
             Ast::List(x) => Ast::List(x.replace_vars(free_vars, vars)),
+            Ast::Vector(x) => Ast::Vector(x.replace_vars(free_vars, vars)),
+            Ast::Matrix(x) => Ast::Matrix(x.replace_vars(free_vars, vars)),
+            Ast::Poly(x) => Ast::Poly(x.replace_vars(free_vars, vars)),
             Ast::Set(x) => Ast::Set(x.replace_vars(free_vars, vars)),
             Ast::Map(x) => Ast::Map(x.replace_vars(free_vars, vars)),
             _ => self,
@@ -484,6 +773,29 @@ impl From<Values> for Ast {
     fn from(value: Values) -> Self {
         match value {
             Values::Float(i) => Ast::Float(i),
+            Values::Complex(re, im) => Ast::Complex(re, im),
+            Values::Quantity(q) => Ast::Quantity(q),
+            Values::Vector(v) => Ast::Vector(
+                Stack {
+                    elems: v.into_iter().map(Ast::Float).collect(),
+                }
+                .into(),
+            ),
+            Values::Matrix(m) => Ast::Matrix(
+                Stack {
+                    elems: m
+                        .rows()
+                        .map(|row| Values::Vector(row.to_vec()).into())
+                        .collect(),
+                }
+                .into(),
+            ),
+            Values::Poly(p) => Ast::Poly(
+                Stack {
+                    elems: p.into_iter().rev().map(Ast::Float).collect(),
+                }
+                .into(),
+            ),
             Values::Int(i) => Ast::Int(i),
             Values::Bool(i) => Ast::Bool(i),
             Values::Stack(i) => Ast::Stack(i),
@@ -493,13 +805,13 @@ impl From<Values> for Ast {
                 }
                 .into(),
             ),
-            Values::Set(i) => Ast::List(
+            Values::Set(i) => Ast::Set(
                 Stack {
                     elems: i.into_iter().map(|x| x.into()).collect(),
                 }
                 .into(),
             ),
-            Values::Map(i) => Ast::List(
+            Values::Map(i) => Ast::Map(
                 Stack {
                     elems: i
                         .into_iter()
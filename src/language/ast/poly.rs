@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use malachite::Rational;
+use malachite::num::basic::traits::One;
+
+use crate::language::{
+    env::Env,
+    eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values},
+    parse::{Parse, ParseCtx, Rule},
+    poly,
+};
+
+use super::{Ast, parse_rational, stack::Stack};
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Poly {
+    elements: Stack,
+}
+
+impl From<Stack> for Poly {
+    fn from(value: Stack) -> Self {
+        Self { elements: value }
+    }
+}
+
+impl Parse for Poly {
+    fn parse(pairs: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> Self {
+        let mut inner = pairs.into_inner().peekable();
+        if let Some(notation) = inner.next_if(|x| x.as_rule() == Rule::polyNotation) {
+            return Self {
+                elements: Stack {
+                    elems: parse_notation(notation)
+                        .into_iter()
+                        .rev()
+                        .map(Ast::Float)
+                        .collect::<Arc<_>>(),
+                },
+            };
+        }
+        Self {
+            elements: Stack {
+                elems: inner.map(|x| Ast::parse(x, ctx)).collect::<Arc<_>>(),
+            },
+        }
+    }
+}
+
+/// Coefficients of a `polyNotation`, lowest power first.
+fn parse_notation(pair: pest::iterators::Pair<'_, Rule>) -> Vec<Rational> {
+    let mut negative = false;
+    let mut terms = vec![];
+    for part in pair.into_inner() {
+        if part.as_rule() == Rule::polySign {
+            negative = part.as_str() == "-";
+            continue;
+        }
+        let mut coefficient = Rational::ONE;
+        let mut power = 0;
+        for x in part.into_inner() {
+            match x.as_rule() {
+                Rule::polyX => {
+                    power = x
+                        .into_inner()
+                        .next()
+                        .map_or(1, |x| x.as_str().parse().unwrap())
+                }
+                _ => coefficient = parse_rational(x),
+            }
+        }
+        if negative {
+            coefficient = -coefficient;
+        }
+        terms.push((coefficient, power));
+        negative = false;
+    }
+    poly::from_terms(terms.into_iter())
+}
+
+use crate::language::repr::Representation;
+impl Representation<(), ParseCtx> for Poly {
+    fn get_repr(&self, context: &ParseCtx) -> String {
+        let mut result = String::new();
+        result.push_str("Poly(");
+        self.elements
+            .elems
+            .iter()
+            .for_each(|x| result.push_str(&format!(" {} ", x.get_repr(context))));
+        result.push(')');
+        result
+    }
+}
+
+impl Eval<Flow> for Poly {
+    fn eval(
+        &self,
+        values: &mut ValueStack,
+        env: &Env,
+        vars: &mut ChainMap,
+    ) -> Result<Flow, EvalError> {
+        let mut result = ValueStack::default();
+        let _res = self.elements.eval(&mut result, env, vars)?;
+        let mut ret: Vec<_> = result
+            .into_iter()
+            .map(poly::entry)
+            .collect::<Result<_, _>>()?;
+        // Written highest power first, stored lowest first.
+        ret.reverse();
+        values.push(Values::Poly(poly::normalize(ret)));
+        Ok(Flow::Ok)
+    }
+
+    fn get_free_vars(&self, vars: &mut std::collections::HashSet<usize>) {
+        self.elements.get_free_vars(vars);
+    }
+
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elements.get_calls(calls);
+    }
+
+    fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
+        self.elements.get_free_vars(vars);
+    }
+
+    fn replace_vars(self, free_vars: &std::collections::HashSet<usize>, vars: &ChainMap) -> Self {
+        let elements = self.elements;
+        let elems = elements.replace_vars(free_vars, vars);
+        Self { elements: elems }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::language::env::Env;
-use crate::language::eval::{ChainMap, Eval, EvalError, Flow, Values};
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack};
 //use malachite::num::arithmetic::traits::*;
 #[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
 pub enum Primitives {
@@ -48,7 +48,7 @@ pub enum Primitives {
 impl Eval<Flow> for Primitives {
     fn eval(
         &self,
-        _values: &mut Vec<Values>,
+        _values: &mut ValueStack,
         _env: &Env,
         _vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -57,6 +57,8 @@ impl Eval<Flow> for Primitives {
 
     fn get_free_vars(&self, _vars: &mut std::collections::HashSet<usize>) {}
 
+    fn get_calls(&self, _calls: &mut std::collections::HashSet<usize>) {}
+
     fn get_vars(&self, _vars: &mut std::collections::HashSet<usize>) {}
 
     fn replace_vars(self, _free_vars: &std::collections::HashSet<usize>, _vars: &ChainMap) -> Self {
@@ -2,7 +2,7 @@ use std::{collections::BTreeSet, sync::Arc};
 
 use crate::language::{
     env::Env,
-    eval::{ChainMap, Eval, EvalError, Flow, Values},
+    eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values},
     parse::{Parse, ParseCtx, Rule},
 };
 
@@ -49,11 +49,11 @@ impl Representation<(), ParseCtx> for Set {
 impl Eval<Flow> for Set {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
-        let mut result = vec![];
+        let mut result = ValueStack::default();
         let _ = self.elements.eval(&mut result, env, vars)?;
         let ret = Values::Set(result.into_iter().collect::<BTreeSet<_>>());
         values.push(ret);
@@ -64,6 +64,10 @@ impl Eval<Flow> for Set {
         self.elements.get_free_vars(vars)
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elements.get_calls(calls)
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.elements.get_vars(vars)
     }
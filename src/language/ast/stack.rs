@@ -1,6 +1,6 @@
 use super::Ast;
 use crate::language::env::Env;
-use crate::language::eval::{ChainMap, Eval, EvalError, Flow, Values};
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack};
 use crate::language::parse::{Parse, ParseCtx, Rule};
 use std::sync::Arc;
 
@@ -19,7 +19,7 @@ impl Default for Stack {
 impl Eval<Flow> for Stack {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -38,6 +38,10 @@ impl Eval<Flow> for Stack {
         self.elems.iter().for_each(|x| x.get_free_vars(vars));
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.elems.iter().for_each(|x| x.get_calls(calls));
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.elems.iter().for_each(|x| x.get_vars(vars));
     }
@@ -1,6 +1,6 @@
 use super::stack::Stack;
 use crate::language::env::Env;
-use crate::language::eval::{ChainMap, Eval, EvalError, Flow, Values};
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack};
 
 #[derive(Debug, Clone, Default, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Take {
@@ -11,7 +11,7 @@ pub struct Take {
 impl Eval<Flow> for Take {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -41,6 +41,10 @@ impl Eval<Flow> for Take {
         *vars = vars.difference(&my_vars).cloned().collect();
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.body.get_calls(calls)
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.body.get_vars(vars)
     }
@@ -0,0 +1,102 @@
+use super::match_block::Match;
+use super::stack::Stack;
+use crate::language::env::Env;
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack};
+
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Ord, Eq)]
+pub struct TryCatch {
+    body: Stack,
+    handler: Match,
+}
+
+impl Eval<Flow> for TryCatch {
+    fn eval(
+        &self,
+        values: &mut ValueStack,
+        env: &Env,
+        vars: &mut ChainMap,
+    ) -> Result<Flow, EvalError> {
+        let saved_values = values.clone();
+        let saved_vars = vars.clone();
+        let err = match self.body.eval(values, env, vars) {
+            ok @ Ok(_) => return ok,
+            Err(err) => err,
+        };
+
+        *values = saved_values;
+        *vars = saved_vars;
+        values.push(err.to_value(env));
+        match self.handler.eval(values, env, vars) {
+            Err(EvalError::NoMatch) => {
+                values.pop();
+                Err(err)
+            }
+            ret => ret,
+        }
+    }
+
+    fn get_free_vars(&self, vars: &mut std::collections::HashSet<usize>) {
+        self.body.get_free_vars(vars);
+        self.handler.get_free_vars(vars);
+    }
+
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.body.get_calls(calls);
+        self.handler.get_calls(calls);
+    }
+
+    fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
+        self.body.get_vars(vars);
+        self.handler.get_vars(vars);
+    }
+
+    fn replace_vars(self, free_vars: &std::collections::HashSet<usize>, vars: &ChainMap) -> Self {
+        let TryCatch {
+            mut body,
+            mut handler,
+        } = self;
+        body = body.replace_vars(free_vars, vars);
+        handler = handler.replace_vars(free_vars, vars);
+        TryCatch { body, handler }
+    }
+}
+
+use crate::language::ast::Ast;
+use std::sync::Arc;
+
+use crate::language::parse::{Parse, ParseCtx, Rule};
+impl Parse for TryCatch {
+    fn parse(pairs: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> Self {
+        let mut inners = pairs
+            .into_inner()
+            .filter(|x| !matches!(x.as_rule(), Rule::tryKw | Rule::catchKw));
+        let block = inners.next().unwrap();
+        let body = match block.as_rule() {
+            Rule::block => Stack {
+                elems: block
+                    .into_inner()
+                    .map(|x| Ast::parse(x, ctx))
+                    .collect::<Arc<_>>(),
+            },
+            _ => unreachable!(),
+        };
+        let handler = Match::parse(inners.next().unwrap(), ctx);
+        TryCatch { body, handler }
+    }
+}
+
+use crate::language::repr::Representation;
+
+impl Representation<(), ParseCtx> for TryCatch {
+    fn get_repr(&self, context: &ParseCtx) -> String {
+        let mut result = String::new();
+        result.push_str("try {");
+        self.body
+            .elems
+            .iter()
+            .for_each(|x| result.push_str(&format!(" {} ", x.get_repr(context))));
+        result.push_str("} catch ");
+        result.push_str(&self.handler.get_repr(context));
+        result
+    }
+}
@@ -1,6 +1,6 @@
 use super::stack::Stack;
 use crate::language::env::Env;
-use crate::language::eval::{ChainMap, Eval, EvalError, Flow, Values};
+use crate::language::eval::{ChainMap, Eval, EvalError, Flow, ValueStack, Values};
 #[derive(Debug, Clone, Default, PartialEq, PartialOrd, Ord, Eq)]
 pub struct While {
     cond: Stack,
@@ -10,7 +10,7 @@ pub struct While {
 impl Eval<Flow> for While {
     fn eval(
         &self,
-        values: &mut Vec<Values>,
+        values: &mut ValueStack,
         env: &Env,
         vars: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -53,6 +53,11 @@ impl Eval<Flow> for While {
         self.body.get_free_vars(vars)
     }
 
+    fn get_calls(&self, calls: &mut std::collections::HashSet<usize>) {
+        self.cond.get_calls(calls);
+        self.body.get_calls(calls)
+    }
+
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>) {
         self.cond.get_vars(vars);
         self.body.get_vars(vars)
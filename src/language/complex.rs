@@ -0,0 +1,77 @@
+use malachite::Rational;
+use malachite::num::basic::traits::Zero;
+
+use super::ast::Type;
+use super::eval::{EvalError, Values};
+use super::math;
+
+/// Operand types of the arithmetic protocols that take a `Complex`: any mix
+/// of `Complex`, `Int` and `Rat` with at least one `Complex`.
+pub const OPERANDS: [[Type; 2]; 5] = [
+    [Type::Complex, Type::Complex],
+    [Type::Complex, Type::Integer],
+    [Type::Integer, Type::Complex],
+    [Type::Complex, Type::Float],
+    [Type::Float, Type::Complex],
+];
+
+/// Real and imaginary part of a `Complex`, `Int` or `Rat`.
+pub fn parts(value: Values) -> (Rational, Rational) {
+    match value {
+        Values::Complex(re, im) => (re, im),
+        Values::Int(i) => (Rational::from(i), Rational::ZERO),
+        Values::Float(f) => (f, Rational::ZERO),
+        _ => unreachable!(),
+    }
+}
+
+pub fn add((a, b): (Rational, Rational), (c, d): (Rational, Rational)) -> Values {
+    Values::Complex(a + c, b + d)
+}
+
+pub fn sub((a, b): (Rational, Rational), (c, d): (Rational, Rational)) -> Values {
+    Values::Complex(a - c, b - d)
+}
+
+pub fn mul((a, b): (Rational, Rational), (c, d): (Rational, Rational)) -> Values {
+    Values::Complex(&a * &c - &b * &d, a * d + b * c)
+}
+
+pub fn div(
+    (a, b): (Rational, Rational),
+    (c, d): (Rational, Rational),
+) -> Result<Values, EvalError> {
+    let norm = &c * &c + &d * &d;
+    if norm == 0u32 {
+        return Err(EvalError::DivisionByZero);
+    }
+    let re = (&a * &c + &b * &d) / &norm;
+    let im = (b * c - a * d) / norm;
+    Ok(Values::Complex(re, im))
+}
+
+/// `|z|^2`, exact unlike `|z|`.
+pub fn abs2(re: &Rational, im: &Rational) -> Rational {
+    re * re + im * im
+}
+
+/// `|z|` and the angle of `z` in `(-pi, pi]`, to `digits` significant digits.
+pub fn to_polar(
+    re: &Rational,
+    im: &Rational,
+    digits: usize,
+) -> Result<(Rational, Rational), EvalError> {
+    let r = math::sqrt(&abs2(re, im), digits)?;
+    Ok((r, math::atan2(im, re, digits)))
+}
+
+/// The `Complex` with absolute value `r` and angle `theta`.
+pub fn from_polar(r: &Rational, theta: &Rational, digits: usize) -> Result<Values, EvalError> {
+    // A few more digits so the product with r is still right to `digits`.
+    let re = r * math::cos(theta, digits + 4)?;
+    let im = r * math::sin(theta, digits + 4)?;
+    Ok(Values::Complex(
+        math::round_digits(re, digits),
+        math::round_digits(im, digits),
+    ))
+}
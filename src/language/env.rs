@@ -1,28 +1,42 @@
 use std::{
-    collections::{HashMap, HashSet},
+    cell::Cell,
+    collections::{HashMap, HashSet, VecDeque},
     rc::Rc,
 };
 
-use malachite::Rational;
+use malachite::num::arithmetic::traits::{Abs, CeilingDivMod, DivMod, Gcd, Lcm, Mod, UnsignedAbs};
+use malachite::num::conversion::traits::RoundingFrom;
+use malachite::num::logic::traits::SignificantBits;
+use malachite::rounding_modes::RoundingMode;
+use malachite::{Integer, Rational};
 
 use crate::language::ast::stack::Stack;
 
 use super::{
     ast::Type,
-    eval::{ChainMap, Eval, EvalError, Flow, Values},
+    complex,
+    eval::{ChainMap, ERROR_VARIANTS, Eval, EvalError, Flow, ValueStack, Values},
+    linalg, math,
+    number::{
+        FORMAT_VARIANTS, INT_DISPLAY_TYPES, INT_FORMAT_VARIANTS, IntFormat, MAX_PLACES,
+        NumberFormat,
+    },
+    number_theory,
     parse::ParseCtx,
+    poly, random, stats,
+    units::{self, BUILTIN, Unit},
 };
 
 #[derive(Clone)]
 pub enum CallType {
     Stack(Stack),
-    Fun(Rc<dyn for<'a> Fn(&'a mut Vec<Values>, &Env, &mut ChainMap) -> Result<(), EvalError>>),
+    Fun(Rc<dyn for<'a> Fn(&'a mut ValueStack, &Env, &mut ChainMap) -> Result<(), EvalError>>),
 }
 
 impl CallType {
     pub fn eval(
         &self,
-        vals: &mut Vec<Values>,
+        vals: &mut ValueStack,
         env: &Env,
         map: &mut ChainMap,
     ) -> Result<Flow, EvalError> {
@@ -40,6 +54,95 @@ pub struct Env {
     pub protocol_arity: HashMap<usize, (usize, Option<usize>)>,
     pub typ_data: HashMap<(usize, Option<usize>), Vec<Type>>,
     pub type_variants: HashMap<usize, HashSet<usize>>,
+    pub type_params: HashMap<usize, Vec<usize>>,
+    pub interfaces: HashMap<usize, Vec<usize>>,
+    pub interface_impls: HashMap<usize, HashSet<Type>>,
+    pub error_type: usize,
+    pub error_tags: Vec<usize>,
+    /// Set by the `number_format` protocol and collected by the VM once
+    /// evaluation is done.
+    pub requested_format: Cell<Option<NumberFormat>>,
+    /// Set by the `int_format` protocol, like `requested_format`.
+    pub requested_int_format: Cell<Option<IntFormat>>,
+    /// Significant digits of irrational results, loaded from the VM before an
+    /// evaluation and stored back after it, as `precision` may change it.
+    pub precision: Cell<usize>,
+    /// State of the random generator, loaded from the VM and stored back like
+    /// `precision`.
+    pub rng: Cell<u64>,
+    /// Units by the id of their name, built-in and defined with `unit`.
+    pub units: HashMap<usize, Unit>,
+}
+
+impl Env {
+    /// Computes the type of a value. Values of parameterised types are
+    /// instantiated from their fields, parameters that can't be inferred
+    /// (e.g. in `Option::None()`) are left as `GenericTyp(usize::MAX)`.
+    pub fn type_of(&self, val: &Values) -> Type {
+        match val {
+            Values::Custom { name, tag, values } => match self.type_params.get(name) {
+                Some(params) if !params.is_empty() => {
+                    let mut generics = HashMap::new();
+                    if let (Some(types), Some(values)) = (self.typ_data.get(&(*name, *tag)), values)
+                    {
+                        types.iter().zip(values.iter()).for_each(|(ty, val)| {
+                            ty.bind(&self.type_of(val), &mut generics, self);
+                        });
+                    }
+                    Type::Applied(
+                        *name,
+                        params
+                            .iter()
+                            .map(|param| {
+                                generics
+                                    .remove(param)
+                                    .unwrap_or(Type::GenericTyp(usize::MAX))
+                            })
+                            .collect(),
+                    )
+                }
+                _ => Type::CustomType(*name),
+            },
+            other => other.get_real_type(),
+        }
+    }
+}
+
+impl Env {
+    /// Types are registered for an interface by their name only, so every
+    /// instantiation of a parameterised type shares the implementation.
+    pub fn interface_key(typ: &Type) -> Type {
+        match typ {
+            Type::Applied(name, _) => Type::CustomType(*name),
+            other => other.clone(),
+        }
+    }
+
+    pub fn implements(&self, typ: &Type, interface: usize) -> bool {
+        self.interface_impls
+            .get(&interface)
+            .is_some_and(|impls| impls.contains(&Env::interface_key(typ)))
+    }
+
+    /// Returns the methods of `interface` that have no overload mentioning `typ`.
+    pub fn missing_methods(&self, interface: usize, typ: &Type) -> Vec<usize> {
+        let key = Env::interface_key(typ);
+        self.interfaces
+            .get(&interface)
+            .map(|methods| {
+                methods
+                    .iter()
+                    .filter(|method| {
+                        !self.protocol_data.get(method).is_some_and(|arms| {
+                            arms.keys()
+                                .any(|types| types.iter().any(|t| Env::interface_key(t) == key))
+                        })
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 impl Default for Env {
@@ -49,10 +152,37 @@ impl Default for Env {
             protocol_data: Default::default(),
             typ_data: Default::default(),
             type_variants: Default::default(),
+            type_params: Default::default(),
+            interfaces: Default::default(),
+            interface_impls: Default::default(),
+            error_type: Default::default(),
+            error_tags: Default::default(),
             protocol_arity: Default::default(),
+            requested_format: Default::default(),
+            requested_int_format: Default::default(),
+            precision: Cell::new(math::DEFAULT_PRECISION),
+            rng: Cell::new(random::DEFAULT_SEED),
+            units: Default::default(),
         };
 
         let mut ctx = ParseCtx::default();
+        for unit in BUILTIN {
+            ret.units
+                .insert(ctx.insert_unit(unit.0), Unit::builtin(unit));
+        }
+        {
+            let error = ctx.insert_type("Error");
+            let mut variants = HashSet::new();
+            for (variant, types) in ERROR_VARIANTS {
+                let tag = ctx.insert_tag(*variant);
+                ret.typ_data.insert((error, Some(tag)), types.to_vec());
+                variants.insert(tag);
+                ret.error_tags.push(tag);
+            }
+            ret.error_type = error;
+            ret.type_variants.insert(error, variants);
+            ret.type_params.insert(error, vec![]);
+        }
         {
             let fun = ctx.insert_fun("add");
             let mut map = HashMap::new();
@@ -122,6 +252,65 @@ impl Default for Env {
                     })),
                 ),
             );
+            for operands in complex::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Complex],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = complex::parts(values.pop().unwrap());
+                            let a = complex::parts(values.pop().unwrap());
+                            values.push(complex::add(a, b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::ELEMENTWISE {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![operands[0].clone()],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(linalg::elementwise(a, b, |x, y| x + y)?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in poly::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Poly],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = poly::coefficients(values.pop().unwrap());
+                            let a = poly::coefficients(values.pop().unwrap());
+                            values.push(Values::Poly(poly::add(&a, &b)));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            map.insert(
+                vec![Type::Quantity, Type::Quantity],
+                (
+                    vec![Type::Quantity],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Quantity(a), Values::Quantity(b)) => {
+                                values.push(units::add(*a, *b, 1)?);
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
             ret.protocol_data.insert(fun, map);
             ret.protocol_arity.insert(fun, (2, Some(1)));
         }
@@ -178,87 +367,2938 @@ impl Default for Env {
                 ),
             );
             map.insert(
-                vec![Type::Float, Type::Integer],
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                values.push(Values::Float(a - Rational::from(b)))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            for operands in complex::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Complex],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = complex::parts(values.pop().unwrap());
+                            let a = complex::parts(values.pop().unwrap());
+                            values.push(complex::sub(a, b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::ELEMENTWISE {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![operands[0].clone()],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(linalg::elementwise(a, b, |x, y| x - y)?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in poly::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Poly],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = poly::coefficients(values.pop().unwrap());
+                            let a = poly::coefficients(values.pop().unwrap());
+                            values.push(Values::Poly(poly::sub(&a, &b)));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            map.insert(
+                vec![Type::Quantity, Type::Quantity],
+                (
+                    vec![Type::Quantity],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Quantity(a), Values::Quantity(b)) => {
+                                values.push(units::add(*a, *b, -1)?);
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("mul");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => values.push(Values::Int(a * b)),
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                values.push(Values::Float(a * b))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                values.push(Values::Float(Rational::from(a) * b))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                values.push(Values::Float(a * Rational::from(b)))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            for operands in complex::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Complex],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = complex::parts(values.pop().unwrap());
+                            let a = complex::parts(values.pop().unwrap());
+                            values.push(complex::mul(a, b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::ELEMENTWISE {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![operands[0].clone()],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(linalg::elementwise(a, b, |x, y| x * y)?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::SCALED {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![operands[0].clone()],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(linalg::scaled(a, b, |x, y| x * y));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::SCALED {
+                map.insert(
+                    vec![operands[1].clone(), operands[0].clone()],
+                    (
+                        vec![operands[0].clone()],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let a = values.pop().unwrap();
+                            let b = values.pop().unwrap();
+                            values.push(linalg::scaled(a, b, |x, y| y * x));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in poly::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Poly],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = poly::coefficients(values.pop().unwrap());
+                            let a = poly::coefficients(values.pop().unwrap());
+                            values.push(Values::Poly(poly::mul(&a, &b)?));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in units::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Quantity],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(units::mul(a, b)?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("div");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Int(a / b))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Float(a / b))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Float(Rational::from(a) / b))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Float(a / Rational::from(b)))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            for operands in complex::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Complex],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = complex::parts(values.pop().unwrap());
+                            let a = complex::parts(values.pop().unwrap());
+                            values.push(complex::div(a, b)?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::SCALED {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![operands[0].clone()],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            if matches!(&b, Values::Int(x) if *x == 0u32)
+                                || matches!(&b, Values::Float(x) if *x == 0u32)
+                            {
+                                return Err(EvalError::DivisionByZero);
+                            }
+                            values.push(linalg::scaled(a, b, |x, y| x / y));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in units::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Quantity],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(units::div(a, b)?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("mod");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Int(a.mod_op(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Float(math::modulo(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Float(math::modulo(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Float(math::modulo(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("rem");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Int(a % b));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Float(math::rem(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Float(math::rem(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Float(math::rem(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("fdiv");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Int(a.div_mod(b).0));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Int(math::floor_div(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Int(math::floor_div(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Int(math::floor_div(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("cdiv");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                values.push(Values::Int(a.ceiling_div_mod(b).0));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Int(math::ceil_div(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Int(math::ceil_div(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Int(math::ceil_div(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("divmod");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer, Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                if b == 0u32 {
+                                    return Err(EvalError::DivisionByZero);
+                                }
+                                let (q, r) = a.div_mod(b);
+                                values.push(Values::Int(q));
+                                values.push(Values::Int(r));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Integer, Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Int(math::floor_div(&a, &b)?));
+                                values.push(Values::Float(math::modulo(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Integer, Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Int(math::floor_div(&a, &b)?));
+                                values.push(Values::Float(math::modulo(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Integer, Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Int(math::floor_div(&a, &b)?));
+                                values.push(Values::Float(math::modulo(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Poly, Type::Poly],
+                (
+                    vec![Type::Poly, Type::Poly],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Poly(a), Values::Poly(b)) => {
+                                let (q, r) = poly::div_rem(&a, &b)?;
+                                values.push(Values::Poly(q));
+                                values.push(Values::Poly(r));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(2)));
+        }
+        {
+            let fun = ctx.insert_fun("abs");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(a.abs()));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(a.abs()));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("neg");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(-a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(-a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("min");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(a.min(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Float(a.min(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Float(a.min(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Float(a.min(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(stats::extreme(data, false)?);
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(stats::extreme(data, false)?);
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("max");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(a.max(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let (a, b) = (a, b);
+                                values.push(Values::Float(a.max(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let (a, b) = (Rational::from(a), b);
+                                values.push(Values::Float(a.max(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let (a, b) = (a, Rational::from(b));
+                                values.push(Values::Float(a.max(b)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(stats::extreme(data, true)?);
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(stats::extreme(data, true)?);
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("gcd");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                let r = a.unsigned_abs().gcd(b.unsigned_abs());
+                                values.push(Values::Int(Integer::from(r)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Poly, Type::Poly],
+                (
+                    vec![Type::Poly],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Poly(a), Values::Poly(b)) => {
+                                values.push(Values::Poly(poly::gcd(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("lcm");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                let r = a.unsigned_abs().lcm(b.unsigned_abs());
+                                values.push(Values::Int(Integer::from(r)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("modpow");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let c = values.pop().unwrap();
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b, c) {
+                            (Values::Int(a), Values::Int(b), Values::Int(c)) => {
+                                values.push(Values::Int(number_theory::mod_pow(&a, &b, &c)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (3, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("modinv");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(number_theory::mod_inverse(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("is_prime");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Bool],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Bool(
+                                    a > 0u32 && number_theory::is_prime(&a.unsigned_abs()),
+                                ));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("factor");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                if a == 0u32 {
+                                    return Err(EvalError::PrimitiveEvalErr);
+                                }
+                                let mut factors: VecDeque<Values> =
+                                    number_theory::factor(&(&a).unsigned_abs())?
+                                        .into_iter()
+                                        .map(|p| Values::Int(Integer::from(p)))
+                                        .collect();
+                                if a < 0u32 {
+                                    factors.push_front(Values::Int(Integer::from(-1)));
+                                }
+                                values.push(Values::List(factors));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("factorial");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(number_theory::factorial(&a)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("binomial");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(number_theory::binomial(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("isqrt");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(number_theory::isqrt(&a)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("log");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(number_theory::log(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("numerator");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Int(Integer::from_sign_and_abs(
+                                    a >= 0u32,
+                                    a.into_numerator(),
+                                )));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("denominator");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(_) => {
+                                values.push(Values::Int(Integer::from(1)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Int(Integer::from(a.into_denominator())));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("floor");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Int(
+                                    Integer::rounding_from(a, RoundingMode::Floor).0,
+                                ));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("ceil");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Int(
+                                    Integer::rounding_from(a, RoundingMode::Ceiling).0,
+                                ));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("round");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Int(math::round_half_away(&a)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("bit_and");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(a & b));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("bit_or");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(a | b));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("bit_xor");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(a ^ b));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("bit_not");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(!a));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("shl");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(number_theory::shift(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("shr");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                values.push(Values::Int(number_theory::shift(&a, &-b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("popcount");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(number_theory::popcount(&a)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("bit_length");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                values.push(Values::Int(Integer::from(a.significant_bits())));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("sqrt");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => values.push(Values::Float(math::sqrt(
+                                &Rational::from(a),
+                                env.precision.get(),
+                            )?)),
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(math::sqrt(&a, env.precision.get())?))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("pow");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    // An `Int`, or a `Rat` for a negative exponent.
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                let r = math::pow_int(&Rational::from(a), &b)?;
+                                values.push(if b < 0u32 {
+                                    Values::Float(r)
+                                } else {
+                                    Values::Int(Integer::try_from(r).unwrap())
+                                });
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Int(b)) => {
+                                let r = math::pow_int(&a, &b)?;
+                                values.push(Values::Float(r));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Integer, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Float(b)) => {
+                                let r = math::pow(&Rational::from(a), &b, env.precision.get())?;
+                                values.push(Values::Float(r));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Float(a), Values::Float(b)) => {
+                                let r = math::pow(&a, &b, env.precision.get())?;
+                                values.push(Values::Float(r));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("exp");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => values.push(Values::Float(math::exp(
+                                &Rational::from(a),
+                                env.precision.get(),
+                            )?)),
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(math::exp(&a, env.precision.get())?))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("ln");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => values.push(Values::Float(math::ln(
+                                &Rational::from(a),
+                                env.precision.get(),
+                            )?)),
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(math::ln(&a, env.precision.get())?))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("sin");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => values.push(Values::Float(math::sin(
+                                &Rational::from(a),
+                                env.precision.get(),
+                            )?)),
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(math::sin(&a, env.precision.get())?))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("cos");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => values.push(Values::Float(math::cos(
+                                &Rational::from(a),
+                                env.precision.get(),
+                            )?)),
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Float(a) => {
+                                values.push(Values::Float(math::cos(&a, env.precision.get())?))
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("pi");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        values.push(Values::Float(math::pi(env.precision.get())));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (0, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("precision");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                let digits = usize::try_from(&a)
+                                    .ok()
+                                    .filter(|x| (1..=math::MAX_PRECISION).contains(x))
+                                    .ok_or(EvalError::PrimitiveEvalErr)?;
+                                env.precision.set(digits);
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(0)));
+        }
+        {
+            let fun = ctx.insert_fun("re");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Complex],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Complex(re, _) => {
+                                values.push(Values::Float(re));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("im");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Complex],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Complex(_, im) => {
+                                values.push(Values::Float(im));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("conj");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Complex],
+                (
+                    vec![Type::Complex],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Complex(re, im) => {
+                                values.push(Values::Complex(re, -im));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("abs2");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Complex],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Complex(re, im) => {
+                                values.push(Values::Float(complex::abs2(&re, &im)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("to_polar");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Complex],
+                (
+                    vec![Type::Float, Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Complex(re, im) => {
+                                let (r, theta) = complex::to_polar(&re, &im, env.precision.get())?;
+                                values.push(Values::Float(r));
+                                values.push(Values::Float(theta));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(2)));
+        }
+        {
+            let fun = ctx.insert_fun("from_polar");
+            let mut map = HashMap::new();
+            for operands in [
+                [Type::Float, Type::Float],
+                [Type::Integer, Type::Float],
+                [Type::Float, Type::Integer],
+                [Type::Integer, Type::Integer],
+            ] {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Complex],
+                        CallType::Fun(Rc::new(|values, env, _chain_map| {
+                            let (theta, _) = complex::parts(values.pop().unwrap());
+                            let (r, _) = complex::parts(values.pop().unwrap());
+                            values.push(complex::from_polar(&r, &theta, env.precision.get())?);
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("dot");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Vector, Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Vector(a), Values::Vector(b)) => {
+                                values.push(Values::Float(linalg::dot(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("matmul");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Matrix, Type::Matrix],
+                (
+                    vec![Type::Matrix],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Matrix(a), Values::Matrix(b)) => {
+                                values.push(Values::Matrix(linalg::matmul(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Matrix, Type::Vector],
+                (
+                    vec![Type::Vector],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Matrix(a), Values::Vector(b)) => {
+                                values.push(Values::Vector(linalg::mat_vec(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector, Type::Matrix],
+                (
+                    vec![Type::Vector],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Vector(a), Values::Matrix(b)) => {
+                                values.push(Values::Vector(linalg::vec_mat(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("transpose");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Matrix],
+                (
+                    vec![Type::Matrix],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Matrix(a) => {
+                                values.push(Values::Matrix(linalg::transpose(&a)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("det");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Matrix],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Matrix(a) => {
+                                values.push(Values::Float(linalg::det(&a)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("inverse");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Matrix],
+                (
+                    vec![Type::Matrix],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Matrix(a) => {
+                                values.push(Values::Matrix(linalg::inverse(&a)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("solve");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Matrix, Type::Vector],
+                (
+                    vec![Type::Vector],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Matrix(a), Values::Vector(b)) => {
+                                values.push(Values::Vector(linalg::solve(&a, &b)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("rank");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Matrix],
+                (
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Matrix(a) => {
+                                values.push(Values::Int(linalg::rank(&a).into()));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("identity");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Matrix],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                let n =
+                                    usize::try_from(&a).map_err(|_| EvalError::PrimitiveEvalErr)?;
+                                values.push(Values::Matrix(linalg::Matrix::identity(n)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("zero_mat");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer, Type::Integer],
+                (
+                    vec![Type::Matrix],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Int(a), Values::Int(b)) => {
+                                let rows =
+                                    usize::try_from(&a).map_err(|_| EvalError::PrimitiveEvalErr)?;
+                                let cols =
+                                    usize::try_from(&b).map_err(|_| EvalError::PrimitiveEvalErr)?;
+                                values.push(Values::Matrix(linalg::Matrix::zeros(rows, cols)?));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("zero_vec");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::Vector],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Int(a) => {
+                                let n =
+                                    usize::try_from(&a).map_err(|_| EvalError::PrimitiveEvalErr)?;
+                                let zeros = linalg::Matrix::zeros(1, n)?;
+                                values.push(Values::Vector(
+                                    zeros.rows().flatten().cloned().collect(),
+                                ));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("eval");
+            let mut map = HashMap::new();
+            for x in [Type::Integer, Type::Float] {
+                map.insert(
+                    vec![Type::Poly, x],
+                    (
+                        vec![Type::Float],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let x = poly::entry(values.pop().unwrap())?;
+                            let p = poly::coefficients(values.pop().unwrap());
+                            values.push(Values::Float(poly::eval(&p, &x)));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("derive");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Poly],
+                (
+                    vec![Type::Poly],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Poly(a) => {
+                                values.push(Values::Poly(poly::derive(&a)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("integrate");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Poly],
+                (
+                    vec![Type::Poly],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Poly(a) => {
+                                values.push(Values::Poly(poly::integrate(&a)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("roots");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Poly],
+                (
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Poly(a) => {
+                                let roots = poly::roots(&a)?.into_iter().map(Values::Float);
+                                values.push(Values::List(roots.collect()));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("sum");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, integral) = stats::numbers(values.pop().unwrap())?;
+                        values.push(stats::number(stats::sum(&data), integral));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, integral) = stats::numbers(values.pop().unwrap())?;
+                        values.push(stats::number(stats::sum(&data), integral));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("product");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, integral) = stats::numbers(values.pop().unwrap())?;
+                        values.push(stats::number(stats::product(&data), integral));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, integral) = stats::numbers(values.pop().unwrap())?;
+                        values.push(stats::number(stats::product(&data), integral));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("mean");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::mean(&data)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::mean(&data)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("variance");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::variance(&data)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::variance(&data)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("median");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::median(data)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::median(data)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("stddev");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::stddev(&data, env.precision.get())?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::stddev(&data, env.precision.get())?));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("mode");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(stats::mode(data)?);
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(stats::mode(data)?);
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("quantile");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let q = match values.pop().unwrap() {
+                            Values::Int(i) => Rational::from(i),
+                            Values::Float(f) => f,
+                            _ => unreachable!(),
+                        };
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::quantile(data, &q)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::List, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let q = match values.pop().unwrap() {
+                            Values::Int(i) => Rational::from(i),
+                            Values::Float(f) => f,
+                            _ => unreachable!(),
+                        };
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::quantile(data, &q)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector, Type::Integer],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let q = match values.pop().unwrap() {
+                            Values::Int(i) => Rational::from(i),
+                            Values::Float(f) => f,
+                            _ => unreachable!(),
+                        };
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::quantile(data, &q)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector, Type::Float],
+                (
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let q = match values.pop().unwrap() {
+                            Values::Int(i) => Rational::from(i),
+                            Values::Float(f) => f,
+                            _ => unreachable!(),
+                        };
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Float(stats::quantile(data, &q)?));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (2, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("histogram");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::Map],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(Values::Map(stats::histogram(data)));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Map],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let data = stats::entries(values.pop().unwrap());
+                        values.push(Values::Map(stats::histogram(data)));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("cumsum");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, integral) = stats::numbers(values.pop().unwrap())?;
+                        let sums = stats::cumsum(&data).into_iter();
+                        values.push(Values::List(
+                            sums.map(|x| stats::number(x, integral)).collect(),
+                        ));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::Vector],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let (data, _) = stats::numbers(values.pop().unwrap())?;
+                        values.push(Values::Vector(stats::cumsum(&data)));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("sort");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let mut data = Vec::from(stats::entries(values.pop().unwrap()));
+                        data.sort_by(stats::compare);
+                        values.push(Values::List(data.into()));
+                        Ok(())
+                    })),
+                ),
+            );
+            map.insert(
+                vec![Type::Vector],
                 (
-                    vec![Type::Float],
+                    vec![Type::Vector],
                     CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Float(a), Values::Int(b)) => {
-                                values.push(Values::Float(a - Rational::from(b)))
-                            }
-                            _ => unreachable!(),
-                        }
+                        let (mut data, _) = stats::numbers(values.pop().unwrap())?;
+                        data.sort();
+                        values.push(Values::Vector(data));
                         Ok(())
                     })),
                 ),
             );
             ret.protocol_data.insert(fun, map);
-            ret.protocol_arity.insert(fun, (2, Some(1)));
+            ret.protocol_arity.insert(fun, (1, Some(1)));
         }
         {
-            let fun = ctx.insert_fun("mul");
+            let fun = ctx.insert_fun("sort_desc");
             let mut map = HashMap::new();
             map.insert(
-                vec![Type::Integer, Type::Integer],
+                vec![Type::List],
                 (
-                    vec![Type::Integer],
+                    vec![Type::List],
                     CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Int(a), Values::Int(b)) => values.push(Values::Int(a * b)),
-                            _ => unreachable!(),
-                        }
+                        let mut data = Vec::from(stats::entries(values.pop().unwrap()));
+                        data.sort_by(|a, b| stats::compare(b, a));
+                        values.push(Values::List(data.into()));
                         Ok(())
                     })),
                 ),
             );
             map.insert(
-                vec![Type::Float, Type::Float],
+                vec![Type::Vector],
                 (
-                    vec![Type::Float],
+                    vec![Type::Vector],
                     CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Float(a), Values::Float(b)) => {
-                                values.push(Values::Float(a * b))
-                            }
-                            _ => unreachable!(),
-                        }
+                        let (mut data, _) = stats::numbers(values.pop().unwrap())?;
+                        data.sort_by(|a, b| b.cmp(a));
+                        values.push(Values::Vector(data));
                         Ok(())
                     })),
                 ),
             );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("seed");
+            let mut map = HashMap::new();
             map.insert(
-                vec![Type::Integer, Type::Float],
+                vec![Type::Integer],
                 (
-                    vec![Type::Float],
-                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Int(a), Values::Float(b)) => {
-                                values.push(Values::Float(Rational::from(a) * b))
-                            }
+                    vec![],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        match values.pop().unwrap() {
+                            Values::Int(a) => env.rng.set(random::seed(&a)),
                             _ => unreachable!(),
                         }
                         Ok(())
                     })),
                 ),
             );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(0)));
+        }
+        {
+            let fun = ctx.insert_fun("rand_int");
+            let mut map = HashMap::new();
             map.insert(
-                vec![Type::Float, Type::Integer],
+                vec![Type::Integer, Type::Integer],
                 (
-                    vec![Type::Float],
-                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Float(a), Values::Int(b)) => {
-                                values.push(Values::Float(a * Rational::from(b)))
+                    vec![Type::Integer],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let hi = values.pop().unwrap();
+                        let lo = values.pop().unwrap();
+                        match (lo, hi) {
+                            (Values::Int(lo), Values::Int(hi)) => {
+                                values.push(Values::Int(random::int(&env.rng, &lo, &hi)?));
                             }
                             _ => unreachable!(),
                         }
@@ -270,33 +3310,32 @@ impl Default for Env {
             ret.protocol_arity.insert(fun, (2, Some(1)));
         }
         {
-            let fun = ctx.insert_fun("div");
+            let fun = ctx.insert_fun("rand_rat");
             let mut map = HashMap::new();
             map.insert(
-                vec![Type::Integer, Type::Integer],
+                vec![],
                 (
-                    vec![Type::Integer],
-                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Int(a), Values::Int(b)) => values.push(Values::Int(a / b)),
-                            _ => unreachable!(),
-                        }
+                    vec![Type::Float],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        values.push(Values::Float(random::rat(&env.rng)));
                         Ok(())
                     })),
                 ),
             );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (0, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("shuffle");
+            let mut map = HashMap::new();
             map.insert(
-                vec![Type::Float, Type::Float],
+                vec![Type::List],
                 (
-                    vec![Type::Float],
-                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Float(a), Values::Float(b)) => {
-                                values.push(Values::Float(a / b))
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        match values.pop().unwrap() {
+                            Values::List(l) => {
+                                values.push(Values::List(random::shuffle(&env.rng, l)))
                             }
                             _ => unreachable!(),
                         }
@@ -304,33 +3343,41 @@ impl Default for Env {
                     })),
                 ),
             );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("choice");
+            let mut map = HashMap::new();
             map.insert(
-                vec![Type::Integer, Type::Float],
+                vec![Type::List],
                 (
-                    vec![Type::Float],
-                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Int(a), Values::Float(b)) => {
-                                values.push(Values::Float(Rational::from(a) / b))
-                            }
+                    vec![Type::GenericTyp(usize::MAX)],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        match values.pop().unwrap() {
+                            Values::List(l) => values.push(random::choice(&env.rng, l)?),
                             _ => unreachable!(),
                         }
                         Ok(())
                     })),
                 ),
             );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("sample");
+            let mut map = HashMap::new();
             map.insert(
-                vec![Type::Float, Type::Integer],
+                vec![Type::List, Type::Integer],
                 (
-                    vec![Type::Float],
-                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
-                        let b = values.pop().unwrap();
-                        let a = values.pop().unwrap();
-                        match (a, b) {
-                            (Values::Float(a), Values::Int(b)) => {
-                                values.push(Values::Float(a / Rational::from(b)))
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, env, _chain_map| {
+                        let k = values.pop().unwrap();
+                        let list = values.pop().unwrap();
+                        match (list, k) {
+                            (Values::List(l), Values::Int(k)) => {
+                                values.push(Values::List(random::sample(&env.rng, l, &k)?));
                             }
                             _ => unreachable!(),
                         }
@@ -421,6 +3468,65 @@ impl Default for Env {
                     })),
                 ),
             );
+            for operands in complex::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Bool],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = complex::parts(values.pop().unwrap());
+                            let a = complex::parts(values.pop().unwrap());
+                            values.push(Values::Bool(a == b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::ELEMENTWISE {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Bool],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(Values::Bool(a == b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in poly::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Bool],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = poly::coefficients(values.pop().unwrap());
+                            let a = poly::coefficients(values.pop().unwrap());
+                            values.push(Values::Bool(a == b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            map.insert(
+                vec![Type::Quantity, Type::Quantity],
+                (
+                    vec![Type::Bool],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Quantity(a), Values::Quantity(b)) => {
+                                values.push(Values::Bool((a.value, a.dim) == (b.value, b.dim)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
             ret.protocol_data.insert(fun, map);
             ret.protocol_arity.insert(fun, (2, Some(1)));
         }
@@ -504,6 +3610,65 @@ impl Default for Env {
                     })),
                 ),
             );
+            for operands in complex::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Bool],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = complex::parts(values.pop().unwrap());
+                            let a = complex::parts(values.pop().unwrap());
+                            values.push(Values::Bool(a != b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in linalg::ELEMENTWISE {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Bool],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = values.pop().unwrap();
+                            let a = values.pop().unwrap();
+                            values.push(Values::Bool(a != b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            for operands in poly::OPERANDS {
+                map.insert(
+                    operands.to_vec(),
+                    (
+                        vec![Type::Bool],
+                        CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                            let b = poly::coefficients(values.pop().unwrap());
+                            let a = poly::coefficients(values.pop().unwrap());
+                            values.push(Values::Bool(a != b));
+                            Ok(())
+                        })),
+                    ),
+                );
+            }
+            map.insert(
+                vec![Type::Quantity, Type::Quantity],
+                (
+                    vec![Type::Bool],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let b = values.pop().unwrap();
+                        let a = values.pop().unwrap();
+                        match (a, b) {
+                            (Values::Quantity(a), Values::Quantity(b)) => {
+                                values.push(Values::Bool((a.value, a.dim) != (b.value, b.dim)));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
             ret.protocol_data.insert(fun, map);
             ret.protocol_arity.insert(fun, (2, Some(1)));
         }
@@ -774,7 +3939,7 @@ impl Default for Env {
             let fun = ctx.insert_fun("not");
             let mut map = HashMap::new();
             map.insert(
-                vec![Type::Integer, Type::Integer],
+                vec![Type::Integer],
                 (
                     vec![Type::Integer],
                     CallType::Fun(Rc::new(|values, _env, _chain_map| {
@@ -788,7 +3953,7 @@ impl Default for Env {
                 ),
             );
             map.insert(
-                vec![Type::Bool, Type::Bool],
+                vec![Type::Bool],
                 (
                     vec![Type::Bool],
                     CallType::Fun(Rc::new(|values, _env, _chain_map| {
@@ -1651,7 +4816,7 @@ impl Default for Env {
                             match stack.to_owned().eval(values, env, chain_map) {
                                 Ok(Flow::Ret | Flow::Break) => Ok(()),
                                 _ret @ Ok(_) => Ok(()),
-                                Err(_) => Err(EvalError::PrimitiveEvalErr),
+                                Err(err) => Err(err),
                             }
                         } else {
                             unreachable!()
@@ -1758,6 +4923,227 @@ impl Default for Env {
             ret.protocol_data.insert(fun, map);
             ret.protocol_arity.insert(fun, (1, Some(1)));
         }
+        {
+            let fun = ctx.insert_fun("l2v");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::List],
+                (
+                    vec![Type::Vector],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::List(l) => {
+                                let v =
+                                    l.into_iter().map(linalg::entry).collect::<Result<_, _>>()?;
+                                values.push(Values::Vector(v));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+        {
+            let fun = ctx.insert_fun("v2l");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Vector],
+                (
+                    vec![Type::List],
+                    CallType::Fun(Rc::new(|values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        match a {
+                            Values::Vector(v) => {
+                                values
+                                    .push(Values::List(v.into_iter().map(Values::Float).collect()));
+                            }
+                            _ => unreachable!(),
+                        }
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
+
+        {
+            let format = ctx.insert_type("NumberFormat");
+            let mut variants = HashSet::new();
+            let mut tags = vec![];
+            for (variant, types) in FORMAT_VARIANTS {
+                let tag = ctx.insert_tag(*variant);
+                ret.typ_data.insert((format, Some(tag)), types.to_vec());
+                variants.insert(tag);
+                tags.push(tag);
+            }
+            ret.type_variants.insert(format, variants);
+            ret.type_params.insert(format, vec![]);
+
+            let fun = ctx.insert_fun("number_format");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::CustomType(format)],
+                (
+                    vec![],
+                    CallType::Fun(Rc::new(move |values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        let Values::Custom {
+                            tag: Some(tag),
+                            values: fields,
+                            ..
+                        } = a
+                        else {
+                            unreachable!()
+                        };
+                        let places = || match fields.as_ref().and_then(|x| x.front()) {
+                            Some(Values::Int(i)) => usize::try_from(i)
+                                .ok()
+                                .filter(|x| *x <= MAX_PLACES)
+                                .ok_or(EvalError::PrimitiveEvalErr),
+                            _ => unreachable!(),
+                        };
+                        let format = match tags.iter().position(|x| *x == tag) {
+                            Some(0) => NumberFormat::Fraction,
+                            Some(1) => NumberFormat::Fixed(places()?),
+                            Some(2) => NumberFormat::Scientific(places()?),
+                            Some(3) => NumberFormat::Repeating,
+                            Some(4) => NumberFormat::Mixed,
+                            _ => unreachable!(),
+                        };
+                        env.requested_format.set(Some(format));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(0)));
+        }
+        {
+            let format = ctx.insert_type("IntFormat");
+            let mut variants = HashSet::new();
+            let mut tags = vec![];
+            for (variant, types) in INT_FORMAT_VARIANTS {
+                let tag = ctx.insert_tag(*variant);
+                ret.typ_data.insert((format, Some(tag)), types.to_vec());
+                variants.insert(tag);
+                tags.push(tag);
+            }
+            ret.type_variants.insert(format, variants);
+            ret.type_params.insert(format, vec![]);
+
+            let fun = ctx.insert_fun("int_format");
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::CustomType(format)],
+                (
+                    vec![],
+                    CallType::Fun(Rc::new(move |values, env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        let Values::Custom { tag: Some(tag), .. } = a else {
+                            unreachable!()
+                        };
+                        let format = match tags.iter().position(|x| *x == tag) {
+                            Some(0) => IntFormat::Decimal,
+                            Some(1) => IntFormat::Hex,
+                            Some(2) => IntFormat::Octal,
+                            Some(3) => IntFormat::Binary,
+                            _ => unreachable!(),
+                        };
+                        env.requested_int_format.set(Some(format));
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(0)));
+        }
+        for (typ, word, _) in INT_DISPLAY_TYPES {
+            let typ = ctx.insert_type(*typ);
+            ret.typ_data.insert((typ, None), vec![Type::Integer]);
+            ret.type_params.insert(typ, vec![]);
+
+            let fun = ctx.insert_fun(*word);
+            let mut map = HashMap::new();
+            map.insert(
+                vec![Type::Integer],
+                (
+                    vec![Type::CustomType(typ)],
+                    CallType::Fun(Rc::new(move |values, _env, _chain_map| {
+                        let a = values.pop().unwrap();
+                        values.push(Values::Custom {
+                            name: typ,
+                            tag: None,
+                            values: Some(VecDeque::from([a])),
+                        });
+                        Ok(())
+                    })),
+                ),
+            );
+            ret.protocol_data.insert(fun, map);
+            ret.protocol_arity.insert(fun, (1, Some(1)));
+        }
         ret
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::language::vm::VM;
+
+    fn run(source: &str) -> Result<Vec<String>, String> {
+        let mut vm = VM::default();
+        vm.parse_full_program(source).unwrap();
+        match vm.eval() {
+            Ok(()) => Ok(vm.stack.iter().map(|x| vm.display(x)).collect()),
+            Err(err) => Err(format!("{err:?}")),
+        }
+    }
+
+    #[test]
+    fn int_pow() {
+        assert_eq!(
+            run("2 10 pow -3 3 pow 5 0 pow;").unwrap(),
+            ["1024", "-27", "1"]
+        );
+        assert_eq!(
+            run("2 -1 pow -2 -3 pow 1 -4 pow;").unwrap(),
+            ["1/2", "-1/8", "1.0"]
+        );
+        assert_eq!(run("0 -1 pow;").unwrap_err(), "DivisionByZero");
+    }
+
+    #[test]
+    fn poly_eval() {
+        assert_eq!(run("Poly(1 -3 2) 5 eval;").unwrap(), ["12.0"]);
+    }
+
+    #[test]
+    fn poly_roots() {
+        assert_eq!(run("Poly(1 -1 0) roots;").unwrap(), ["List(0.0, 1.0)"]);
+    }
+
+    #[test]
+    fn short_overloads() {
+        let vm = VM::default();
+        for (fun, arms) in vm.env.protocol_data.iter() {
+            let arity = vm.env.protocol_arity[fun].0;
+            let name = vm.parse_ctx.lookup_call_name(*fun);
+            assert!(arms.keys().all(|types| types.len() <= arity), "{name}");
+        }
+        let source = "true not 1 1 eq not List(3 1/2 2) min 1 2 min 0 1 1 eq not;";
+        let values = run(source).unwrap();
+        assert_eq!(values, ["false", "false", "1/2", "1", "0", "false"]);
+    }
+
+    #[test]
+    fn counts_by_value() {
+        let source = "List(2 2.0 3 3) mode List(2 2.0 3) histogram List(1.0 1) mode;";
+        let values = run(source).unwrap();
+        assert_eq!(values, ["2", "Map(List(2, 2), List(3, 1))", "1.0"]);
+    }
+}
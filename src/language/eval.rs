@@ -2,6 +2,7 @@ use super::ast::Type;
 use super::ast::primitives::Primitives;
 use super::env::Env;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub enum EvalError {
@@ -34,10 +35,164 @@ pub enum EvalError {
     MapExprMustHaveListOfLen2,
 
     TypeDoesntExist(usize),
+    /// The type, how many fields it expects and how many it got.
     TypeConstructorLenMismatch(usize, usize, usize),
+    TypeConstructorTypeMismatch(usize),
 
     IndexOutOfBounds,
     Underflow,
+    DivisionByZero,
+    /// Shapes of the two operands, `[len]` for a vector and `[rows, cols]`
+    /// for a matrix.
+    DimensionMismatch(Vec<usize>, Vec<usize>),
+    SingularMatrix,
+    VecExpectsNumberButGot(Values),
+    MatExpectsVecButGot(Values),
+    PolyExpectsNumberButGot(Values),
+    IncompatibleDimensions(Box<(Values, Values)>),
+    UnitExpectsNumberButGot(Values),
+    StatsExpectsNumberButGot(Values),
+    EmptyCollection,
+
+    Thrown(Values),
+}
+
+/// Built-in errors as seen by `catch`: variants of the `Error` enum with
+/// their payload types.
+pub const ERROR_VARIANTS: &[(&str, &[Type])] = &[
+    ("UndefinedVariable", &[]),
+    ("UndefinedCall", &[]),
+    ("WhileCondUnderFlow", &[]),
+    (
+        "WhileCondExpectsBoolButGot",
+        &[Type::GenericTyp(usize::MAX)],
+    ),
+    ("IfCondUnderFlow", &[]),
+    ("IfCondExpectsBoolButGot", &[Type::GenericTyp(usize::MAX)]),
+    ("TakeUnderflow", &[]),
+    ("NoMatch", &[]),
+    ("MatchPatternUnderflow", &[]),
+    ("MatchCondUnderFlow", &[]),
+    (
+        "MatchCondExpectsBoolButGot",
+        &[Type::GenericTyp(usize::MAX)],
+    ),
+    ("PrimitiveUnderflow", &[]),
+    ("PrimitiveTypeErr", &[]),
+    ("PrimitiveEvalErr", &[]),
+    ("MapExprMustHaveListOfLen2", &[]),
+    ("TypeDoesntExist", &[]),
+    (
+        "TypeConstructorLenMismatch",
+        &[Type::Integer, Type::Integer],
+    ),
+    ("TypeConstructorTypeMismatch", &[]),
+    ("IndexOutOfBounds", &[]),
+    ("Underflow", &[]),
+    ("DivisionByZero", &[]),
+    ("DimensionMismatch", &[Type::List, Type::List]),
+    ("SingularMatrix", &[]),
+    ("VecExpectsNumberButGot", &[Type::GenericTyp(usize::MAX)]),
+    ("MatExpectsVecButGot", &[Type::GenericTyp(usize::MAX)]),
+    ("PolyExpectsNumberButGot", &[Type::GenericTyp(usize::MAX)]),
+    (
+        "IncompatibleDimensions",
+        &[Type::GenericTyp(usize::MAX), Type::GenericTyp(usize::MAX)],
+    ),
+    ("UnitExpectsNumberButGot", &[Type::GenericTyp(usize::MAX)]),
+    ("StatsExpectsNumberButGot", &[Type::GenericTyp(usize::MAX)]),
+    ("EmptyCollection", &[]),
+];
+
+impl EvalError {
+    /// Strips the wrappers added while unwinding through blocks and calls.
+    pub fn root_cause(&self) -> &EvalError {
+        match self {
+            EvalError::FuncCallFail(x) => x.1.root_cause(),
+            EvalError::WhileCondFail(x)
+            | EvalError::WhileBodyFail(x)
+            | EvalError::IfBodyFail(x)
+            | EvalError::TakeBodyFail(x)
+            | EvalError::MatchBodyFail(x)
+            | EvalError::MatchCondFail(x)
+            | EvalError::MatchArmFail(x) => x.root_cause(),
+            other => other,
+        }
+    }
+
+    /// The value a `catch` block matches against. Thrown values are passed
+    /// through, built-in errors become `Error::<Kind>(..)`.
+    pub fn to_value(&self, env: &Env) -> Values {
+        let (variant, values) = match self.root_cause() {
+            EvalError::Thrown(value) => return value.clone(),
+            EvalError::UndefinedVariable(_) => ("UndefinedVariable", vec![]),
+            EvalError::UndefinedCall(_) => ("UndefinedCall", vec![]),
+            EvalError::WhileCondUnderFlow => ("WhileCondUnderFlow", vec![]),
+            EvalError::WhileCondExpectsBoolButGot(x) => {
+                ("WhileCondExpectsBoolButGot", vec![x.clone()])
+            }
+            EvalError::IfCondUnderFlow => ("IfCondUnderFlow", vec![]),
+            EvalError::IfCondExpectsBoolButGot(x) => ("IfCondExpectsBoolButGot", vec![x.clone()]),
+            EvalError::TakeUnderflow => ("TakeUnderflow", vec![]),
+            EvalError::NoMatch => ("NoMatch", vec![]),
+            EvalError::MatchPatternUnderflow => ("MatchPatternUnderflow", vec![]),
+            EvalError::MatchCondUnderFlow => ("MatchCondUnderFlow", vec![]),
+            EvalError::MatchCondExpectsBoolButGot(x) => {
+                ("MatchCondExpectsBoolButGot", vec![x.clone()])
+            }
+            EvalError::PrimitiveUnderflow(_) => ("PrimitiveUnderflow", vec![]),
+            EvalError::PrimitiveTypeErr(_, _) => ("PrimitiveTypeErr", vec![]),
+            EvalError::PrimitiveEvalErr => ("PrimitiveEvalErr", vec![]),
+            EvalError::MapExprMustHaveListOfLen2 => ("MapExprMustHaveListOfLen2", vec![]),
+            EvalError::TypeDoesntExist(_) => ("TypeDoesntExist", vec![]),
+            EvalError::TypeConstructorLenMismatch(_, expects, got) => (
+                "TypeConstructorLenMismatch",
+                vec![Values::Int((*expects).into()), Values::Int((*got).into())],
+            ),
+            EvalError::TypeConstructorTypeMismatch(_) => ("TypeConstructorTypeMismatch", vec![]),
+            EvalError::IndexOutOfBounds => ("IndexOutOfBounds", vec![]),
+            EvalError::Underflow => ("Underflow", vec![]),
+            EvalError::DivisionByZero => ("DivisionByZero", vec![]),
+            EvalError::DimensionMismatch(a, b) => {
+                let shape = |x: &Vec<usize>| {
+                    Values::List(x.iter().map(|x| Values::Int((*x).into())).collect())
+                };
+                ("DimensionMismatch", vec![shape(a), shape(b)])
+            }
+            EvalError::SingularMatrix => ("SingularMatrix", vec![]),
+            EvalError::VecExpectsNumberButGot(x) => ("VecExpectsNumberButGot", vec![x.clone()]),
+            EvalError::MatExpectsVecButGot(x) => ("MatExpectsVecButGot", vec![x.clone()]),
+            EvalError::PolyExpectsNumberButGot(x) => ("PolyExpectsNumberButGot", vec![x.clone()]),
+            EvalError::IncompatibleDimensions(x) => {
+                let (a, b) = &**x;
+                ("IncompatibleDimensions", vec![a.clone(), b.clone()])
+            }
+            EvalError::UnitExpectsNumberButGot(x) => ("UnitExpectsNumberButGot", vec![x.clone()]),
+            EvalError::StatsExpectsNumberButGot(x) => ("StatsExpectsNumberButGot", vec![x.clone()]),
+            EvalError::EmptyCollection => ("EmptyCollection", vec![]),
+            EvalError::FuncCallFail(_)
+            | EvalError::WhileCondFail(_)
+            | EvalError::WhileBodyFail(_)
+            | EvalError::IfBodyFail(_)
+            | EvalError::TakeBodyFail(_)
+            | EvalError::MatchBodyFail(_)
+            | EvalError::MatchCondFail(_)
+            | EvalError::MatchArmFail(_) => unreachable!("root_cause strips wrappers"),
+        };
+        let idx = ERROR_VARIANTS
+            .iter()
+            .position(|(name, _)| *name == variant)
+            .unwrap();
+        Values::Custom {
+            name: env.error_type,
+            tag: Some(env.error_tags[idx]),
+            values: if values.is_empty() {
+                None
+            } else {
+                Some(values.into_iter().collect())
+            },
+        }
+    }
 }
 
 use malachite::{Integer, Rational};
@@ -47,6 +202,13 @@ pub enum Values {
     Bool(bool),
     Int(Integer),
     Float(Rational),
+    /// Real and imaginary part.
+    Complex(Rational, Rational),
+    Vector(Vec<Rational>),
+    Matrix(super::linalg::Matrix),
+    /// Coefficients, lowest power first, without zero leading ones.
+    Poly(Vec<Rational>),
+    Quantity(Box<super::units::Quantity>),
 
     Stack(super::ast::stack::Stack),
     List(VecDeque<Values>),
@@ -64,6 +226,11 @@ impl Values {
     pub fn get_type(&self) -> &str {
         match self {
             Values::Float(_) => "float",
+            Values::Complex(..) => "complex",
+            Values::Vector(_) => "vector",
+            Values::Matrix(_) => "matrix",
+            Values::Poly(_) => "poly",
+            Values::Quantity(_) => "quantity",
             Values::Int(_) => "int",
             Values::Bool(_) => "bool",
             Values::Stack(_) => "stack",
@@ -83,6 +250,11 @@ impl Values {
             Values::Bool(_) => Type::Bool,
             Values::Int(_) => Type::Integer,
             Values::Float(_) => Type::Float,
+            Values::Complex(..) => Type::Complex,
+            Values::Vector(_) => Type::Vector,
+            Values::Matrix(_) => Type::Matrix,
+            Values::Poly(_) => Type::Poly,
+            Values::Quantity(_) => Type::Quantity,
             Values::Stack(_) => Type::Stack,
             Values::List(_) => Type::List,
             Values::Set(_) => Type::Set,
@@ -130,14 +302,109 @@ impl ChainMap {
     }
 }
 
+/// The value stack. Entries sit behind `Rc` so copies of the stack, like undo
+/// snapshots or the one `try` rolls back to, share them. An entry is only
+/// copied when it is popped while still shared.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueStack(Vec<Rc<Values>>);
+
+impl ValueStack {
+    pub fn pop(&mut self) -> Option<Values> {
+        self.0.pop().map(Rc::unwrap_or_clone)
+    }
+
+    pub fn push(&mut self, value: Values) {
+        self.0.push(Rc::new(value));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Bottom first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Values> + ExactSizeIterator {
+        self.0.iter().map(|x| &**x)
+    }
+
+    /// The top `n` values, bottom first.
+    pub fn top(&self, n: usize) -> impl Iterator<Item = &Values> {
+        self.0[self.0.len() - n..].iter().map(|x| &**x)
+    }
+
+    /// The shared entries themselves, for rearranging the stack without
+    /// copying values or telling which entries changed.
+    pub fn entries(&self) -> &[Rc<Values>] {
+        &self.0
+    }
+
+    pub fn entries_mut(&mut self) -> &mut Vec<Rc<Values>> {
+        &mut self.0
+    }
+}
+
+impl From<Vec<Rc<Values>>> for ValueStack {
+    fn from(entries: Vec<Rc<Values>>) -> Self {
+        ValueStack(entries)
+    }
+}
+
+impl FromIterator<Values> for ValueStack {
+    fn from_iter<T: IntoIterator<Item = Values>>(iter: T) -> Self {
+        ValueStack(iter.into_iter().map(Rc::new).collect())
+    }
+}
+
+impl IntoIterator for ValueStack {
+    type Item = Values;
+    type IntoIter = std::iter::Map<std::vec::IntoIter<Rc<Values>>, fn(Rc<Values>) -> Values>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter().map(Rc::unwrap_or_clone)
+    }
+}
+
+impl std::ops::Index<usize> for ValueStack {
+    type Output = Values;
+
+    fn index(&self, index: usize) -> &Values {
+        &self.0[index]
+    }
+}
+
 use crate::language::parse::ParseCtx;
 
+use crate::language::number::{INT_DISPLAY_TYPES, IntFormat, NumberFormat};
 use crate::language::repr::Representation;
 impl Representation<(), ParseCtx> for Values {
     fn get_repr(&self, context: &ParseCtx) -> String {
+        self.display(context, NumberFormat::Fraction, IntFormat::Decimal)
+    }
+}
+
+impl Values {
+    /// Like `get_repr`, with every `Rat` inside written in `format` and every
+    /// `Int` in `int_format`.
+    pub fn display(
+        &self,
+        context: &ParseCtx,
+        format: NumberFormat,
+        int_format: IntFormat,
+    ) -> String {
         match self {
-            Values::Float(i) => format!("{i}"),
-            Values::Int(i) => format!("{i}"),
+            Values::Float(i) => format.format(i),
+            Values::Complex(re, im) => format.format_complex(re, im),
+            Values::Vector(v) => format.format_vector(v),
+            Values::Matrix(m) => {
+                let rows: Vec<_> = m.rows().map(|row| format.format_vector(row)).collect();
+                format!("Mat({})", rows.join(", "))
+            }
+            Values::Poly(p) => format.format_poly(p),
+            Values::Quantity(q) => format.format_quantity(q),
+            Values::Int(i) => int_format.format(i),
             Values::Bool(i) => format!("{i}"),
             Values::Stack(s) => s.get_repr(context),
             Values::List(l) => {
@@ -145,7 +412,7 @@ impl Representation<(), ParseCtx> for Values {
                 ret.push_str("List(");
                 let len = l.len();
                 l.iter().enumerate().for_each(|(idx, x)| {
-                    ret.push_str(&x.get_repr(context));
+                    ret.push_str(&x.display(context, format, int_format));
                     if idx != len - 1 {
                         ret.push_str(", ");
                     }
@@ -158,7 +425,7 @@ impl Representation<(), ParseCtx> for Values {
                 let len = l.len();
                 ret.push_str("Set(");
                 l.iter().enumerate().for_each(|(idx, x)| {
-                    ret.push_str(&x.get_repr(context));
+                    ret.push_str(&x.display(context, format, int_format));
                     if idx != len - 1 {
                         ret.push_str(", ");
                     }
@@ -171,11 +438,11 @@ impl Representation<(), ParseCtx> for Values {
                 ret.push_str("Map(");
                 let len = map.len();
                 map.iter().enumerate().for_each(|(idx, (x, y))| {
-                    ret.push_str("[ ");
-                    ret.push_str(&x.get_repr(context));
+                    ret.push_str("List(");
+                    ret.push_str(&x.display(context, format, int_format));
                     ret.push_str(", ");
-                    ret.push_str(&y.get_repr(context));
-                    ret.push_str("] ");
+                    ret.push_str(&y.display(context, format, int_format));
+                    ret.push(')');
                     if idx != len - 1 {
                         ret.push_str(", ");
                     }
@@ -185,7 +452,12 @@ impl Representation<(), ParseCtx> for Values {
             }
             Values::Custom { name, tag, values } => {
                 let mut ret = String::new();
-                ret.push_str(context.lookup_type_name(*name).as_str());
+                let typ = context.lookup_type_name(*name);
+                let int_format = INT_DISPLAY_TYPES
+                    .iter()
+                    .find(|(x, ..)| tag.is_none() && *x == typ)
+                    .map_or(int_format, |(.., format)| *format);
+                ret.push_str(typ.as_str());
                 tag.map(|tag| {
                     ret.push_str("::");
                     ret.push_str(context.lookup_tag_name(tag).as_str())
@@ -194,7 +466,7 @@ impl Representation<(), ParseCtx> for Values {
                 values.as_ref().map(|x| {
                     let len = x.len();
                     x.iter().enumerate().for_each(|(idx, value)| {
-                        ret.push_str(&value.get_repr(context));
+                        ret.push_str(&value.display(context, format, int_format));
                         if idx != len - 1 {
                             ret.push(',');
                         }
@@ -277,8 +549,47 @@ impl Representation<(), ParseCtx> for EvalError {
                     context.lookup_type_name(*name)
                 )
             }
+            EvalError::TypeConstructorTypeMismatch(name) => {
+                format!(
+                    "TypeConstructorTypeMismatch arguments don't fit the definition of {}",
+                    context.lookup_type_name(*name)
+                )
+            }
             EvalError::IndexOutOfBounds => "Index out of bounds".to_string(),
             EvalError::Underflow => "Underflow".to_string(),
+            EvalError::DivisionByZero => "Division by zero".to_string(),
+            EvalError::DimensionMismatch(a, b) => {
+                let shape = |x: &Vec<usize>| {
+                    x.iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                        .join("x")
+                };
+                format!("Dimension mismatch {} and {}", shape(a), shape(b))
+            }
+            EvalError::SingularMatrix => "Singular matrix".to_string(),
+            EvalError::VecExpectsNumberButGot(x) => {
+                format!("VecExpectsNumberButGot {}", x.get_repr(context))
+            }
+            EvalError::MatExpectsVecButGot(x) => {
+                format!("MatExpectsVecButGot {}", x.get_repr(context))
+            }
+            EvalError::PolyExpectsNumberButGot(x) => {
+                format!("PolyExpectsNumberButGot {}", x.get_repr(context))
+            }
+            EvalError::IncompatibleDimensions(x) => format!(
+                "Incompatible dimensions {} and {}",
+                x.0.get_repr(context),
+                x.1.get_repr(context)
+            ),
+            EvalError::UnitExpectsNumberButGot(x) => {
+                format!("UnitExpectsNumberButGot {}", x.get_repr(context))
+            }
+            EvalError::StatsExpectsNumberButGot(x) => {
+                format!("StatsExpectsNumberButGot {}", x.get_repr(context))
+            }
+            EvalError::EmptyCollection => "Empty collection".to_string(),
+            EvalError::Thrown(x) => format!("Uncaught {}", x.get_repr(context)),
         }
     }
 }
@@ -292,14 +603,30 @@ pub enum Flow {
 }
 
 pub trait Eval<T> {
-    fn eval(
-        &self,
-        values: &mut Vec<Values>,
-        env: &Env,
-        vars: &mut ChainMap,
-    ) -> Result<T, EvalError>;
+    fn eval(&self, values: &mut ValueStack, env: &Env, vars: &mut ChainMap)
+    -> Result<T, EvalError>;
 
     fn get_free_vars(&self, vars: &mut HashSet<usize>);
     fn get_vars(&self, vars: &mut std::collections::HashSet<usize>);
+    /// Collects the ids of the user functions and protocols called anywhere inside.
+    fn get_calls(&self, calls: &mut HashSet<usize>);
     fn replace_vars(self, free_vars: &std::collections::HashSet<usize>, vars: &ChainMap) -> Self;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::language::vm::VM;
+
+    #[test]
+    fn caught_fields() {
+        let mut vm = VM::default();
+        vm.parse_full_program(
+            "struct Pair { Int Int };
+            try { Pair(1) } catch | Error::TypeConstructorLenMismatch(expects got) => expects got, ;",
+        )
+        .unwrap();
+        vm.eval().unwrap();
+        let values: Vec<_> = vm.stack.iter().map(|x| vm.display(x)).collect();
+        assert_eq!(values, ["2", "1"]);
+    }
+}
@@ -0,0 +1,683 @@
+use pest::Parser;
+use pest::error::Error;
+use pest::iterators::Pair;
+
+use super::parse::{Rule, StackParser, clarify_error};
+
+/// Blocks and collections longer than this are split over several lines.
+const INLINE_WIDTH: usize = 60;
+/// Runs of atoms wrap once a line grows past this.
+const LINE_WIDTH: usize = 72;
+const INDENT: &str = "    ";
+
+/// Parses `source` and prints it back canonically indented: one top-level
+/// item per line ending in `;`, blank lines around definitions, long blocks
+/// and collections broken up, `match` arms on their own lines with aligned
+/// `=>`. Comments are kept. Input that is a single item without a trailing
+/// `;` is printed without one.
+pub fn format_source(source: &str) -> Result<String, Box<Error<Rule>>> {
+    let (pairs, terminated) = match StackParser::parse(Rule::defsAndExprs, source) {
+        Ok(pairs) => (pairs, true),
+        Err(program_err) => match StackParser::parse(Rule::justExprOrDef, source) {
+            Ok(pairs) => (pairs, false),
+            Err(_) => return Err(Box::new(clarify_error(program_err, source))),
+        },
+    };
+    let fmt = Formatter { source };
+    let mut out: Vec<String> = vec![];
+    let mut pos = 0;
+    let mut prev_is_expr = None;
+    for pair in pairs.filter(|x| x.as_rule() != Rule::EOI) {
+        let span = pair.as_span();
+        let is_expr = pair.as_rule() == Rule::expr;
+        let comments = fmt.comments(pos, span.start());
+        // Comments on the line of the previous item's `;` stay there.
+        let (trailing, leading) = split_trailing(&comments);
+        if let Some(last) = out.last_mut() {
+            for comment in trailing {
+                last.push(' ');
+                last.push_str(comment.text);
+            }
+        }
+        if prev_is_expr.is_some_and(|prev| !(prev && is_expr)) {
+            out.push(String::new());
+        }
+        out.extend(leading.iter().map(|x| x.text.to_string()));
+
+        let mut lines = fmt.item(pair);
+        if terminated {
+            terminate(&mut lines);
+        }
+        out.extend(lines);
+        pos = span.end();
+        prev_is_expr = Some(is_expr);
+    }
+    let comments = fmt.comments(pos, source.len());
+    let (trailing, leading) = split_trailing(&comments);
+    if let Some(last) = out.last_mut() {
+        for comment in trailing {
+            last.push(' ');
+            last.push_str(comment.text);
+        }
+    }
+    out.extend(leading.iter().map(|x| x.text.to_string()));
+
+    let mut ret = out
+        .iter()
+        .map(|x| x.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if terminated && !ret.is_empty() {
+        ret.push('\n');
+    }
+    Ok(ret)
+}
+
+/// Ends an item with `;`, on its own line after a multi-line `match`.
+fn terminate(lines: &mut Vec<String>) {
+    let multi_line = lines.len() > 1;
+    match lines.last_mut() {
+        Some(last) if last.ends_with(',') && multi_line => lines.push(";".to_string()),
+        Some(last) if last.ends_with(',') => last.push_str(" ;"),
+        Some(last) => last.push(';'),
+        None => {}
+    }
+}
+
+struct Comment<'a> {
+    text: &'a str,
+    /// Starts on the same source line as the code before it.
+    trailing: bool,
+    line: bool,
+}
+
+fn split_trailing<'a, 'b>(comments: &'b [Comment<'a>]) -> (&'b [Comment<'a>], &'b [Comment<'a>]) {
+    let n = comments.iter().take_while(|x| x.trailing).count();
+    comments.split_at(n)
+}
+
+/// Joins `next` onto `lines`: its first line continues the last line after
+/// `sep`, the rest follow as they are.
+fn append(lines: &mut Vec<String>, next: Vec<String>, sep: &str) {
+    let mut next = next.into_iter();
+    let Some(first) = next.next() else {
+        return;
+    };
+    match lines.last_mut() {
+        Some(last) if !last.trim().is_empty() => {
+            last.push_str(sep);
+            last.push_str(&first);
+        }
+        Some(last) => last.push_str(&first),
+        None => lines.push(first),
+    }
+    lines.extend(next);
+}
+
+fn indented(lines: Vec<String>) -> impl Iterator<Item = String> {
+    lines.into_iter().map(|x| {
+        if x.is_empty() {
+            x
+        } else {
+            format!("{INDENT}{x}")
+        }
+    })
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+}
+
+impl<'a> Formatter<'a> {
+    /// The comments in `source[start..end]`, a stretch between two tokens.
+    fn comments(&self, start: usize, end: usize) -> Vec<Comment<'a>> {
+        let gap = &self.source[start..end];
+        let mut ret = vec![];
+        let mut rest = 0;
+        while rest < gap.len() {
+            let found = [gap[rest..].find("//"), gap[rest..].find("/*")]
+                .into_iter()
+                .flatten()
+                .min();
+            let Some(offset) = found else {
+                break;
+            };
+            let from = rest + offset;
+            let line = gap[from..].starts_with("//");
+            let to = if line {
+                gap[from..].find('\n').map_or(gap.len(), |x| from + x)
+            } else {
+                gap[from..].find("*/").map_or(gap.len(), |x| from + x + 2)
+            };
+            let before = &self.source[..start + from];
+            let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+            let trailing = start + from > 0 && !before[line_start..].trim().is_empty();
+            ret.push(Comment {
+                text: gap[from..to].trim_end(),
+                trailing,
+                line,
+            });
+            rest = to;
+        }
+        ret
+    }
+
+    fn has_comment(&self, start: usize, end: usize) -> bool {
+        let text = &self.source[start..end];
+        text.contains("//") || text.contains("/*")
+    }
+
+    fn item(&self, pair: Pair<'a, Rule>) -> Vec<String> {
+        match pair.as_rule() {
+            Rule::def => {
+                let end = pair.as_span().end();
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap();
+                let head = format!("{} =", name.as_str());
+                self.definition(head, inner, name.as_span().end(), end)
+            }
+            Rule::unitDef => {
+                let end = pair.as_span().end();
+                let mut inner = pair.into_inner();
+                inner.next();
+                let name = inner.next().unwrap();
+                let head = format!("unit {} =", name.as_str());
+                self.definition(head, inner, name.as_span().end(), end)
+            }
+            Rule::protocol_def => {
+                let end = pair.as_span().end();
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap();
+                let types = inner.next().unwrap();
+                let types_end = types.as_span().end();
+                let head = format!(
+                    "{}({}) =",
+                    name.as_str(),
+                    types
+                        .into_inner()
+                        .map(|x| self.typ(x))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                );
+                self.definition(head, inner, types_end, end)
+            }
+            Rule::moduleDef => {
+                let mut inner = pair.into_inner();
+                inner.next();
+                let name = inner.next().unwrap();
+                self.item_block(
+                    format!("module {}", name.as_str()),
+                    inner,
+                    name.as_span().end(),
+                )
+            }
+            Rule::implDef => {
+                let mut inner = pair.into_inner();
+                inner.next();
+                let interface = inner.next().unwrap();
+                inner.next();
+                let typ = inner.next().unwrap();
+                let typ_end = typ.as_span().end();
+                let head = format!("impl {} for {}", interface.as_str(), self.typ(typ));
+                self.item_block(head, inner, typ_end)
+            }
+            Rule::interfaceDef => {
+                let mut inner = pair.into_inner();
+                inner.next();
+                let name = inner.next().unwrap();
+                let methods: Vec<_> = inner.map(|x| x.as_str()).collect();
+                vec![format!(
+                    "interface {} {{ {} }}",
+                    name.as_str(),
+                    methods.join(" ")
+                )]
+            }
+            Rule::structDef => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap().as_str().to_string();
+                let mut head = format!("struct {name}");
+                let mut fields = vec![];
+                for x in inner {
+                    match x.as_rule() {
+                        Rule::typeParams => head.push_str(&self.type_params(x)),
+                        _ => fields.push(self.typ(x)),
+                    }
+                }
+                vec![format!("{head} {{ {} }}", fields.join(" "))]
+            }
+            Rule::enumDef => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap().as_str().to_string();
+                let mut head = format!("enum {name}");
+                let mut variants = vec![];
+                for x in inner {
+                    match x.as_rule() {
+                        Rule::typeParams => head.push_str(&self.type_params(x)),
+                        _ => {
+                            let mut parts = x.into_inner();
+                            let tag = parts.next().unwrap().as_str();
+                            let fields: Vec<_> = parts.map(|x| self.typ(x)).collect();
+                            variants.push(format!("| {tag}({})", fields.join(" ")));
+                        }
+                    }
+                }
+                let inline = format!("{head} {{ {} }}", variants.join(" "));
+                if inline.len() <= INLINE_WIDTH {
+                    vec![inline]
+                } else {
+                    let mut ret = vec![format!("{head} {{")];
+                    ret.extend(indented(variants));
+                    ret.push("}".to_string());
+                    ret
+                }
+            }
+            Rule::importStmt | Rule::useStmt => vec![normalize(pair.as_str())],
+            Rule::expr => {
+                let span = pair.as_span();
+                self.seq(pair.into_inner(), span.start(), span.end())
+            }
+            _ => vec![normalize(pair.as_str())],
+        }
+    }
+
+    /// `head` followed by the body of a definition. A body starting with a
+    /// `match` puts its arms on their own lines below the head.
+    fn definition(
+        &self,
+        head: String,
+        body: impl Iterator<Item = Pair<'a, Rule>>,
+        body_start: usize,
+        end: usize,
+    ) -> Vec<String> {
+        let body: Vec<_> = body.collect();
+        let starts_with_match = body
+            .first()
+            .is_some_and(|x| x.as_rule() == Rule::matchBlock);
+        let lines = self.seq(body.into_iter(), body_start, end);
+        let mut ret = vec![head];
+        if starts_with_match && lines.len() > 1 {
+            ret.extend(indented(lines));
+        } else {
+            append(&mut ret, lines, " ");
+        }
+        ret
+    }
+
+    /// `head { item; item; }` for modules and impls.
+    fn item_block(
+        &self,
+        head: String,
+        items: impl Iterator<Item = Pair<'a, Rule>>,
+        start: usize,
+    ) -> Vec<String> {
+        let mut body = vec![];
+        let mut pos = start;
+        for item in items {
+            let span = item.as_span();
+            body.extend(
+                self.comments(pos, span.start())
+                    .iter()
+                    .map(|x| x.text.to_string()),
+            );
+            let mut lines = self.item(item);
+            terminate(&mut lines);
+            body.extend(lines);
+            pos = span.end();
+        }
+        if body.is_empty() {
+            return vec![format!("{head} {{}}")];
+        }
+        let mut ret = vec![format!("{head} {{")];
+        ret.extend(indented(body));
+        ret.push("}".to_string());
+        ret
+    }
+
+    /// Atoms separated by spaces, with the comments found between them.
+    /// `start..end` is the stretch of source the atoms were parsed from.
+    fn seq(
+        &self,
+        atoms: impl Iterator<Item = Pair<'a, Rule>>,
+        start: usize,
+        end: usize,
+    ) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        let mut pos = start;
+        let mut after_match = false;
+        for atom in atoms {
+            let span = atom.as_span();
+            self.push_comments(&mut lines, pos, span.start());
+            let is_match = atom.as_rule() == Rule::matchBlock;
+            let rendered = self.atom(atom);
+            let too_long = lines
+                .last()
+                .is_some_and(|x| !x.is_empty() && x.len() + 1 + rendered[0].len() > LINE_WIDTH);
+            // Arms read top to bottom, so nothing shares their lines.
+            let own_line = after_match || (is_match && rendered.len() > 1);
+            if (too_long || own_line) && lines.last().is_some_and(|x| !x.is_empty()) {
+                lines.push(String::new());
+            }
+            append(&mut lines, rendered.clone(), " ");
+            after_match = is_match && rendered.len() > 1;
+            pos = span.end();
+        }
+        self.push_comments(&mut lines, pos, end);
+        while lines.last().is_some_and(|x| x.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    fn push_comments(&self, lines: &mut Vec<String>, start: usize, end: usize) {
+        for comment in self.comments(start, end) {
+            if !comment.trailing && lines.last().is_some_and(|x| !x.is_empty()) {
+                lines.push(String::new());
+            }
+            append(lines, vec![comment.text.to_string()], " ");
+            if comment.line {
+                lines.push(String::new());
+            }
+        }
+    }
+
+    /// `open inner close` on one line if short enough, else with the inner
+    /// lines indented between the brackets.
+    fn bracketed(&self, open: &str, close: &str, pair: Pair<'a, Rule>) -> Vec<String> {
+        let span = pair.as_span();
+        let inner_start = span.start() + pair.as_str().find(open).map_or(0, |x| x + open.len());
+        let inner_end = span.end() - close.len();
+        let inner = self.seq(pair.into_inner(), inner_start, inner_end);
+        self.wrap(
+            open,
+            close,
+            inner,
+            self.has_comment(span.start(), span.end()),
+        )
+    }
+
+    fn wrap(&self, open: &str, close: &str, inner: Vec<String>, force: bool) -> Vec<String> {
+        match inner.as_slice() {
+            [] => vec![format!("{open}{close}")],
+            [line] if !force && line.len() <= INLINE_WIDTH => vec![format!("{open}{line}{close}")],
+            _ => {
+                let mut ret = vec![open.to_string()];
+                ret.extend(indented(inner));
+                ret.push(close.to_string());
+                ret
+            }
+        }
+    }
+
+    fn atom(&self, pair: Pair<'a, Rule>) -> Vec<String> {
+        match pair.as_rule() {
+            Rule::block => self.bracketed("{", "}", pair),
+            Rule::stack => self.bracketed("[", "]", pair),
+            Rule::take => {
+                let mut inner = pair.into_inner();
+                let vars: Vec<_> = inner
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .map(|x| x.as_str())
+                    .collect();
+                let mut ret = vec![format!("|{}|", vars.join(" "))];
+                append(&mut ret, self.atom(inner.next().unwrap()), " ");
+                ret
+            }
+            Rule::whileLoop => {
+                let mut inner = pair.into_inner();
+                let cond = inner.next().unwrap();
+                let span = cond.as_span();
+                let mut ret = vec!["while".to_string()];
+                append(
+                    &mut ret,
+                    self.seq(cond.into_inner(), span.start() + "while".len(), span.end()),
+                    " ",
+                );
+                append(&mut ret, self.atom(inner.next().unwrap()), " ");
+                ret
+            }
+            Rule::ifTrue => {
+                let mut ret = vec!["?".to_string()];
+                append(&mut ret, self.atom(pair.into_inner().next().unwrap()), " ");
+                ret
+            }
+            Rule::tryCatch => {
+                let mut inner = pair.into_inner();
+                inner.next();
+                let mut ret = vec!["try".to_string()];
+                append(&mut ret, self.atom(inner.next().unwrap()), " ");
+                inner.next();
+                append(&mut ret, vec!["catch".to_string()], " ");
+                let arms = self.match_block(inner.next().unwrap());
+                if arms.len() == 1 {
+                    append(&mut ret, arms, " ");
+                } else {
+                    ret.extend(indented(arms));
+                }
+                ret
+            }
+            Rule::matchBlock => self.match_block(pair),
+            Rule::list
+            | Rule::set
+            | Rule::map
+            | Rule::vector
+            | Rule::matrix
+            | Rule::poly
+            | Rule::variantInst
+            | Rule::typeInst => self.collection(pair),
+            _ => vec![normalize(pair.as_str())],
+        }
+    }
+
+    /// One line per arm with the `=>` aligned. A single short arm stays on
+    /// one line.
+    fn match_block(&self, pair: Pair<'a, Rule>) -> Vec<String> {
+        let force = self.has_comment(pair.as_span().start(), pair.as_span().end());
+        let mut arms = vec![];
+        for elem in pair.into_inner() {
+            let mut head = "|".to_string();
+            let mut body = vec![];
+            for part in elem.into_inner() {
+                match part.as_rule() {
+                    Rule::patterns => {
+                        for pattern in part.into_inner() {
+                            head.push(' ');
+                            head.push_str(&normalize(pattern.as_str()));
+                        }
+                    }
+                    Rule::when => {
+                        let span = part.as_span();
+                        let cond =
+                            self.seq(part.into_inner(), span.start() + "when".len(), span.end());
+                        head.push_str(" when ");
+                        head.push_str(&cond.join(" "));
+                    }
+                    _ => {
+                        let span = part.as_span();
+                        body = self.seq(part.into_inner(), span.start(), span.end());
+                    }
+                }
+            }
+            arms.push((head, body));
+        }
+        let width = arms.iter().map(|(head, _)| head.len()).max().unwrap_or(0);
+        let mut ret = vec![];
+        let single = arms.len() == 1;
+        for (head, body) in arms {
+            let mut lines = if single {
+                vec![format!("{head} =>")]
+            } else {
+                vec![format!("{head:width$} =>")]
+            };
+            append(&mut lines, body, " ");
+            if let Some(last) = lines.last_mut() {
+                last.push(',');
+            }
+            ret.extend(lines);
+        }
+        if single && ret.len() == 1 && (force || ret[0].len() > INLINE_WIDTH) {
+            // A lone arm that can't stay inline still goes on its own line.
+            ret.insert(0, String::new());
+        }
+        ret
+    }
+
+    /// `List(a b, c)`, `Point(1 2)`, `Option::Some(x)`: elements are the
+    /// comma separated runs of atoms.
+    fn collection(&self, pair: Pair<'a, Rule>) -> Vec<String> {
+        let span = pair.as_span();
+        let open = pair.as_str().find('(').unwrap();
+        let head = pair.as_str()[..open].trim().to_string();
+        let force = self.has_comment(span.start(), span.end());
+        let mut elems: Vec<Vec<Pair<'a, Rule>>> = vec![];
+        let mut current = vec![];
+        let mut pos = span.start() + open + 1;
+        for atom in pair.into_inner() {
+            if matches!(atom.as_rule(), Rule::variantName | Rule::typName) {
+                continue;
+            }
+            let atom_span = atom.as_span();
+            let gap = &self.source[pos..atom_span.start()];
+            if strip_comments(gap).contains(',') {
+                elems.push(std::mem::take(&mut current));
+            }
+            current.push(atom);
+            pos = atom_span.end();
+        }
+        if !current.is_empty() {
+            elems.push(current);
+        }
+        let mut pos = span.start() + open + 1;
+        let rendered: Vec<Vec<String>> = elems
+            .into_iter()
+            .map(|atoms| {
+                let end = atoms.last().unwrap().as_span().end();
+                let lines = self.seq(atoms.into_iter(), pos, end);
+                pos = end;
+                lines
+            })
+            .collect();
+        let inline = rendered.iter().all(|x| x.len() == 1)
+            && !force
+            && rendered.iter().map(|x| x[0].len() + 2).sum::<usize>() <= INLINE_WIDTH;
+        if inline {
+            let elems: Vec<_> = rendered.into_iter().map(|mut x| x.remove(0)).collect();
+            return vec![format!("{head}({})", elems.join(", "))];
+        }
+        let mut ret = vec![format!("{head}(")];
+        let count = rendered.len();
+        for (i, mut lines) in rendered.into_iter().enumerate() {
+            if i + 1 < count
+                && let Some(last) = lines.last_mut()
+            {
+                last.push(',');
+            }
+            ret.extend(indented(lines));
+        }
+        ret.push(")".to_string());
+        ret
+    }
+
+    fn type_params(&self, pair: Pair<'a, Rule>) -> String {
+        let params: Vec<_> = pair.into_inner().map(|x| x.as_str()).collect();
+        format!("<{}>", params.join(", "))
+    }
+
+    fn typ(&self, pair: Pair<'a, Rule>) -> String {
+        match pair.as_rule() {
+            Rule::typeApp => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap().as_str();
+                let args: Vec<_> = inner.map(|x| self.typ(x)).collect();
+                format!("{name}<{}>", args.join(", "))
+            }
+            Rule::constrainedGeneric => {
+                let mut inner = pair.into_inner();
+                let generic = inner.next().unwrap().as_str();
+                let interface = inner.next().unwrap().as_str();
+                format!("{generic}: {interface}")
+            }
+            _ => pair.as_str().to_string(),
+        }
+    }
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut ret = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.find('\n').map_or("", |x| &after[x..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |x| &after[x + 2..]);
+        } else {
+            let c = rest.chars().next().unwrap();
+            ret.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    ret
+}
+
+/// Collapses whitespace in a leaf such as a pattern: single spaces between
+/// words, none inside brackets or before commas.
+fn normalize(text: &str) -> String {
+    let words: Vec<_> = text.split_whitespace().collect();
+    let mut ret = words.join(" ");
+    for (from, to) in [
+        ("( ", "("),
+        (" )", ")"),
+        (" ,", ","),
+        ("< ", "<"),
+        (" >", ">"),
+    ] {
+        while ret.contains(from) {
+            ret = ret.replace(from, to);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCES: &[&str] = &[
+        "1 2 add; 3 dup mul;",
+        "sign = | 0 => 0, | x when x 0 lt => -1, | _ => 1, ;",
+        "struct Point { Int Int }; enum Shape { | Circle(Rat) | Rect(Rat Rat) };",
+        "module geo { sq = |x| {x x mul}; impl Sized for Box {}; };",
+        "List(1 2, 3) [while dup 10 lt {1 add}] try { 1 throw } catch | x => x, ;",
+        "// lead\nx = 1; // after\n/* block */ 2 /* inner */ 3;\n// tail",
+    ];
+
+    #[test]
+    fn idempotent() {
+        for source in SOURCES {
+            let once = format_source(source).unwrap();
+            assert_eq!(format_source(&once).unwrap(), once, "{source}");
+        }
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let source = "// lead\nx = 1; // after\n/* block */ 2 /* inner */ 3;\n// tail";
+        let expected = "// lead\nx = 1; // after\n\n/* block */\n2 /* inner */ 3;\n// tail\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn aligned_arms() {
+        let source = "sign = | 0 => 0, | x when x 0 lt => -1, | _ => 1, ;";
+        let expected = "\
+sign =
+    | 0             => 0,
+    | x when x 0 lt => -1,
+    | _             => 1,
+;
+";
+        assert_eq!(format_source(source).unwrap(), expected);
+        assert_eq!(format_source("3 | x => x,").unwrap(), "3 | x => x,");
+    }
+}
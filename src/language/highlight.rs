@@ -0,0 +1,129 @@
+use std::ops::Range;
+
+use pest::Parser;
+
+use super::parse::{Rule, StackParser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Comment,
+    Number,
+    Bool,
+    Keyword,
+    Var,
+    Type,
+    Variant,
+    Name,
+}
+
+impl Token {
+    /// CSS colour the editor draws this token in.
+    pub fn color(&self) -> &'static str {
+        match self {
+            Token::Plain => "#f0f6fc",
+            Token::Comment => "#6e7681",
+            Token::Number => "#79c0ff",
+            Token::Bool => "#ff7b72",
+            Token::Keyword => "#d2a8ff",
+            Token::Var => "#7ee787",
+            Token::Type => "#f79000",
+            Token::Variant => "#ffa657",
+            Token::Name => "#f0f6fc",
+        }
+    }
+}
+
+/// Splits `source` into consecutive classified spans covering all of it,
+/// using the grammar's own token rules so the colours agree with the parser.
+pub fn highlight(source: &str) -> Vec<(Range<usize>, Token)> {
+    let mut ret = vec![];
+    let mut pos = 0;
+    let Ok(pairs) = StackParser::parse(Rule::highlight, source) else {
+        return vec![(0..source.len(), Token::Plain)];
+    };
+    for pair in pairs.flatten() {
+        let token = match pair.as_rule() {
+            Rule::hlComment => Token::Comment,
+            Rule::integer | Rule::float | Rule::fraction | Rule::complex => Token::Number,
+            Rule::bools => Token::Bool,
+            Rule::hlKeyword => Token::Keyword,
+            Rule::variantName => Token::Variant,
+            Rule::typName => Token::Type,
+            Rule::identifier if pair.as_str().starts_with('_') => Token::Var,
+            Rule::identifier | Rule::qualifiedName => Token::Name,
+            _ => continue,
+        };
+        let span = pair.as_span();
+        if span.start() < pos {
+            // The `typName` inside an already emitted `variantName`.
+            continue;
+        }
+        if span.start() > pos {
+            ret.push((pos..span.start(), Token::Plain));
+        }
+        ret.push((span.start()..span.end(), token));
+        pos = span.end();
+    }
+    if pos < source.len() {
+        ret.push((pos..source.len(), Token::Plain));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The classified spans of `source` with their text, leaving out the
+    /// whitespace between them.
+    fn tokens(source: &str) -> Vec<(&str, Token)> {
+        highlight(source)
+            .into_iter()
+            .map(|(range, token)| (&source[range], token))
+            .filter(|(text, _)| !text.trim().is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn classified() {
+        use Token::*;
+        assert_eq!(
+            tokens("1 0xff 1/2 2.5 3+4i true"),
+            [
+                ("1", Number),
+                ("0xff", Number),
+                ("1/2", Number),
+                ("2.5", Number),
+                ("3+4i", Number),
+                ("true", Bool)
+            ]
+        );
+        assert_eq!(
+            tokens("while whiles _x geo::sq Shape::Circle Point // note"),
+            [
+                ("while", Keyword),
+                ("whiles", Name),
+                ("_x", Var),
+                ("geo::sq", Name),
+                ("Shape::Circle", Variant),
+                ("Point", Type),
+                ("// note", Comment)
+            ]
+        );
+    }
+
+    #[test]
+    fn covers_source() {
+        for source in ["", "1 add /* open", "1__0 é {|x| x}", "List(1 2) \"quote"] {
+            let spans = highlight(source);
+            let mut pos = 0;
+            for (range, _) in &spans {
+                assert_eq!(range.start, pos, "{source}");
+                pos = range.end;
+            }
+            assert_eq!(pos, source.len(), "{source}");
+        }
+        assert_eq!(tokens("1__0"), [("1__0", Token::Plain)]);
+    }
+}
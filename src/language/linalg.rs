@@ -0,0 +1,312 @@
+use malachite::Rational;
+use malachite::num::basic::traits::{One, Zero};
+
+use super::ast::Type;
+use super::eval::{EvalError, Values};
+
+/// Vectors and matrices holding more entries than this are refused instead
+/// of exhausting memory.
+const MAX_ELEMENTS: usize = 1 << 20;
+
+/// Operand types of `add`, `sub` and `mul` taken entry by entry.
+pub const ELEMENTWISE: [[Type; 2]; 2] =
+    [[Type::Vector, Type::Vector], [Type::Matrix, Type::Matrix]];
+
+/// Operand types of `mul` and `div` with a scalar, the scalar second.
+pub const SCALED: [[Type; 2]; 4] = [
+    [Type::Vector, Type::Integer],
+    [Type::Vector, Type::Float],
+    [Type::Matrix, Type::Integer],
+    [Type::Matrix, Type::Float],
+];
+
+/// A dense matrix of exact rationals, stored row by row.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<Rational>,
+}
+
+impl Matrix {
+    /// A matrix without entries is always `0x0`, so `Mat(Vec() Vec())` and
+    /// `Mat()` are the same value.
+    pub fn zeros(rows: usize, cols: usize) -> Result<Self, EvalError> {
+        if rows.saturating_mul(cols) > MAX_ELEMENTS {
+            return Err(EvalError::PrimitiveEvalErr);
+        }
+        let (rows, cols) = if rows == 0 || cols == 0 {
+            (0, 0)
+        } else {
+            (rows, cols)
+        };
+        Ok(Matrix {
+            rows,
+            cols,
+            data: vec![Rational::ZERO; rows * cols],
+        })
+    }
+
+    pub fn identity(n: usize) -> Result<Self, EvalError> {
+        let mut ret = Matrix::zeros(n, n)?;
+        for i in 0..n {
+            ret.data[i * n + i] = Rational::ONE;
+        }
+        Ok(ret)
+    }
+
+    /// The matrix with the given rows, which must all have the same length.
+    pub fn from_rows(rows: Vec<Vec<Rational>>) -> Result<Self, EvalError> {
+        let cols = rows.first().map_or(0, |x| x.len());
+        if let Some(row) = rows.iter().find(|x| x.len() != cols) {
+            return Err(EvalError::DimensionMismatch(vec![cols], vec![row.len()]));
+        }
+        if cols == 0 {
+            return Matrix::zeros(0, 0);
+        }
+        Ok(Matrix {
+            rows: rows.len(),
+            cols,
+            data: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Rational]> {
+        // `chunks` refuses a size of zero, a `0x0` matrix has no rows anyway.
+        self.data.chunks(self.cols.max(1))
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        vec![self.rows, self.cols]
+    }
+
+    fn get(&self, i: usize, j: usize) -> &Rational {
+        &self.data[i * self.cols + j]
+    }
+
+    fn check_square(&self) -> Result<usize, EvalError> {
+        if self.rows != self.cols {
+            return Err(EvalError::DimensionMismatch(
+                self.shape(),
+                vec![self.rows, self.rows],
+            ));
+        }
+        Ok(self.rows)
+    }
+}
+
+/// Entries of a `Vec` or `Mat` literal or of `l2v`, which must be numbers.
+pub fn entry(value: Values) -> Result<Rational, EvalError> {
+    match value {
+        Values::Int(i) => Ok(Rational::from(i)),
+        Values::Float(f) => Ok(f),
+        other => Err(EvalError::VecExpectsNumberButGot(other)),
+    }
+}
+
+fn scalar(value: Values) -> Rational {
+    match value {
+        Values::Int(i) => Rational::from(i),
+        Values::Float(f) => f,
+        _ => unreachable!(),
+    }
+}
+
+fn zip_with(
+    a: &[Rational],
+    b: &[Rational],
+    f: impl Fn(&Rational, &Rational) -> Rational,
+) -> Vec<Rational> {
+    a.iter().zip(b).map(|(x, y)| f(x, y)).collect()
+}
+
+/// `a` and `b`, both vectors or both matrices of the same shape, combined
+/// entry by entry.
+pub fn elementwise(
+    a: Values,
+    b: Values,
+    f: impl Fn(&Rational, &Rational) -> Rational,
+) -> Result<Values, EvalError> {
+    match (a, b) {
+        (Values::Vector(a), Values::Vector(b)) => {
+            if a.len() != b.len() {
+                return Err(EvalError::DimensionMismatch(vec![a.len()], vec![b.len()]));
+            }
+            Ok(Values::Vector(zip_with(&a, &b, f)))
+        }
+        (Values::Matrix(a), Values::Matrix(b)) => {
+            if a.shape() != b.shape() {
+                return Err(EvalError::DimensionMismatch(a.shape(), b.shape()));
+            }
+            let data = zip_with(&a.data, &b.data, f);
+            Ok(Values::Matrix(Matrix { data, ..a }))
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Every entry of the vector or matrix `a` combined with the scalar `b`.
+pub fn scaled(a: Values, b: Values, f: impl Fn(&Rational, &Rational) -> Rational) -> Values {
+    let b = scalar(b);
+    match a {
+        Values::Vector(a) => Values::Vector(a.iter().map(|x| f(x, &b)).collect()),
+        Values::Matrix(a) => {
+            let data = a.data.iter().map(|x| f(x, &b)).collect();
+            Values::Matrix(Matrix { data, ..a })
+        }
+        _ => unreachable!(),
+    }
+}
+
+pub fn dot(a: &[Rational], b: &[Rational]) -> Result<Rational, EvalError> {
+    if a.len() != b.len() {
+        return Err(EvalError::DimensionMismatch(vec![a.len()], vec![b.len()]));
+    }
+    Ok(a.iter().zip(b).map(|(x, y)| x * y).sum())
+}
+
+pub fn transpose(a: &Matrix) -> Matrix {
+    let mut data = Vec::with_capacity(a.data.len());
+    for j in 0..a.cols {
+        for i in 0..a.rows {
+            data.push(a.get(i, j).clone());
+        }
+    }
+    Matrix {
+        rows: a.cols,
+        cols: a.rows,
+        data,
+    }
+}
+
+pub fn matmul(a: &Matrix, b: &Matrix) -> Result<Matrix, EvalError> {
+    if a.cols != b.rows {
+        return Err(EvalError::DimensionMismatch(a.shape(), b.shape()));
+    }
+    let mut ret = Matrix::zeros(a.rows, b.cols)?;
+    let columns = transpose(b);
+    for (i, row) in a.rows().enumerate() {
+        for (j, col) in columns.rows().enumerate() {
+            ret.data[i * b.cols + j] = dot(row, col)?;
+        }
+    }
+    Ok(ret)
+}
+
+/// `a v`, with `v` as a column.
+pub fn mat_vec(a: &Matrix, v: &[Rational]) -> Result<Vec<Rational>, EvalError> {
+    if a.cols != v.len() {
+        return Err(EvalError::DimensionMismatch(a.shape(), vec![v.len()]));
+    }
+    a.rows().map(|row| dot(row, v)).collect()
+}
+
+/// `v a`, with `v` as a row.
+pub fn vec_mat(v: &[Rational], a: &Matrix) -> Result<Vec<Rational>, EvalError> {
+    if a.rows != v.len() {
+        return Err(EvalError::DimensionMismatch(vec![v.len()], a.shape()));
+    }
+    transpose(a).rows().map(|col| dot(v, col)).collect()
+}
+
+/// Brings `a` into reduced row echelon form in place. Returns the rank and,
+/// for square `a`, the determinant.
+fn reduce(a: &mut Matrix) -> (usize, Rational) {
+    let mut rank = 0;
+    let mut det = Rational::ONE;
+    for col in 0..a.cols {
+        let Some(pivot) = (rank..a.rows).find(|i| *a.get(*i, col) != 0u32) else {
+            det = Rational::ZERO;
+            continue;
+        };
+        if pivot != rank {
+            for j in 0..a.cols {
+                a.data.swap(pivot * a.cols + j, rank * a.cols + j);
+            }
+            det = -det;
+        }
+        let p = a.get(rank, col).clone();
+        det *= &p;
+        for j in 0..a.cols {
+            a.data[rank * a.cols + j] /= &p;
+        }
+        for i in 0..a.rows {
+            let factor = a.get(i, col).clone();
+            if i == rank || factor == 0u32 {
+                continue;
+            }
+            for j in 0..a.cols {
+                let x = &factor * a.get(rank, j);
+                a.data[i * a.cols + j] -= x;
+            }
+        }
+        rank += 1;
+        if rank == a.rows {
+            break;
+        }
+    }
+    (rank, det)
+}
+
+pub fn rank(a: &Matrix) -> usize {
+    reduce(&mut a.clone()).0
+}
+
+pub fn det(a: &Matrix) -> Result<Rational, EvalError> {
+    let n = a.check_square()?;
+    if n == 0 {
+        return Ok(Rational::ONE);
+    }
+    let (rank, det) = reduce(&mut a.clone());
+    Ok(if rank < n { Rational::ZERO } else { det })
+}
+
+/// `a` with the columns of `b` appended.
+fn augment(a: &Matrix, b: &Matrix) -> Matrix {
+    let data = a
+        .rows()
+        .zip(b.rows())
+        .flat_map(|(x, y)| x.iter().chain(y).cloned())
+        .collect();
+    Matrix {
+        rows: a.rows,
+        cols: a.cols + b.cols,
+        data,
+    }
+}
+
+/// The `x` with `a x = b` for square, invertible `a`, `b` having as many
+/// rows as `a`.
+fn solve_columns(a: &Matrix, b: &Matrix) -> Result<Matrix, EvalError> {
+    let n = a.check_square()?;
+    if b.rows != n {
+        return Err(EvalError::DimensionMismatch(a.shape(), b.shape()));
+    }
+    if n == 0 {
+        return Matrix::zeros(0, 0);
+    }
+    let mut m = augment(a, b);
+    reduce(&mut m);
+    if (0..n).any(|i| *m.get(i, i) != 1u32) {
+        return Err(EvalError::SingularMatrix);
+    }
+    let data = m.rows().flat_map(|row| row[n..].to_vec()).collect();
+    Ok(Matrix {
+        rows: n,
+        cols: b.cols,
+        data,
+    })
+}
+
+pub fn inverse(a: &Matrix) -> Result<Matrix, EvalError> {
+    solve_columns(a, &Matrix::identity(a.check_square()?)?)
+}
+
+pub fn solve(a: &Matrix, b: &[Rational]) -> Result<Vec<Rational>, EvalError> {
+    let column = Matrix {
+        rows: b.len(),
+        cols: 1,
+        data: b.to_vec(),
+    };
+    Ok(solve_columns(a, &column)?.data)
+}
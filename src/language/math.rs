@@ -0,0 +1,362 @@
+use std::cmp::Ordering;
+
+use malachite::num::arithmetic::traits::{
+    Abs, CheckedRoot, CheckedSqrt, FloorLogBase2, FloorSqrt, Pow, RoundToMultipleOfPowerOf2,
+};
+use malachite::num::basic::traits::{One, Zero};
+use malachite::num::conversion::traits::RoundingFrom;
+use malachite::num::logic::traits::SignificantBits;
+use malachite::rounding_modes::RoundingMode;
+use malachite::{Integer, Natural, Rational};
+
+use super::eval::EvalError;
+use super::number::decimal_exponent;
+
+/// Significant decimal digits irrational results are computed to, unless
+/// the session asks for another precision.
+pub const DEFAULT_PRECISION: usize = 20;
+pub const MAX_PRECISION: usize = 1000;
+
+/// Results and exact powers are refused beyond about `2^MAX_BITS`, roughly
+/// 300000 decimal digits, instead of exhausting memory.
+const MAX_BITS: u64 = 1 << 20;
+
+/// Extra bits carried through intermediate steps so the rounding errors of
+/// a series don't reach the digits that are returned.
+const GUARD_BITS: u64 = 32;
+
+fn working_bits(digits: usize) -> u64 {
+    digits as u64 * 3322 / 1000 + 1 + GUARD_BITS
+}
+
+fn log2(x: &Rational) -> i64 {
+    x.abs().floor_log_base_2()
+}
+
+/// `x` rounded to `bits` significant bits.
+fn round_bits(x: Rational, bits: u64) -> Rational {
+    if x == 0u32 {
+        return x;
+    }
+    let pow = log2(&x) - bits as i64;
+    x.round_to_multiple_of_power_of_2(pow, RoundingMode::Nearest)
+        .0
+}
+
+/// `x` rounded to `digits` significant decimal digits, the form every
+/// approximate result is returned in.
+pub fn round_digits(x: Rational, digits: usize) -> Rational {
+    if x == 0u32 {
+        return x;
+    }
+    let (n, d) = x.to_numerator_and_denominator();
+    let shift = digits as i64 - 1 - decimal_exponent(&n, &d);
+    let scale = Rational::from(10).pow(shift);
+    let (q, _) = Integer::rounding_from(x * &scale, RoundingMode::Nearest);
+    Rational::from(q) / scale
+}
+
+/// `sum z^(2k+1) / (2k+1)`, alternating in sign for `atan`, for `|z| < 1`.
+fn odd_series(z: Rational, bits: u64, alternate: bool) -> Rational {
+    let z2 = round_bits(&z * &z, bits);
+    let mut power = z.clone();
+    let mut sum = z;
+    let mut k = 1u64;
+    loop {
+        power = round_bits(power * &z2, bits);
+        k += 2;
+        let term = round_bits(&power / Rational::from(k), bits);
+        if term == 0u32 || log2(&term) < log2(&sum) - bits as i64 {
+            return sum;
+        }
+        if alternate && k % 4 == 3 {
+            sum -= term;
+        } else {
+            sum += term;
+        }
+    }
+}
+
+fn ln2(bits: u64) -> Rational {
+    odd_series(Rational::from_signeds(1, 3), bits, false) * Rational::from(2)
+}
+
+fn pi_bits(bits: u64) -> Rational {
+    let a = odd_series(Rational::from_signeds(1, 5), bits + 4, true);
+    let b = odd_series(Rational::from_signeds(1, 239), bits + 4, true);
+    round_bits(a * Rational::from(16) - b * Rational::from(4), bits)
+}
+
+pub fn pi(digits: usize) -> Rational {
+    round_digits(pi_bits(working_bits(digits)), digits)
+}
+
+/// `sqrt(x)`, exact when `x` is the square of a rational.
+pub fn sqrt(x: &Rational, digits: usize) -> Result<Rational, EvalError> {
+    if *x < 0u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    if let Some(root) = x.checked_sqrt() {
+        return Ok(root);
+    }
+    Ok(round_digits(sqrt_bits(x, working_bits(digits)), digits))
+}
+
+/// `sqrt(x)` for `x > 0`, truncated to about `bits` significant bits.
+fn sqrt_bits(x: &Rational, bits: u64) -> Rational {
+    let shift = (bits as i64 - log2(x) / 2).max(0) as u64;
+    let (n, d) = x.to_numerator_and_denominator();
+    let root = ((n << (2 * shift)) / d).floor_sqrt();
+    Rational::from(root) >> shift
+}
+
+/// `exp(x)` for `|x| <= 1/2`, accurate to about `2^-bits`.
+fn exp_small(x: &Rational, bits: u64) -> Rational {
+    let mut term = Rational::ONE;
+    let mut sum = Rational::ONE;
+    let mut k = 0u64;
+    loop {
+        k += 1;
+        term = round_bits(term * x / Rational::from(k), bits);
+        if term == 0u32 || log2(&term) < -(bits as i64) {
+            return sum;
+        }
+        sum += &term;
+    }
+}
+
+pub fn exp(x: &Rational, digits: usize) -> Result<Rational, EvalError> {
+    if *x == 0u32 {
+        return Ok(Rational::ONE);
+    }
+    // x = n ln 2 + r with |r| <= ln 2 / 2, so exp(x) = 2^n exp(r).
+    let (n, _) = Integer::rounding_from(x / ln2(64), RoundingMode::Nearest);
+    if n.significant_bits() > MAX_BITS.significant_bits() {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    let n = i64::try_from(&n).map_err(|_| EvalError::PrimitiveEvalErr)?;
+    let bits = working_bits(digits) + n.unsigned_abs().significant_bits();
+    let r = round_bits(x - ln2(bits) * Rational::from(n), bits);
+    Ok(round_digits(exp_small(&r, bits) << n, digits))
+}
+
+fn ln_bits(x: &Rational, bits: u64) -> Rational {
+    // x = 2^k m with 2/3 <= m <= 4/3, ln(m) = 2 atanh((m - 1) / (m + 1)).
+    let mut k = log2(x);
+    let mut m = x >> k;
+    if m > Rational::from_signeds(4, 3) {
+        m >>= 1;
+        k += 1;
+    }
+    let z = round_bits((&m - Rational::ONE) / (m + Rational::ONE), bits);
+    let ln_m = odd_series(z, bits, false) * Rational::from(2);
+    if k == 0 {
+        return ln_m;
+    }
+    ln2(bits + k.unsigned_abs().significant_bits()) * Rational::from(k) + ln_m
+}
+
+pub fn ln(x: &Rational, digits: usize) -> Result<Rational, EvalError> {
+    if *x <= 0u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    if *x == 1u32 {
+        return Ok(Rational::ZERO);
+    }
+    Ok(round_digits(ln_bits(x, working_bits(digits)), digits))
+}
+
+/// `sum (-1)^k x^(2k+start) / (2k+start)!`, `sin` for `start` 1, `cos` for 0.
+fn trig_series(x: &Rational, bits: u64, start: u64) -> Rational {
+    let x2 = round_bits(x * x, bits);
+    let mut term = if start == 1 { x.clone() } else { Rational::ONE };
+    let mut sum = term.clone();
+    let mut k = start;
+    loop {
+        term = round_bits(-term * &x2 / Rational::from((k + 1) * (k + 2)), bits);
+        k += 2;
+        if term == 0u32 || log2(&term) < -(bits as i64) - 8 {
+            return sum;
+        }
+        sum += &term;
+    }
+}
+
+/// `x` moved into `[-pi, pi]` by whole turns.
+fn reduce_turns(x: &Rational, bits: u64) -> Result<Rational, EvalError> {
+    if x.abs() <= 3u32 {
+        return Ok(x.clone());
+    }
+    let magnitude = log2(x).max(0) as u64;
+    if magnitude > MAX_BITS {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    let turn = pi_bits(bits + magnitude + 8) << 1;
+    let (n, _) = Integer::rounding_from(x / &turn, RoundingMode::Nearest);
+    Ok(round_bits(x - turn * Rational::from(n), bits + 8))
+}
+
+pub fn sin(x: &Rational, digits: usize) -> Result<Rational, EvalError> {
+    let bits = working_bits(digits);
+    let r = reduce_turns(x, bits)?;
+    Ok(round_digits(trig_series(&r, bits + 8, 1), digits))
+}
+
+pub fn cos(x: &Rational, digits: usize) -> Result<Rational, EvalError> {
+    let bits = working_bits(digits);
+    let r = reduce_turns(x, bits)?;
+    Ok(round_digits(trig_series(&r, bits + 8, 0), digits))
+}
+
+/// `atan(x)` to about `bits` bits.
+fn atan_bits(x: &Rational, bits: u64) -> Rational {
+    if x.abs() > 1u32 {
+        let quarter: Rational = pi_bits(bits) >> 1;
+        let rest = atan_bits(&(Rational::ONE / x), bits);
+        return if *x < 0u32 {
+            -quarter - rest
+        } else {
+            quarter - rest
+        };
+    }
+    // atan(x) = 2 atan(x / (1 + sqrt(1 + x^2))), three times brings |x|
+    // below tan(pi / 32) where the series converges quickly.
+    let mut z = x.clone();
+    for _ in 0..3 {
+        if z == 0u32 {
+            break;
+        }
+        let root = sqrt_bits(&(Rational::ONE + &z * &z), bits);
+        z = round_bits(z / (Rational::ONE + root), bits);
+    }
+    odd_series(z, bits, true) << 3
+}
+
+/// The angle of the point `(x, y)` in `(-pi, pi]`, `0` for the origin.
+pub fn atan2(y: &Rational, x: &Rational, digits: usize) -> Rational {
+    let bits = working_bits(digits) + 8;
+    let ret = if *x == 0u32 {
+        let quarter: Rational = pi_bits(bits) >> 1;
+        match y.cmp(&Rational::ZERO) {
+            Ordering::Equal => Rational::ZERO,
+            Ordering::Greater => quarter,
+            Ordering::Less => -quarter,
+        }
+    } else {
+        let angle = atan_bits(&(y / x), bits);
+        match (*x < 0u32, *y < 0u32) {
+            (false, _) => angle,
+            (true, false) => angle + pi_bits(bits),
+            (true, true) => angle - pi_bits(bits),
+        }
+    };
+    round_digits(ret, digits)
+}
+
+/// `x^n`, exact. Fails for `0^-n` and results too large to hold.
+pub fn pow_int(x: &Rational, n: &Integer) -> Result<Rational, EvalError> {
+    if *x == 0u32 && *n < 0u32 {
+        return Err(EvalError::DivisionByZero);
+    }
+    if *x == 0u32 {
+        return Ok(if *n == 0u32 {
+            Rational::ONE
+        } else {
+            Rational::ZERO
+        });
+    }
+    if x.abs() == 1u32 {
+        // Only the parity of a huge exponent matters here.
+        let n = i64::try_from(&(n % Integer::from(2))).map_err(|_| EvalError::PrimitiveEvalErr)?;
+        return Ok(x.clone().pow(n));
+    }
+    let n = i64::try_from(n).map_err(|_| EvalError::PrimitiveEvalErr)?;
+    let (num, den) = x.to_numerator_and_denominator();
+    let size = num.significant_bits().max(den.significant_bits());
+    if size.saturating_mul(n.unsigned_abs()) > MAX_BITS {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(x.clone().pow(n))
+}
+
+/// `x^y`, exact when `y` is an integer or the root it asks for is rational.
+/// Negative bases only take exponents with an odd denominator.
+pub fn pow(x: &Rational, y: &Rational, digits: usize) -> Result<Rational, EvalError> {
+    let (p, q) = y.to_numerator_and_denominator();
+    let p = if *y < 0u32 {
+        -Integer::from(p)
+    } else {
+        Integer::from(p)
+    };
+    if q == 1u32 {
+        return pow_int(x, &p);
+    }
+    if *x < 0u32 {
+        if q.clone() % Natural::from(2u32) == 0u32 {
+            return Err(EvalError::PrimitiveEvalErr);
+        }
+        return pow(&-x, y, digits).map(|r| {
+            if p.clone() % Integer::from(2) == 0 {
+                r
+            } else {
+                -r
+            }
+        });
+    }
+    if let Some(root) = u64::try_from(&q).ok().and_then(|q| x.checked_root(q)) {
+        return pow_int(&root, &p);
+    }
+    if *x == 0u32 {
+        return if *y < 0u32 {
+            Err(EvalError::DivisionByZero)
+        } else {
+            Ok(Rational::ZERO)
+        };
+    }
+    // exp needs y ln x to about as many places as it has integer digits.
+    let magnitude = y.abs() * Rational::from(log2(x).unsigned_abs() + 1);
+    let extra = (Integer::rounding_from(magnitude, RoundingMode::Ceiling).0)
+        .to_string()
+        .len();
+    let exponent = y * ln(x, digits + extra + 2)?;
+    exp(&exponent, digits)
+}
+
+fn quotient(a: &Rational, b: &Rational, rm: RoundingMode) -> Result<Integer, EvalError> {
+    if *b == 0u32 {
+        return Err(EvalError::DivisionByZero);
+    }
+    Ok(Integer::rounding_from(a / b, rm).0)
+}
+
+/// `floor(a / b)`.
+pub fn floor_div(a: &Rational, b: &Rational) -> Result<Integer, EvalError> {
+    quotient(a, b, RoundingMode::Floor)
+}
+
+/// `ceil(a / b)`.
+pub fn ceil_div(a: &Rational, b: &Rational) -> Result<Integer, EvalError> {
+    quotient(a, b, RoundingMode::Ceiling)
+}
+
+/// What is left of `a` after taking `floor(a / b)` times `b`, which has the
+/// sign of `b`.
+pub fn modulo(a: &Rational, b: &Rational) -> Result<Rational, EvalError> {
+    Ok(a - b * Rational::from(floor_div(a, b)?))
+}
+
+/// What is left of `a` after taking `a / b` rounded towards zero times `b`,
+/// which has the sign of `a`.
+pub fn rem(a: &Rational, b: &Rational) -> Result<Rational, EvalError> {
+    Ok(a - b * Rational::from(quotient(a, b, RoundingMode::Down)?))
+}
+
+/// `x` rounded to the nearest integer, halves away from zero.
+pub fn round_half_away(x: &Rational) -> Integer {
+    let half = Rational::from_signeds(1, 2);
+    if *x < 0u32 {
+        -Integer::rounding_from(-x + half, RoundingMode::Floor).0
+    } else {
+        Integer::rounding_from(x + half, RoundingMode::Floor).0
+    }
+}
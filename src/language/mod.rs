@@ -1,6 +1,20 @@
 pub mod ast;
+pub mod complex;
 pub mod env;
 pub mod eval;
+pub mod format;
+pub mod highlight;
+pub mod linalg;
+pub mod math;
+pub mod module;
+pub mod number;
+pub mod number_theory;
 pub mod parse;
+pub mod poly;
+pub mod random;
 pub mod repr;
+pub mod serialize;
+pub mod session;
+pub mod stats;
+pub mod units;
 pub mod vm;
@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Where `import` looks for modules that haven't been defined in the session yet.
+/// The browser only has the in-memory registry, headless runs also search
+/// `search_path` for `<name>.stk`.
+#[derive(Clone, Default)]
+pub struct ModuleLoader {
+    pub registry: HashMap<String, Arc<String>>,
+    pub search_path: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn source(&self, name: &str) -> Option<Arc<String>> {
+        if let Some(source) = self.registry.get(name) {
+            return Some(source.clone());
+        }
+        self.search_path
+            .iter()
+            .map(|dir| dir.join(format!("{name}.stk")))
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .map(Arc::new)
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use malachite::num::arithmetic::traits::{Abs, Pow};
+use malachite::{Integer, Natural, Rational};
+
+use super::ast::Type;
+use super::ast::rational_repr;
+
+/// Variants of the builtin `NumberFormat` enum taken by the `number_format`
+/// protocol, in the order they are interned.
+pub const FORMAT_VARIANTS: &[(&str, &[Type])] = &[
+    ("Fraction", &[]),
+    ("Fixed", &[Type::Integer]),
+    ("Scientific", &[Type::Integer]),
+    ("Repeating", &[]),
+    ("Mixed", &[]),
+];
+
+/// Variants of the builtin `IntFormat` enum taken by the `int_format`
+/// protocol, in the order they are interned.
+pub const INT_FORMAT_VARIANTS: &[(&str, &[Type])] = &[
+    ("Decimal", &[]),
+    ("Hex", &[]),
+    ("Octal", &[]),
+    ("Binary", &[]),
+];
+
+/// Builtin one-`Int` structs that show their field in one base whatever the
+/// `IntFormat` mode, with the word wrapping an `Int` in each.
+pub const INT_DISPLAY_TYPES: &[(&str, &str, IntFormat)] = &[
+    ("Hex", "hex", IntFormat::Hex),
+    ("Oct", "oct", IntFormat::Octal),
+    ("Bin", "bin", IntFormat::Binary),
+];
+
+/// Decimal places beyond which `Fixed` and `Scientific` are refused.
+pub const MAX_PLACES: usize = 1000;
+
+/// Fractional digits `Repeating` writes before giving up on finding the period.
+const MAX_REPEATING_DIGITS: usize = 100;
+
+/// How `Rat` values are shown on the stack. Only `Fraction` is guaranteed to
+/// read back as the same value; the others are for people.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `6/5`, `2.0`
+    #[default]
+    Fraction,
+    /// `1.20` with the given number of decimal places, rounded half up.
+    Fixed(usize),
+    /// `1.200e0` with the given number of places after the point.
+    Scientific(usize),
+    /// `0.1(6)`, the repeating part of the expansion in parentheses.
+    Repeating,
+    /// `1 1/5`
+    Mixed,
+}
+
+impl NumberFormat {
+    pub fn format(&self, r: &Rational) -> String {
+        let sign = if *r < 0u32 { "-" } else { "" };
+        let (n, d) = r.to_numerator_and_denominator();
+        match self {
+            NumberFormat::Fraction => rational_repr(r),
+            NumberFormat::Fixed(places) => {
+                let q = scaled_round(&n, &d, *places as i64);
+                let sign = if q == 0u32 { "" } else { sign };
+                let digits = format!("{q:0>width$}", width = places + 1);
+                let (int, frac) = digits.split_at(digits.len() - places);
+                match frac {
+                    "" => format!("{sign}{int}"),
+                    _ => format!("{sign}{int}.{frac}"),
+                }
+            }
+            NumberFormat::Scientific(places) => {
+                let mut exp = 0;
+                let mut q = Natural::from(0u32);
+                if n != 0u32 {
+                    exp = decimal_exponent(&n, &d);
+                    q = scaled_round(&n, &d, *places as i64 - exp);
+                    if q >= Natural::from(10u32).pow(*places as u64 + 1) {
+                        exp += 1;
+                        q = scaled_round(&n, &d, *places as i64 - exp);
+                    }
+                }
+                let q = format!("{q:0>width$}", width = places + 1);
+                let (first, rest) = q.split_at(1);
+                match rest {
+                    "" => format!("{sign}{first}e{exp}"),
+                    _ => format!("{sign}{first}.{rest}e{exp}"),
+                }
+            }
+            NumberFormat::Repeating => {
+                let int = &n / &d;
+                let mut rem = &n % &d;
+                let mut digits = String::new();
+                let mut seen = HashMap::new();
+                let mut period_start = None;
+                while rem != 0u32 {
+                    if digits.len() == MAX_REPEATING_DIGITS {
+                        digits.push('…');
+                        break;
+                    }
+                    if let Some(&pos) = seen.get(&rem) {
+                        period_start = Some(pos);
+                        break;
+                    }
+                    seen.insert(rem.clone(), digits.len());
+                    rem *= Natural::from(10u32);
+                    digits.push_str(&(&rem / &d).to_string());
+                    rem %= &d;
+                }
+                if let Some(pos) = period_start {
+                    digits.insert(pos, '(');
+                    digits.push(')');
+                }
+                if digits.is_empty() {
+                    digits.push('0');
+                }
+                format!("{sign}{int}.{digits}")
+            }
+            NumberFormat::Mixed => {
+                let int = &n / &d;
+                let rem = &n % &d;
+                if rem == 0u32 || int == 0u32 {
+                    rational_repr(r)
+                } else {
+                    format!("{sign}{int} {rem}/{d}")
+                }
+            }
+        }
+    }
+
+    /// `re+imi` with both parts in this format, e.g. `3.0-4.0i`.
+    pub fn format_complex(&self, re: &Rational, im: &Rational) -> String {
+        let sign = if *im < 0u32 { "-" } else { "+" };
+        format!("{}{sign}{}i", self.format(re), self.format(&im.abs()))
+    }
+
+    /// `Vec(1, 1/2)`. Entries of a vector are always rationals, so integral
+    /// ones need no `.0` to read back.
+    pub fn format_vector(&self, v: &[Rational]) -> String {
+        let entries: Vec<_> = v
+            .iter()
+            .map(|x| match self {
+                NumberFormat::Fraction => x.to_string(),
+                _ => self.format(x),
+            })
+            .collect();
+        format!("Vec({})", entries.join(", "))
+    }
+
+    /// `Poly(3x^2 - 1/2x + 1)`, see `poly::notation`.
+    pub fn format_poly(&self, p: &[Rational]) -> String {
+        let notation = super::poly::notation(p, |x| match self {
+            NumberFormat::Fraction => x.to_string(),
+            _ => self.format(x),
+        });
+        format!("Poly({notation})")
+    }
+
+    /// `7/2 km`, the magnitude in the units the quantity is shown in.
+    pub fn format_quantity(&self, q: &super::units::Quantity) -> String {
+        q.format(|x| match self {
+            NumberFormat::Fraction => x.to_string(),
+            _ => self.format(x),
+        })
+    }
+}
+
+/// `n / d * 10^shift` rounded half up.
+fn scaled_round(n: &Natural, d: &Natural, shift: i64) -> Natural {
+    let ten = Natural::from(10u32);
+    let (n, d) = if shift >= 0 {
+        (n * (&ten).pow(shift as u64), d.clone())
+    } else {
+        (n.clone(), d * (&ten).pow(shift.unsigned_abs()))
+    };
+    (n * Natural::from(2u32) + &d) / (d * Natural::from(2u32))
+}
+
+/// The `e` with `10^e <= n / d < 10^(e + 1)`, for `n` not zero.
+pub fn decimal_exponent(n: &Natural, d: &Natural) -> i64 {
+    let exp = n.to_string().len() as i64 - d.to_string().len() as i64;
+    let ten = Natural::from(10u32);
+    let at_least = if exp >= 0 {
+        *n >= d * (&ten).pow(exp as u64)
+    } else {
+        n * (&ten).pow(exp.unsigned_abs()) >= *d
+    };
+    if at_least { exp } else { exp - 1 }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumberFormat::Fraction => write!(f, "fraction"),
+            NumberFormat::Fixed(places) => write!(f, "fixed {places}"),
+            NumberFormat::Scientific(places) => write!(f, "scientific {places}"),
+            NumberFormat::Repeating => write!(f, "repeating"),
+            NumberFormat::Mixed => write!(f, "mixed"),
+        }
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let places = |word: Option<&str>| {
+            word.and_then(|x| x.parse().ok())
+                .filter(|x| *x <= MAX_PLACES)
+                .ok_or_else(|| format!("expected a number of places in {s:?}"))
+        };
+        let ret = match words.next() {
+            Some("fraction") => NumberFormat::Fraction,
+            Some("fixed") => NumberFormat::Fixed(places(words.next())?),
+            Some("scientific") => NumberFormat::Scientific(places(words.next())?),
+            Some("repeating") => NumberFormat::Repeating,
+            Some("mixed") => NumberFormat::Mixed,
+            _ => return Err(format!("unknown number format {s:?}")),
+        };
+        match words.next() {
+            Some(_) => Err(format!("unknown number format {s:?}")),
+            None => Ok(ret),
+        }
+    }
+}
+
+/// How `Int` values are shown on the stack. Every mode writes a literal that
+/// reads back as the same value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntFormat {
+    /// `-255`
+    #[default]
+    Decimal,
+    /// `-0xff`
+    Hex,
+    /// `-0o377`
+    Octal,
+    /// `-0b11111111`
+    Binary,
+}
+
+impl IntFormat {
+    pub fn format(&self, i: &Integer) -> String {
+        match self {
+            IntFormat::Decimal => format!("{i}"),
+            IntFormat::Hex => format!("{i:#x}"),
+            IntFormat::Octal => format!("{i:#o}"),
+            IntFormat::Binary => format!("{i:#b}"),
+        }
+    }
+}
+
+impl fmt::Display for IntFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntFormat::Decimal => write!(f, "decimal"),
+            IntFormat::Hex => write!(f, "hex"),
+            IntFormat::Octal => write!(f, "octal"),
+            IntFormat::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+impl FromStr for IntFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "decimal" => Ok(IntFormat::Decimal),
+            "hex" => Ok(IntFormat::Hex),
+            "octal" => Ok(IntFormat::Octal),
+            "binary" => Ok(IntFormat::Binary),
+            _ => Err(format!("unknown int format {s:?}")),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use malachite::num::arithmetic::traits::{
+    BinomialCoefficient, Factorial, FloorLogBase, FloorSqrt, Gcd, Mod, ModInverse, ModPow,
+    UnsignedAbs,
+};
+use malachite::num::basic::traits::One;
+use malachite::num::logic::traits::{CountOnes, SignificantBits};
+use malachite::{Integer, Natural};
+
+use super::eval::EvalError;
+
+/// The first 40 primes, used for trial division and as Miller-Rabin bases.
+const PRIMES: [u32; 40] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173,
+];
+
+/// Below this, Miller-Rabin with the first 13 primes as bases never lets a
+/// composite through.
+const DETERMINISTIC_BELOW: &str = "3317044064679887385961981";
+
+/// Results are refused beyond about this many bits instead of exhausting
+/// memory, as for `math::pow`.
+const MAX_BITS: u64 = 1 << 20;
+
+/// Steps of Pollard's rho per polynomial and the number of polynomials tried
+/// before `factor` gives up, which it does on composites without a prime
+/// factor below about 10^11.
+const RHO_STEPS: u64 = 1 << 19;
+const RHO_TRIES: u64 = 8;
+const RHO_BATCH: u64 = 64;
+
+fn strong_probable_prime(n: &Natural, d: &Natural, s: u64, base: u32) -> bool {
+    let n_minus_1 = n - Natural::ONE;
+    let mut x = Natural::from(base).mod_pow(d, n);
+    if x == 1u32 || x == n_minus_1 {
+        return true;
+    }
+    for _ in 1..s {
+        x = &x * &x % n;
+        if x == n_minus_1 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Exact below `DETERMINISTIC_BELOW`, above it a composite is reported prime
+/// with a probability below `4^-40`.
+pub fn is_prime(n: &Natural) -> bool {
+    if *n < 2u32 {
+        return false;
+    }
+    for p in PRIMES {
+        if *n == p {
+            return true;
+        }
+        if n % Natural::from(p) == 0u32 {
+            return false;
+        }
+    }
+    let n_minus_1 = n - Natural::ONE;
+    let s = n_minus_1.trailing_zeros().unwrap();
+    let d = &n_minus_1 >> s;
+    let bases = if *n < Natural::from_str(DETERMINISTIC_BELOW).unwrap() {
+        &PRIMES[..13]
+    } else {
+        &PRIMES[..]
+    };
+    bases
+        .iter()
+        .all(|base| strong_probable_prime(n, &d, s, *base))
+}
+
+fn distance(x: &Natural, y: &Natural) -> Natural {
+    if x > y { x - y } else { y - x }
+}
+
+/// A proper divisor of the composite `n`, found with `x^2 + c`.
+fn pollard_rho(n: &Natural, c: u64) -> Option<Natural> {
+    let c = Natural::from(c);
+    let f = |x: &Natural| (x * x + &c) % n;
+    let mut x = Natural::from(2u32);
+    let mut y = x.clone();
+    let mut steps = 0;
+    while steps < RHO_STEPS {
+        let (saved_x, saved_y) = (x.clone(), y.clone());
+        let mut product = Natural::ONE;
+        for _ in 0..RHO_BATCH {
+            x = f(&x);
+            y = f(&f(&y));
+            product = product * distance(&x, &y) % n;
+        }
+        steps += RHO_BATCH;
+        let d = (&product).gcd(n);
+        if d == 1u32 {
+            continue;
+        }
+        if d != *n {
+            return Some(d);
+        }
+        // A factor showed up somewhere in the batch, retrace it one step at a time.
+        (x, y) = (saved_x, saved_y);
+        for _ in 0..RHO_BATCH {
+            x = f(&x);
+            y = f(&f(&y));
+            let d = distance(&x, &y).gcd(n);
+            if d != 1u32 {
+                return (d != *n).then_some(d);
+            }
+        }
+        return None;
+    }
+    None
+}
+
+/// Prime factors of `n > 0` in ascending order, repeated by multiplicity.
+pub fn factor(n: &Natural) -> Result<Vec<Natural>, EvalError> {
+    let mut ret = vec![];
+    let mut n = n.clone();
+    for p in PRIMES {
+        let p = Natural::from(p);
+        while &n % &p == 0u32 {
+            n /= &p;
+            ret.push(p.clone());
+        }
+    }
+    let mut rest = vec![n];
+    while let Some(n) = rest.pop() {
+        if n == 1u32 {
+            continue;
+        }
+        if is_prime(&n) {
+            ret.push(n);
+            continue;
+        }
+        let d = (1..=RHO_TRIES)
+            .find_map(|c| pollard_rho(&n, c))
+            .ok_or(EvalError::PrimitiveEvalErr)?;
+        rest.push(&n / &d);
+        rest.push(d);
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+/// `a^e mod m` in `[0, m)`; a negative `e` raises the inverse of `a`.
+pub fn mod_pow(a: &Integer, e: &Integer, m: &Integer) -> Result<Integer, EvalError> {
+    let base = if *e < 0u32 {
+        mod_inverse(a, m)?.unsigned_abs()
+    } else {
+        reduce(a, m)?
+    };
+    let m = m.unsigned_abs();
+    Ok(Integer::from(base.mod_pow(e.unsigned_abs(), &m)))
+}
+
+/// The `x` in `[0, m)` with `a x = 1 mod m`, if `a` and `m` are coprime.
+pub fn mod_inverse(a: &Integer, m: &Integer) -> Result<Integer, EvalError> {
+    let a = reduce(a, m)?;
+    if *m == 1u32 {
+        return Ok(Integer::from(0));
+    }
+    a.mod_inverse(m.unsigned_abs())
+        .map(Integer::from)
+        .ok_or(EvalError::PrimitiveEvalErr)
+}
+
+/// `a mod m` for a positive modulus.
+fn reduce(a: &Integer, m: &Integer) -> Result<Natural, EvalError> {
+    if *m == 0u32 {
+        return Err(EvalError::DivisionByZero);
+    }
+    if *m < 0u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(a.mod_op(m).unsigned_abs())
+}
+
+pub fn factorial(n: &Integer) -> Result<Integer, EvalError> {
+    let n = u64::try_from(n).map_err(|_| EvalError::PrimitiveEvalErr)?;
+    if n.saturating_mul(n.significant_bits()) > MAX_BITS {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(Integer::from(Natural::factorial(n)))
+}
+
+/// `n choose k`, zero for `k < 0`. Negative `n` follows the usual extension
+/// `(-1)^k (k - n - 1 choose k)`.
+pub fn binomial(n: &Integer, k: &Integer) -> Result<Integer, EvalError> {
+    if *k < 0u32 {
+        return Ok(Integer::from(0));
+    }
+    // The result has at most min(k, n - k) * bits(n) bits.
+    let smaller = if *n > 0u32 {
+        k.min(&(n - k)).clone()
+    } else {
+        k.clone()
+    };
+    let size = u64::try_from(&smaller.max(Integer::from(0)))
+        .map_err(|_| EvalError::PrimitiveEvalErr)?
+        .saturating_mul((n.unsigned_abs() + k.unsigned_abs()).significant_bits());
+    if size > MAX_BITS {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(Integer::binomial_coefficient(n.clone(), k.clone()))
+}
+
+pub fn isqrt(n: &Integer) -> Result<Integer, EvalError> {
+    if *n < 0u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(Integer::from(n.unsigned_abs().floor_sqrt()))
+}
+
+/// `floor(log_base(n))` for `n >= 1` and `base >= 2`.
+pub fn log(n: &Integer, base: &Integer) -> Result<Integer, EvalError> {
+    if *n < 1u32 || *base < 2u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(Integer::from(
+        n.unsigned_abs().floor_log_base(&base.unsigned_abs()),
+    ))
+}
+
+/// `a * 2^n`, rounded towards negative infinity for `n < 0` like an
+/// arithmetic right shift.
+pub fn shift(a: &Integer, n: &Integer) -> Result<Integer, EvalError> {
+    if *n < 0u32 {
+        // Shifting out every bit leaves 0 or -1 however far it goes.
+        let n = u64::try_from(&-n).unwrap_or(u64::MAX);
+        return Ok(a >> n);
+    }
+    let n = u64::try_from(n).map_err(|_| EvalError::PrimitiveEvalErr)?;
+    if *a != 0u32 && a.significant_bits().saturating_add(n) > MAX_BITS {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(a << n)
+}
+
+/// Set bits of `n >= 0`.
+pub fn popcount(n: &Integer) -> Result<Integer, EvalError> {
+    if *n < 0u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    Ok(Integer::from(n.unsigned_abs().count_ones()))
+}
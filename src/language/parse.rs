@@ -1,4 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::language::eval::ERROR_VARIANTS;
+use crate::language::number::{FORMAT_VARIANTS, INT_DISPLAY_TYPES, INT_FORMAT_VARIANTS};
+use crate::language::units::BUILTIN;
+
+/// What names stand for beyond their ids: modules, imported names and units.
+/// Undo snapshots share it and roll it back, while the interned names only
+/// ever grow and stay valid.
+#[derive(Clone, Debug, Default)]
+pub struct Scopes {
+    modules: HashMap<String, HashMap<String, usize>>,
+    aliases: HashMap<String, usize>,
+    /// Types of each module, resolved like `modules` but for type names.
+    module_types: HashMap<String, HashMap<String, usize>>,
+    type_aliases: HashMap<String, usize>,
+    /// Names in `fun_names` that are units rather than calls.
+    units: HashSet<usize>,
+}
 
 #[derive(Clone, Debug)]
 pub struct ParseCtx {
@@ -13,6 +32,9 @@ pub struct ParseCtx {
     tag_idx: HashMap<String, usize>,
     field_idx: HashMap<String, usize>,
     var_scopes: Vec<HashMap<String, usize>>,
+    module_scope: Option<HashMap<String, usize>>,
+    module_type_scope: Option<HashMap<String, usize>>,
+    scopes: Rc<Scopes>,
 }
 
 impl Default for ParseCtx {
@@ -29,11 +51,92 @@ impl Default for ParseCtx {
             tag_idx: Default::default(),
             field_idx: Default::default(),
             var_scopes: vec![HashMap::new()],
+            module_scope: None,
+            module_type_scope: None,
+            scopes: Default::default(),
         };
         ret.insert_fun("add");
         ret.insert_fun("sub");
         ret.insert_fun("mul");
         ret.insert_fun("div");
+        ret.insert_fun("mod");
+        ret.insert_fun("rem");
+        ret.insert_fun("fdiv");
+        ret.insert_fun("cdiv");
+        ret.insert_fun("divmod");
+        ret.insert_fun("abs");
+        ret.insert_fun("neg");
+        ret.insert_fun("min");
+        ret.insert_fun("max");
+        ret.insert_fun("gcd");
+        ret.insert_fun("lcm");
+        ret.insert_fun("modpow");
+        ret.insert_fun("modinv");
+        ret.insert_fun("is_prime");
+        ret.insert_fun("factor");
+        ret.insert_fun("factorial");
+        ret.insert_fun("binomial");
+        ret.insert_fun("isqrt");
+        ret.insert_fun("log");
+        ret.insert_fun("numerator");
+        ret.insert_fun("denominator");
+        ret.insert_fun("floor");
+        ret.insert_fun("ceil");
+        ret.insert_fun("round");
+        ret.insert_fun("bit_and");
+        ret.insert_fun("bit_or");
+        ret.insert_fun("bit_xor");
+        ret.insert_fun("bit_not");
+        ret.insert_fun("shl");
+        ret.insert_fun("shr");
+        ret.insert_fun("popcount");
+        ret.insert_fun("bit_length");
+        ret.insert_fun("sqrt");
+        ret.insert_fun("pow");
+        ret.insert_fun("exp");
+        ret.insert_fun("ln");
+        ret.insert_fun("sin");
+        ret.insert_fun("cos");
+        ret.insert_fun("pi");
+        ret.insert_fun("precision");
+        ret.insert_fun("re");
+        ret.insert_fun("im");
+        ret.insert_fun("conj");
+        ret.insert_fun("abs2");
+        ret.insert_fun("to_polar");
+        ret.insert_fun("from_polar");
+        ret.insert_fun("dot");
+        ret.insert_fun("matmul");
+        ret.insert_fun("transpose");
+        ret.insert_fun("det");
+        ret.insert_fun("inverse");
+        ret.insert_fun("solve");
+        ret.insert_fun("rank");
+        ret.insert_fun("identity");
+        ret.insert_fun("zero_mat");
+        ret.insert_fun("zero_vec");
+        ret.insert_fun("eval");
+        ret.insert_fun("derive");
+        ret.insert_fun("integrate");
+        ret.insert_fun("roots");
+        ret.insert_fun("sum");
+        ret.insert_fun("product");
+        ret.insert_fun("mean");
+        ret.insert_fun("variance");
+        ret.insert_fun("median");
+        ret.insert_fun("stddev");
+        ret.insert_fun("mode");
+        ret.insert_fun("quantile");
+        ret.insert_fun("histogram");
+        ret.insert_fun("cumsum");
+        ret.insert_fun("sort");
+        ret.insert_fun("sort_desc");
+        ret.insert_fun("seed");
+        ret.insert_fun("rand_int");
+        ret.insert_fun("rand_rat");
+        ret.insert_fun("shuffle");
+        ret.insert_fun("choice");
+        ret.insert_fun("sample");
 
         ret.insert_fun("eq");
         ret.insert_fun("neq");
@@ -75,6 +178,34 @@ impl Default for ParseCtx {
         ret.insert_fun("f2i");
         ret.insert_fun("l2s");
         ret.insert_fun("s2l");
+        ret.insert_fun("l2v");
+        ret.insert_fun("v2l");
+
+        ret.insert_type("Error");
+        for (variant, _) in ERROR_VARIANTS {
+            ret.insert_tag(*variant);
+        }
+
+        ret.insert_type("NumberFormat");
+        for (variant, _) in FORMAT_VARIANTS {
+            ret.insert_tag(*variant);
+        }
+        ret.insert_fun("number_format");
+
+        ret.insert_type("IntFormat");
+        for (variant, _) in INT_FORMAT_VARIANTS {
+            ret.insert_tag(*variant);
+        }
+        ret.insert_fun("int_format");
+
+        for (typ, word, _) in INT_DISPLAY_TYPES {
+            ret.insert_type(*typ);
+            ret.insert_fun(*word);
+        }
+
+        for (name, ..) in BUILTIN {
+            ret.insert_unit(name);
+        }
 
         ret
     }
@@ -101,7 +232,6 @@ impl ParseCtx {
         None
     }
     pub fn insert_var(&mut self, var: impl Into<String> + Clone) -> usize {
-
         match self.var_idx.entry(var.clone().into()) {
             std::collections::hash_map::Entry::Occupied(occ) => {
                 let id = *occ.get();
@@ -134,6 +264,151 @@ impl ParseCtx {
         }
     }
 
+    /// Registers `name` as a unit, so the identifier multiplies by it instead
+    /// of calling it.
+    pub fn insert_unit(&mut self, name: &str) -> usize {
+        let id = self.insert_fun(name);
+        if !self.scopes.units.contains(&id) {
+            Rc::make_mut(&mut self.scopes).units.insert(id);
+        }
+        id
+    }
+
+    /// Id of the function `name` if it was ever mentioned, without adding it.
+    pub fn fun_id(&self, name: &str) -> Option<usize> {
+        self.fun_idx.get(name).copied()
+    }
+
+    pub fn lookup_unit(&self, name: &str) -> Option<usize> {
+        self.fun_idx
+            .get(name)
+            .copied()
+            .filter(|x| self.scopes.units.contains(x))
+    }
+
+    /// Resolves a function name as written in the source. Names defined in the
+    /// module being parsed win over imported names, which win over globals.
+    pub fn lookup_fun(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.module_scope.as_ref().and_then(|x| x.get(name)) {
+            return id;
+        }
+        if let Some(&id) = self.scopes.aliases.get(name) {
+            return id;
+        }
+        self.insert_fun(name)
+    }
+
+    /// Id under which a definition called `name` is stored. Inside a module
+    /// this is the qualified `module::name`.
+    pub fn define_fun(&mut self, name: &str) -> usize {
+        match self.module_scope.as_ref().and_then(|x| x.get(name)) {
+            Some(&id) => id,
+            None => {
+                if self.scopes.aliases.contains_key(name) {
+                    Rc::make_mut(&mut self.scopes).aliases.remove(name);
+                }
+                self.insert_fun(name)
+            }
+        }
+    }
+
+    pub fn enter_module(&mut self, module: &str, members: &[&str], types: &[&str]) {
+        let scope: HashMap<String, usize> = members
+            .iter()
+            .map(|x| (x.to_string(), self.insert_fun(format!("{module}::{x}"))))
+            .collect();
+        Rc::make_mut(&mut self.scopes)
+            .modules
+            .entry(module.to_string())
+            .or_default()
+            .extend(scope.clone());
+        self.module_scope = Some(scope);
+        let scope: HashMap<String, usize> = types
+            .iter()
+            .map(|x| (x.to_string(), self.insert_type(format!("{module}::{x}"))))
+            .collect();
+        Rc::make_mut(&mut self.scopes)
+            .module_types
+            .entry(module.to_string())
+            .or_default()
+            .extend(scope.clone());
+        self.module_type_scope = Some(scope);
+    }
+
+    pub fn exit_module(&mut self) {
+        self.module_scope = None;
+        self.module_type_scope = None;
+    }
+
+    pub fn has_module(&self, module: &str) -> bool {
+        self.scopes.modules.contains_key(module)
+    }
+
+    pub fn get_modules(&self) -> Vec<(String, Vec<String>)> {
+        self.scopes
+            .modules
+            .iter()
+            .map(|(name, members)| (name.to_string(), members.keys().cloned().collect()))
+            .collect()
+    }
+
+    /// Makes every member and type of `module` usable without qualification.
+    pub fn import_module(&mut self, module: &str) -> bool {
+        if !self.has_module(module) {
+            return false;
+        }
+        let scopes = Rc::make_mut(&mut self.scopes);
+        let members = &scopes.modules[module];
+        scopes
+            .aliases
+            .extend(members.iter().map(|(x, y)| (x.clone(), *y)));
+        if let Some(types) = scopes.module_types.get(module) {
+            scopes
+                .type_aliases
+                .extend(types.iter().map(|(x, y)| (x.clone(), *y)));
+        }
+        true
+    }
+
+    /// Makes `module::fun` callable as `fun`.
+    pub fn use_fun(&mut self, module: &str, fun: &str) -> bool {
+        match self.scopes.modules.get(module).and_then(|x| x.get(fun)) {
+            Some(&id) => {
+                Rc::make_mut(&mut self.scopes)
+                    .aliases
+                    .insert(fun.to_string(), id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resolves a type name as written in the source, the way
+    /// [`ParseCtx::lookup_fun`] resolves function names.
+    pub fn lookup_type(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.module_type_scope.as_ref().and_then(|x| x.get(name)) {
+            return id;
+        }
+        if let Some(&id) = self.scopes.type_aliases.get(name) {
+            return id;
+        }
+        self.insert_type(name)
+    }
+
+    /// Id under which a struct, enum or interface called `name` is stored.
+    /// Inside a module this is the qualified `module::Name`.
+    pub fn define_type(&mut self, name: &str) -> usize {
+        match self.module_type_scope.as_ref().and_then(|x| x.get(name)) {
+            Some(&id) => id,
+            None => {
+                if self.scopes.type_aliases.contains_key(name) {
+                    Rc::make_mut(&mut self.scopes).type_aliases.remove(name);
+                }
+                self.insert_type(name)
+            }
+        }
+    }
+
     pub fn insert_type(&mut self, fun: impl Into<String> + Clone) -> usize {
         match self.type_idx.entry(fun.clone().into()) {
             std::collections::hash_map::Entry::Occupied(occ) => *occ.get(),
@@ -169,6 +444,24 @@ impl ParseCtx {
         }
     }
 
+    pub fn scopes(&self) -> &Rc<Scopes> {
+        &self.scopes
+    }
+
+    /// Puts back scopes taken with [`ParseCtx::scopes`], e.g. on undo.
+    pub fn set_scopes(&mut self, scopes: Rc<Scopes>) {
+        self.scopes = scopes;
+    }
+
+    /// Qualified names of the module members that are callable unqualified.
+    pub fn get_aliases(&self) -> Vec<String> {
+        self.scopes
+            .aliases
+            .values()
+            .map(|x| self.lookup_call_name(*x))
+            .collect()
+    }
+
     pub fn lookup_call_name(&self, i: usize) -> String {
         self.fun_names[i].to_string()
     }
@@ -184,6 +477,10 @@ impl ParseCtx {
         self.type_names[i].to_string()
     }
 
+    pub fn lookup_type_name_maybe(&self, i: usize) -> Option<String> {
+        self.type_names.get(i).map(|x| x.to_string())
+    }
+
     pub fn lookup_var_name(&self, i: usize) -> String {
         self.var_names[i].to_string()
     }
@@ -193,12 +490,128 @@ impl ParseCtx {
     }
 }
 
+use pest::error::{Error, ErrorVariant, InputLocation};
+use pest::{Position, Span};
 use pest_derive::Parser;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 pub struct StackParser;
 
+fn rule_description(rule: &Rule) -> String {
+    match rule {
+        Rule::integer | Rule::float | Rule::fraction | Rule::complex => "a number",
+        Rule::bools => "true or false",
+        Rule::identifier | Rule::qualifiedName | Rule::funName => "a name",
+        Rule::typName | Rule::variantName | Rule::variant | Rule::typ | Rule::primTyps => "a type",
+        Rule::varName | Rule::manyvar => "a variable",
+        Rule::EOI => "the end of the input",
+        Rule::expr
+        | Rule::stack
+        | Rule::list
+        | Rule::set
+        | Rule::map
+        | Rule::vector
+        | Rule::matrix
+        | Rule::poly
+        | Rule::variantInst
+        | Rule::typeInst
+        | Rule::take
+        | Rule::takeVars
+        | Rule::whileLoop
+        | Rule::whileCond
+        | Rule::tryKw
+        | Rule::matchBlock
+        | Rule::ifTrue
+        | Rule::tryCatch
+        | Rule::throw
+        | Rule::ret
+        | Rule::brek
+        | Rule::convert
+        | Rule::block => "an expression",
+        Rule::unitName | Rule::unitFactor | Rule::unitPower | Rule::upperUnit => "a unit",
+        Rule::def | Rule::protocol_def | Rule::unitDef => "a definition",
+        Rule::matchElem | Rule::patterns | Rule::dontCare | Rule::many => "a pattern",
+        Rule::inner_types => "types",
+        rule => {
+            let name = format!("{rule:?}");
+            if name.to_lowercase().contains("pattern")
+                || [
+                    "listStart",
+                    "listEnd",
+                    "setFront",
+                    "enumStart",
+                    "structStart",
+                ]
+                .iter()
+                .any(|x| name.starts_with(x))
+            {
+                "a pattern"
+            } else {
+                return name;
+            }
+        }
+    }
+    .to_string()
+}
+
+/// Rewrites pest's "expected integer, float, fraction, ..." into plain
+/// words, merging rules that mean the same thing to the reader. `source` is
+/// the input `err` was produced from.
+pub fn clarify_error(err: Error<Rule>, source: &str) -> Error<Rule> {
+    let ErrorVariant::ParsingError {
+        positives,
+        negatives,
+    } = &err.variant
+    else {
+        return err;
+    };
+    let describe = |rules: &[Rule]| {
+        let mut ret: Vec<String> = vec![];
+        for rule in rules.iter().map(rule_description) {
+            if !ret.contains(&rule) {
+                ret.push(rule);
+            }
+        }
+        // Literals, names and types are covered by the broader wording.
+        let has = |x: &str| ret.iter().any(|y| y == x);
+        if has("an expression") || has("a pattern") {
+            let narrow = [
+                "a number",
+                "true or false",
+                "a name",
+                "a type",
+                "a variable",
+            ];
+            ret.retain(|x| !narrow.contains(&x.as_str()));
+        }
+        match ret.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, init)) => format!("{} or {last}", init.join(", ")),
+            None => String::new(),
+        }
+    };
+    let message = match (positives.is_empty(), negatives.is_empty()) {
+        (false, true) => format!("expected {}", describe(positives)),
+        (true, false) => format!("unexpected {}", describe(negatives)),
+        (false, false) => format!(
+            "unexpected {}; expected {}",
+            describe(negatives),
+            describe(positives)
+        ),
+        (true, true) => "unknown parsing error".to_string(),
+    };
+    let variant = ErrorVariant::CustomError { message };
+    match err.location {
+        InputLocation::Pos(pos) => Position::new(source, pos)
+            .map(|pos| Error::new_from_pos(variant, pos))
+            .unwrap_or(err),
+        InputLocation::Span((start, end)) => Span::new(source, start, end)
+            .map(|span| Error::new_from_span(variant, span))
+            .unwrap_or(err),
+    }
+}
+
 pub trait Parse {
     fn parse(pairs: pest::iterators::Pair<'_, Rule>, ctx: &mut ParseCtx) -> Self;
 }
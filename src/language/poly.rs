@@ -0,0 +1,254 @@
+use malachite::num::arithmetic::traits::Lcm;
+use malachite::num::basic::traits::{One, Zero};
+use malachite::{Natural, Rational};
+
+use super::ast::Type;
+use super::eval::{EvalError, Values};
+use super::number_theory;
+
+/// Operand types of the arithmetic protocols that take a `Poly`: a `Poly`
+/// with a `Poly`, `Int` or `Rat`.
+pub const OPERANDS: [[Type; 2]; 5] = [
+    [Type::Poly, Type::Poly],
+    [Type::Poly, Type::Integer],
+    [Type::Integer, Type::Poly],
+    [Type::Poly, Type::Float],
+    [Type::Float, Type::Poly],
+];
+
+/// Degrees beyond which results are refused instead of exhausting memory.
+const MAX_DEGREE: usize = 1 << 16;
+
+/// Divisor pairs `roots` tries before giving up.
+const MAX_CANDIDATES: usize = 1 << 16;
+
+/// Drops zero leading coefficients, so the zero polynomial has none.
+pub fn normalize(mut p: Vec<Rational>) -> Vec<Rational> {
+    while p.last().is_some_and(|x| *x == 0u32) {
+        p.pop();
+    }
+    p
+}
+
+/// Coefficients of a `Poly`, `Int` or `Rat`, lowest power first.
+pub fn coefficients(value: Values) -> Vec<Rational> {
+    match value {
+        Values::Poly(p) => p,
+        Values::Int(i) => normalize(vec![Rational::from(i)]),
+        Values::Float(f) => normalize(vec![f]),
+        _ => unreachable!(),
+    }
+}
+
+/// Entries of a `Poly` literal, which must be numbers.
+pub fn entry(value: Values) -> Result<Rational, EvalError> {
+    match value {
+        Values::Int(i) => Ok(Rational::from(i)),
+        Values::Float(f) => Ok(f),
+        other => Err(EvalError::PolyExpectsNumberButGot(other)),
+    }
+}
+
+pub fn add(a: &[Rational], b: &[Rational]) -> Vec<Rational> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut ret = long.to_vec();
+    for (x, y) in ret.iter_mut().zip(short) {
+        *x += y;
+    }
+    normalize(ret)
+}
+
+pub fn neg(a: &[Rational]) -> Vec<Rational> {
+    a.iter().map(|x| -x).collect()
+}
+
+pub fn sub(a: &[Rational], b: &[Rational]) -> Vec<Rational> {
+    add(a, &neg(b))
+}
+
+pub fn mul(a: &[Rational], b: &[Rational]) -> Result<Vec<Rational>, EvalError> {
+    if a.is_empty() || b.is_empty() {
+        return Ok(vec![]);
+    }
+    if a.len() + b.len() - 1 > MAX_DEGREE {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    let mut ret = vec![Rational::ZERO; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            ret[i + j] += x * y;
+        }
+    }
+    Ok(normalize(ret))
+}
+
+/// Quotient and remainder of `a / b`, the remainder of lower degree than `b`.
+pub fn div_rem(
+    a: &[Rational],
+    b: &[Rational],
+) -> Result<(Vec<Rational>, Vec<Rational>), EvalError> {
+    let Some(lead) = b.last() else {
+        return Err(EvalError::DivisionByZero);
+    };
+    if a.len() < b.len() {
+        return Ok((vec![], a.to_vec()));
+    }
+    let mut rem = a.to_vec();
+    let mut quot = vec![Rational::ZERO; a.len() - b.len() + 1];
+    for k in (0..quot.len()).rev() {
+        let c = &rem[k + b.len() - 1] / lead;
+        for (j, y) in b.iter().enumerate() {
+            rem[k + j] -= &c * y;
+        }
+        quot[k] = c;
+    }
+    rem.truncate(b.len() - 1);
+    Ok((normalize(quot), normalize(rem)))
+}
+
+/// `p(x)` by Horner's rule.
+pub fn eval(p: &[Rational], x: &Rational) -> Rational {
+    p.iter().rev().fold(Rational::ZERO, |acc, c| acc * x + c)
+}
+
+pub fn derive(p: &[Rational]) -> Vec<Rational> {
+    p.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, c)| c * Rational::from(k))
+        .collect()
+}
+
+/// The antiderivative with constant term zero.
+pub fn integrate(p: &[Rational]) -> Vec<Rational> {
+    if p.is_empty() {
+        return vec![];
+    }
+    std::iter::once(Rational::ZERO)
+        .chain(p.iter().enumerate().map(|(k, c)| c / Rational::from(k + 1)))
+        .collect()
+}
+
+/// The monic greatest common divisor, zero only if both are zero.
+pub fn gcd(a: &[Rational], b: &[Rational]) -> Result<Vec<Rational>, EvalError> {
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    while !b.is_empty() {
+        let (_, rem) = div_rem(&a, &b)?;
+        (a, b) = (b, rem);
+    }
+    Ok(match a.last().cloned() {
+        Some(lead) => a.into_iter().map(|x| x / &lead).collect(),
+        None => a,
+    })
+}
+
+/// Positive divisors of `n > 0`.
+fn divisors(n: &Natural) -> Result<Vec<Natural>, EvalError> {
+    let mut ret = vec![Natural::ONE];
+    let factors = number_theory::factor(n)?;
+    let mut i = 0;
+    while i < factors.len() {
+        let p = &factors[i];
+        let count = factors[i..].iter().take_while(|x| *x == p).count();
+        let mut next = vec![];
+        for d in ret.iter() {
+            let mut d = d.clone();
+            next.push(d.clone());
+            for _ in 0..count {
+                d *= p;
+                next.push(d.clone());
+            }
+        }
+        if next.len() > MAX_CANDIDATES {
+            return Err(EvalError::PrimitiveEvalErr);
+        }
+        ret = next;
+        i += count;
+    }
+    Ok(ret)
+}
+
+/// The rational roots of `p`, ascending and repeated by multiplicity. Fails
+/// for the zero polynomial, which every number is a root of.
+pub fn roots(p: &[Rational]) -> Result<Vec<Rational>, EvalError> {
+    if p.is_empty() {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    let mut ret = vec![];
+    // Factors of x first, so the constant term is not zero.
+    let zeros = p.iter().take_while(|x| **x == 0u32).count();
+    ret.extend(std::iter::repeat_n(Rational::ZERO, zeros));
+    let mut p = p[zeros..].to_vec();
+    // A root p/q in lowest terms of a polynomial with integer coefficients
+    // has p dividing the constant and q the leading coefficient.
+    let scale = p.iter().fold(Natural::ONE, |acc, c| {
+        acc.lcm(c.to_numerator_and_denominator().1)
+    });
+    let integral = |c: &Rational| {
+        (c * Rational::from(scale.clone()))
+            .to_numerator_and_denominator()
+            .0
+    };
+    let numerators = divisors(&integral(&p[0]))?;
+    let denominators = divisors(&integral(p.last().unwrap()))?;
+    if numerators.len().saturating_mul(denominators.len()) > MAX_CANDIDATES {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    for n in numerators.iter() {
+        for d in denominators.iter() {
+            for sign in [1, -1] {
+                let x = Rational::from_naturals(n.clone(), d.clone()) * Rational::from(sign);
+                while p.len() > 1 && eval(&p, &x) == 0u32 {
+                    p = div_rem(&p, &[-&x, Rational::ONE])?.0;
+                    ret.push(x.clone());
+                }
+            }
+        }
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+/// `p` in standard notation, highest power first, e.g. `3x^2 - 1/2x + 1`.
+/// Coefficients are written by `coefficient`.
+pub fn notation(p: &[Rational], coefficient: impl Fn(&Rational) -> String) -> String {
+    let mut ret = String::new();
+    for (k, c) in p.iter().enumerate().rev() {
+        if *c == 0u32 {
+            continue;
+        }
+        let negative = *c < 0u32;
+        ret.push_str(match (ret.is_empty(), negative) {
+            (true, false) => "",
+            (true, true) => "-",
+            (false, false) => " + ",
+            (false, true) => " - ",
+        });
+        let magnitude = if negative { -c } else { c.clone() };
+        if k == 0 || magnitude != 1u32 {
+            ret.push_str(&coefficient(&magnitude));
+        }
+        match k {
+            0 => {}
+            1 => ret.push('x'),
+            _ => ret.push_str(&format!("x^{k}")),
+        }
+    }
+    if ret.is_empty() {
+        ret.push('0');
+    }
+    ret
+}
+
+/// Coefficients of a polynomial given as `(coefficient, power)` terms, as
+/// read from standard notation.
+pub fn from_terms(terms: impl Iterator<Item = (Rational, usize)>) -> Vec<Rational> {
+    let mut ret = vec![];
+    for (c, k) in terms {
+        if ret.len() <= k {
+            ret.resize(k + 1, Rational::ZERO);
+        }
+        ret[k] += c;
+    }
+    normalize(ret)
+}
@@ -0,0 +1,109 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use malachite::num::conversion::traits::WrappingFrom;
+use malachite::num::logic::traits::SignificantBits;
+use malachite::{Integer, Natural, Rational};
+
+use super::eval::{EvalError, Values};
+
+/// State a fresh session starts from, so results reproduce without a `seed`
+/// and without asking the OS for entropy.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// The state `seed` sets for an `Int`, its low 64 bits in two's complement.
+pub fn seed(n: &Integer) -> u64 {
+    u64::wrapping_from(n)
+}
+
+/// The next word of SplitMix64, whose whole state is the one word a session
+/// saves.
+pub fn next(state: &Cell<u64>) -> u64 {
+    let s = state.get().wrapping_add(0x9e3779b97f4a7c15);
+    state.set(s);
+    let mut z = s;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Uniform in `[0, n)` for `n > 0`, rejecting draws past the largest
+/// multiple of `n` so small ranges are not biased.
+fn below_u64(state: &Cell<u64>, n: u64) -> u64 {
+    let zone = u64::MAX - u64::MAX % n;
+    loop {
+        let x = next(state);
+        if x < zone {
+            return x % n;
+        }
+    }
+}
+
+/// Uniform in `[0, n)` for `n > 0`, drawing just enough bits and retrying
+/// when they land past `n`.
+fn below(state: &Cell<u64>, n: &Natural) -> Natural {
+    let bits = (n - Natural::from(1u32)).significant_bits();
+    let words = bits.div_ceil(64);
+    loop {
+        let mut x = Natural::from(0u32);
+        for _ in 0..words {
+            x = (x << 64) + Natural::from(next(state));
+        }
+        x >>= words * 64 - bits;
+        if x < *n {
+            return x;
+        }
+    }
+}
+
+/// Uniform in `[lo, hi]`, both included.
+pub fn int(state: &Cell<u64>, lo: &Integer, hi: &Integer) -> Result<Integer, EvalError> {
+    if lo > hi {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    let n = Natural::try_from(hi - lo + Integer::from(1u32)).unwrap();
+    Ok(lo + Integer::from(below(state, &n)))
+}
+
+/// Uniform in `[0, 1)` with 64 random bits.
+pub fn rat(state: &Cell<u64>) -> Rational {
+    Rational::from(next(state)) >> 64u64
+}
+
+/// Swaps a uniformly chosen remaining element into each of the first `k`
+/// places, Fisher-Yates style.
+fn shuffle_prefix(state: &Cell<u64>, data: &mut VecDeque<Values>, k: usize) {
+    for i in 0..k.min(data.len()) {
+        let j = i + below_u64(state, (data.len() - i) as u64) as usize;
+        data.swap(i, j);
+    }
+}
+
+pub fn shuffle(state: &Cell<u64>, mut data: VecDeque<Values>) -> VecDeque<Values> {
+    let len = data.len();
+    shuffle_prefix(state, &mut data, len);
+    data
+}
+
+pub fn choice(state: &Cell<u64>, mut data: VecDeque<Values>) -> Result<Values, EvalError> {
+    if data.is_empty() {
+        return Err(EvalError::EmptyCollection);
+    }
+    let i = below_u64(state, data.len() as u64) as usize;
+    Ok(data.swap_remove_back(i).unwrap())
+}
+
+/// `k` elements at distinct positions, in the order they were drawn.
+pub fn sample(
+    state: &Cell<u64>,
+    mut data: VecDeque<Values>,
+    k: &Integer,
+) -> Result<VecDeque<Values>, EvalError> {
+    let k = usize::try_from(k)
+        .ok()
+        .filter(|k| *k <= data.len())
+        .ok_or(EvalError::PrimitiveEvalErr)?;
+    shuffle_prefix(state, &mut data, k);
+    data.truncate(k);
+    Ok(data)
+}
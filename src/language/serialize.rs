@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+
+use super::ast::Type;
+use super::ast::stack::Stack;
+use super::env::{CallType, Env};
+use super::parse::ParseCtx;
+use super::repr::Representation;
+use super::units::BUILTIN;
+
+/// Canonical source for everything defined in `env`, one top-level item per
+/// entry, in an order that parses back into the same `Env`: units, types,
+/// interfaces, definitions, modules, protocol overloads, then impls and
+/// `use` aliases. Built-ins (primitive overloads, `Error` and the built-in
+/// units) are left out as every `Env` already has them.
+pub fn env_items(env: &Env, ctx: &ParseCtx) -> Vec<String> {
+    let mut ret = vec![];
+
+    let units: BTreeMap<_, _> = env
+        .units
+        .values()
+        .filter(|x| !BUILTIN.iter().any(|(name, ..)| *name == x.name))
+        .map(|x| (&x.name, x.definition()))
+        .collect();
+    ret.extend(units.into_values());
+
+    let types: BTreeMap<_, _> = env
+        .typ_data
+        .keys()
+        .filter(|(name, _)| *name != env.error_type)
+        .map(|(name, _)| (ctx.lookup_type_name(*name), *name))
+        .collect();
+    for id in types.values() {
+        ret.extend(type_item(env, ctx, *id));
+    }
+
+    let interfaces: BTreeMap<_, _> = env
+        .interfaces
+        .iter()
+        .map(|(name, methods)| (ctx.lookup_type_name(*name), methods))
+        .collect();
+    for (name, methods) in interfaces.iter() {
+        ret.push(in_module(name, |module, name| {
+            format!(
+                "interface {name} {{ {} }}",
+                methods
+                    .iter()
+                    .map(|x| {
+                        let method = ctx.lookup_call_name(*x);
+                        match (module, method.split_once("::")) {
+                            (Some(module), Some((prefix, member))) if module == prefix => {
+                                member.to_string()
+                            }
+                            _ => method,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )
+        }));
+    }
+
+    let mut modules: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let defs: BTreeMap<_, _> = env
+        .data
+        .iter()
+        .map(|(name, body)| (ctx.lookup_call_name(*name), body))
+        .collect();
+    for (name, body) in defs {
+        match name.split_once("::") {
+            Some((module, member)) => modules
+                .entry(module.to_string())
+                .or_default()
+                .push(format!("{member} = {};", stack_repr(body, ctx))),
+            None => ret.push(format!("{name} = {}", stack_repr(body, ctx))),
+        }
+    }
+    for (module, members) in modules {
+        ret.push(format!("module {module} {{ {} }}", members.join(" ")));
+    }
+
+    let mut overloads = vec![];
+    for (name, impls) in env.protocol_data.iter() {
+        for types in impls.keys() {
+            overloads.extend(overload_item(env, ctx, *name, types));
+        }
+    }
+    overloads.sort();
+    // Module protocols go first, before a global protocol of the same name
+    // would make the module extend it.
+    overloads.sort_by_key(|x| !x.starts_with("module "));
+    ret.extend(overloads);
+
+    let mut impls: Vec<_> = env
+        .interface_impls
+        .iter()
+        .flat_map(|(interface, types)| {
+            types
+                .iter()
+                .map(|x| (ctx.lookup_type_name(*interface), x.get_repr(ctx)))
+        })
+        .collect();
+    impls.sort();
+    for (interface, typ) in impls {
+        ret.push(format!("impl {interface} for {typ} {{}}"));
+    }
+
+    let mut aliases = ctx.get_aliases();
+    aliases.sort();
+    for qualified in aliases {
+        ret.push(format!("use {qualified}"));
+    }
+    ret
+}
+
+/// Source of the struct or enum `id`, e.g. `struct Point { x y }`.
+pub fn type_item(env: &Env, ctx: &ParseCtx, id: usize) -> Option<String> {
+    let shapes: BTreeMap<_, _> = env
+        .typ_data
+        .iter()
+        .filter(|((name, _), _)| *name == id)
+        .map(|((_, tag), fields)| (tag.map(|x| ctx.lookup_tag_name(x)), types_repr(fields, ctx)))
+        .collect();
+    if shapes.is_empty() {
+        return None;
+    }
+    let params = match env.type_params.get(&id) {
+        Some(params) if !params.is_empty() => format!(
+            "<{}>",
+            params
+                .iter()
+                .map(|x| ctx.lookup_type_name(*x))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => String::new(),
+    };
+    Some(in_module(
+        &ctx.lookup_type_name(id),
+        |_, type_name| match shapes.get(&None) {
+            Some(fields) => format!("struct {type_name}{params} {{ {fields} }}"),
+            None => format!(
+                "enum {type_name}{params} {{ {} }}",
+                shapes
+                    .iter()
+                    .map(|(tag, fields)| format!("| {}({fields})", tag.as_ref().unwrap()))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        },
+    ))
+}
+
+/// Source of the definition `id` on its own. Module members come wrapped in
+/// their `module` block so they are redefined under the same name.
+pub fn definition_item(env: &Env, ctx: &ParseCtx, id: usize) -> Option<String> {
+    let body = stack_repr(env.data.get(&id)?, ctx);
+    Some(in_module(&ctx.lookup_call_name(id), |_, name| {
+        format!("{name} = {body}")
+    }))
+}
+
+/// Source of the user written overload of protocol `id` for `types`.
+/// Built-in overloads have none.
+pub fn overload_item(env: &Env, ctx: &ParseCtx, id: usize, types: &[Type]) -> Option<String> {
+    match env.protocol_data.get(&id)?.get(types)? {
+        (_, CallType::Stack(body)) => Some(in_module(&ctx.lookup_call_name(id), |_, name| {
+            format!(
+                "{name}({}) = {}",
+                types_repr(types, ctx),
+                stack_repr(body, ctx)
+            )
+        })),
+        _ => None,
+    }
+}
+
+/// `item(module, name)` for the unqualified `name`, wrapped in its `module`
+/// block when `qualified` is a module member.
+fn in_module(qualified: &str, item: impl FnOnce(Option<&str>, &str) -> String) -> String {
+    match qualified.split_once("::") {
+        Some((module, name)) => format!("module {module} {{ {}; }}", item(Some(module), name)),
+        None => item(None, qualified),
+    }
+}
+
+fn types_repr(types: &[Type], ctx: &ParseCtx) -> String {
+    types
+        .iter()
+        .map(|x| x.get_repr(ctx))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn stack_repr(stack: &Stack, ctx: &ParseCtx) -> String {
+    stack
+        .elems
+        .iter()
+        .map(|x| x.get_repr(ctx))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::eval::Values;
+    use crate::language::vm::VM;
+
+    const DEFS: &str = "
+        struct Point { Int Int };
+        struct Pair<'a> { 'a 'a };
+        enum Shape { | Circle(Rat) | Rect(Rat Rat) | Empty() };
+        interface Scale { scale };
+        scale(Point Int) = | Point(x y) k => Point(x k mul y k mul), ;
+        impl Scale for Point {};
+        module geo { sq = |x| {x x mul}; quad = sq sq; };
+        module shapes {
+            struct Box { Int }; interface Sized { size };
+            size(Box) = | Box(x) => x, ; impl Sized for Box {};
+        };
+        use geo::quad;
+        twice = |f| {f apply f apply};
+        unit furlong = 201.168 m;
+    ";
+
+    fn vm_with(source: &str) -> VM {
+        let mut vm = VM::default();
+        vm.parse_full_program(DEFS).unwrap();
+        vm.parse_full_program(source).unwrap();
+        vm.eval().unwrap();
+        vm
+    }
+
+    fn assert_round_trip(source: &str) {
+        let vm = vm_with(source);
+        assert!(!vm.stack.is_empty());
+        for value in vm.stack.iter() {
+            let repr = value.get_repr(&vm.parse_ctx);
+            let mut again = vm.clone();
+            again.stack = Default::default();
+            again
+                .parse_snippet(&repr)
+                .unwrap_or_else(|err| panic!("{repr} doesn't parse:\n{err}"));
+            again.eval().unwrap();
+            assert_eq!(again.stack.iter().collect::<Vec<_>>(), [value], "{repr}");
+        }
+    }
+
+    #[test]
+    fn scalars() {
+        assert_round_trip("0 -7 123456789012345678901234567890;");
+        assert_round_trip("1.5 -0.25 2.0 0.0 1/3 -22/7;");
+        assert_round_trip("true false;");
+        // Shown as `1/2-1/4i`, which reads as `1/2 - (1/4)i`.
+        assert_round_trip("3+4i 1/2-1/4i 0.0-1.0i;");
+    }
+
+    #[test]
+    fn collections() {
+        assert_round_trip("List() List(1 2.5 true) List(List(1) Set(2 3));");
+        assert_round_trip("Set() Set(3 1 2 1/2) Set(List(1 2));");
+        assert_round_trip("Map() Map(List(1 true) List(2 List(3 4)));");
+        assert_round_trip("Map(List(Set(1) Map(List(1 2))));");
+        assert_round_trip("Vec() Vec(1 -1/2 2.5) Mat() Mat(Vec(1 2) Vec(3/4 0));");
+        assert_round_trip("Poly() Poly(1 0 -1/2) Poly(x^2 - 3x + 1/2);");
+    }
+
+    #[test]
+    fn quantities() {
+        assert_round_trip("3.5 m 2 kg s^-2 1500 m to km 90 km h^-1 to (m s^-1);");
+        assert_round_trip("3 furlong 1/3 N 2 A s;");
+    }
+
+    #[test]
+    fn customs() {
+        assert_round_trip("Point(1 2) Pair(Point(1 2) Point(3 4)) Pair(1/2 3.0);");
+        assert_round_trip("Shape::Circle(1.5) Shape::Rect(1/3 2.0) Shape::Empty();");
+        assert_round_trip("Option::Some(List(Option::None())) Result::Err(3);");
+        assert_round_trip("shapes::Box(3) [shapes::Box(1) shapes::size];");
+    }
+
+    #[test]
+    fn quotations() {
+        assert_round_trip("[] [1 2 add] [true false and] [Point(1 2) 3 scale];");
+        assert_round_trip("[List(1 2) Set(3) Map(List(1 2)) Shape::Empty()];");
+        assert_round_trip("[Vec(1 2) Mat(Vec(1) Vec(2)) 3+4i];");
+        assert_round_trip("[3 km s^-2 N to (kg m) to furlong];");
+        assert_round_trip("[|a b| {b a sub}] [while dup 10 le {1 add}] [true ?{1} ];");
+        assert_round_trip("[4 geo::sq quad twice] [try { 1 throw } catch | x => x, ];");
+        assert_round_trip("5 |n| { [n 2.5 add 1/3] };");
+    }
+
+    #[test]
+    fn patterns() {
+        assert_round_trip(
+            "[ | 1 1/2 0.5 true _ x when x 1 ge => x,
+               | Int(a) Rat(_) Bool(b) Quote(_) => a b,
+               | List() List(x) List($) List($r) List(x $r) List(x $) List($r y) List(x $ y) => r,
+               | Set() Set(x) Set($) Set($s) Set(x $s) Set(x $) => s,
+               | Point(x y) Point($) Point($p) Point(x $p y) => p,
+               | Shape::Empty() Shape::Circle(r) Shape::Rect($) Shape::Rect($q) => q, ];",
+        );
+    }
+
+    #[test]
+    fn env() {
+        let vm = vm_with("");
+        let items = env_items(&vm.env, &vm.parse_ctx);
+
+        let mut again = VM::default();
+        for item in items.iter() {
+            again
+                .parse_snippet(item)
+                .unwrap_or_else(|err| panic!("{item} doesn't parse:\n{err}"));
+        }
+        assert_eq!(env_items(&again.env, &again.parse_ctx), items);
+
+        again
+            .parse_snippet("Point(1 2) 3 scale 2 quad 3 geo::sq")
+            .unwrap();
+        again.eval().unwrap();
+        assert_eq!(
+            again.stack.iter().skip(1).collect::<Vec<_>>(),
+            [&Values::Int(16.into()), &Values::Int(9.into())]
+        );
+        assert_eq!(again.stack[0].get_repr(&again.parse_ctx), "Point(3,6)");
+    }
+
+    #[test]
+    fn session() {
+        let vm = vm_with("7 seed 1/3 Shape::Rect(1.0 2.0) [twice] 1 6 rand_int;");
+        let text = vm.save_session(&[]).to_text();
+        let again = VM::open_session(&crate::language::session::Session::from_text(&text).unwrap())
+            .unwrap();
+        let reprs = |vm: &VM| {
+            vm.stack
+                .iter()
+                .map(|x| x.get_repr(&vm.parse_ctx))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(reprs(&again), reprs(&vm));
+        assert_eq!(again.save_session(&[]).to_text(), text);
+        let draw = |mut vm: VM| {
+            vm.parse_full_program("0 1000000 rand_int;").unwrap();
+            vm.eval().unwrap();
+            vm.stack.pop()
+        };
+        assert_eq!(draw(again), draw(vm));
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use super::math::MAX_PRECISION;
+use super::number::{IntFormat, NumberFormat};
+
+const HEADER: &str = "stackulator session 1";
+
+/// Everything needed to rebuild a running session: registered module
+/// sources, the canonical source of every top-level definition (see
+/// `serialize::env_items`), the value stack, the input history, the number
+/// display modes, the precision of irrational results and the state of the
+/// random generator.
+///
+/// The text format is a header line followed by sections of the form
+/// `%<kind> [name] <line count>` and exactly that many lines of content,
+/// so sources may contain anything, including lines starting with `%`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub modules: Vec<(String, Arc<String>)>,
+    pub definitions: Vec<Arc<String>>,
+    pub stack: Vec<String>,
+    pub history: Vec<Arc<String>>,
+    /// Absent in sessions saved before display modes existed.
+    pub number_format: Option<NumberFormat>,
+    pub int_format: Option<IntFormat>,
+    pub precision: Option<usize>,
+    /// The random generator's state, so draws continue where they left off.
+    pub seed: Option<u64>,
+}
+
+impl Session {
+    pub fn to_text(&self) -> String {
+        let mut ret = String::from(HEADER);
+        ret.push('\n');
+        for (name, source) in self.modules.iter() {
+            push_section(&mut ret, &format!("module {name}"), source);
+        }
+        for source in self.definitions.iter() {
+            push_section(&mut ret, "definition", source);
+        }
+        for value in self.stack.iter() {
+            push_section(&mut ret, "stack", value);
+        }
+        for entry in self.history.iter() {
+            push_section(&mut ret, "history", entry);
+        }
+        if let Some(format) = self.number_format {
+            push_section(&mut ret, "format", &format.to_string());
+        }
+        if let Some(format) = self.int_format {
+            push_section(&mut ret, "intformat", &format.to_string());
+        }
+        if let Some(precision) = self.precision {
+            push_section(&mut ret, "precision", &precision.to_string());
+        }
+        if let Some(seed) = self.seed {
+            push_section(&mut ret, "seed", &seed.to_string());
+        }
+        ret
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err("not a stackulator session file".to_string()),
+        }
+
+        let mut ret = Session::default();
+        while let Some((line_no, line)) = lines.next() {
+            let malformed = || format!("line {}: malformed section header {line:?}", line_no + 1);
+            let mut words = line.strip_prefix('%').ok_or_else(malformed)?.split(' ');
+            let kind = words.next().ok_or_else(malformed)?;
+            let name = match kind {
+                "module" => Some(words.next().ok_or_else(malformed)?),
+                _ => None,
+            };
+            let count: usize = words
+                .next()
+                .and_then(|x| x.parse().ok())
+                .ok_or_else(malformed)?;
+            if words.next().is_some() {
+                return Err(malformed());
+            }
+
+            let mut body = Vec::with_capacity(count);
+            for _ in 0..count {
+                match lines.next() {
+                    Some((_, line)) => body.push(line),
+                    None => return Err(format!("section {kind} is cut short")),
+                }
+            }
+            let body = body.join("\n");
+
+            match (kind, name) {
+                ("module", Some(name)) => ret.modules.push((name.to_string(), Arc::new(body))),
+                ("definition", _) => ret.definitions.push(Arc::new(body)),
+                ("stack", _) => ret.stack.push(body),
+                ("history", _) => ret.history.push(Arc::new(body)),
+                ("format", _) => {
+                    ret.number_format = Some(
+                        body.parse()
+                            .map_err(|err| format!("line {}: {err}", line_no + 1))?,
+                    )
+                }
+                ("intformat", _) => {
+                    ret.int_format = Some(
+                        body.parse()
+                            .map_err(|err| format!("line {}: {err}", line_no + 1))?,
+                    )
+                }
+                ("precision", _) => {
+                    ret.precision = Some(
+                        body.parse()
+                            .ok()
+                            .filter(|x| (1..=MAX_PRECISION).contains(x))
+                            .ok_or_else(|| format!("line {}: invalid precision", line_no + 1))?,
+                    )
+                }
+                ("seed", _) => {
+                    ret.seed = Some(
+                        body.parse()
+                            .map_err(|_| format!("line {}: invalid seed", line_no + 1))?,
+                    )
+                }
+                _ => return Err(format!("line {}: unknown section {kind}", line_no + 1)),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+fn push_section(out: &mut String, kind: &str, body: &str) {
+    let lines: Vec<&str> = body.lines().collect();
+    out.push_str(&format!("%{kind} {}\n", lines.len()));
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+}
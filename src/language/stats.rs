@@ -0,0 +1,176 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+
+use malachite::num::arithmetic::traits::Floor;
+use malachite::num::basic::traits::{One, Zero};
+use malachite::{Integer, Rational};
+
+use super::eval::{EvalError, Values};
+use super::math;
+
+/// Entries of a `List` of numbers or of a `Vector`, and whether they were
+/// all `Int`s so results can stay `Int`s.
+pub fn numbers(value: Values) -> Result<(Vec<Rational>, bool), EvalError> {
+    match value {
+        Values::Vector(v) => Ok((v, false)),
+        Values::List(l) => {
+            let mut integral = true;
+            let data = l
+                .into_iter()
+                .map(|x| match x {
+                    Values::Int(i) => Ok(Rational::from(i)),
+                    Values::Float(f) => {
+                        integral = false;
+                        Ok(f)
+                    }
+                    other => Err(EvalError::StatsExpectsNumberButGot(other)),
+                })
+                .collect::<Result<_, _>>()?;
+            Ok((data, integral))
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// `x` as an `Int` if `integral`, otherwise as a `Rat`.
+pub fn number(x: Rational, integral: bool) -> Values {
+    if integral {
+        Values::Int(Integer::try_from(&x).unwrap())
+    } else {
+        Values::Float(x)
+    }
+}
+
+/// The order `sort` uses: numbers by value, so `1/2` comes before `1`, and
+/// everything else by the usual order of values.
+pub fn compare(a: &Values, b: &Values) -> Ordering {
+    match (a, b) {
+        (Values::Int(a), Values::Float(b)) => Rational::from(a).cmp(b),
+        (Values::Float(a), Values::Int(b)) => a.cmp(&Rational::from(b)),
+        (a, b) => a.cmp(b),
+    }
+}
+
+pub fn sum(data: &[Rational]) -> Rational {
+    data.iter().fold(Rational::ZERO, |acc, x| acc + x)
+}
+
+pub fn product(data: &[Rational]) -> Rational {
+    data.iter().fold(Rational::ONE, |acc, x| acc * x)
+}
+
+pub fn cumsum(data: &[Rational]) -> Vec<Rational> {
+    data.iter()
+        .scan(Rational::ZERO, |acc, x| {
+            *acc += x;
+            Some(acc.clone())
+        })
+        .collect()
+}
+
+pub fn mean(data: &[Rational]) -> Result<Rational, EvalError> {
+    if data.is_empty() {
+        return Err(EvalError::EmptyCollection);
+    }
+    Ok(sum(data) / Rational::from(data.len()))
+}
+
+/// The middle entry, or the mean of the two middle ones.
+pub fn median(mut data: Vec<Rational>) -> Result<Rational, EvalError> {
+    if data.is_empty() {
+        return Err(EvalError::EmptyCollection);
+    }
+    data.sort();
+    let mid = data.len() / 2;
+    Ok(if data.len().is_multiple_of(2) {
+        (&data[mid - 1] + &data[mid]) / Rational::from(2u32)
+    } else {
+        data.swap_remove(mid)
+    })
+}
+
+/// The population variance, the mean squared distance from the mean.
+pub fn variance(data: &[Rational]) -> Result<Rational, EvalError> {
+    let mean = mean(data)?;
+    let squares = data.iter().map(|x| {
+        let d = x - &mean;
+        &d * &d
+    });
+    Ok(squares.fold(Rational::ZERO, |acc, x| acc + x) / Rational::from(data.len()))
+}
+
+/// The population standard deviation, exact when the variance is a square.
+pub fn stddev(data: &[Rational], digits: usize) -> Result<Rational, EvalError> {
+    math::sqrt(&variance(data)?, digits)
+}
+
+/// The `q`-quantile for `0 <= q <= 1`, interpolating linearly between the
+/// two nearest entries.
+pub fn quantile(mut data: Vec<Rational>, q: &Rational) -> Result<Rational, EvalError> {
+    if data.is_empty() {
+        return Err(EvalError::EmptyCollection);
+    }
+    if *q < 0u32 || *q > 1u32 {
+        return Err(EvalError::PrimitiveEvalErr);
+    }
+    data.sort();
+    let h = Rational::from(data.len() - 1) * q;
+    let lo = (&h).floor();
+    let frac = h - Rational::from(lo.clone());
+    let lo = usize::try_from(&lo).unwrap();
+    Ok(match data.get(lo + 1) {
+        Some(next) => &data[lo] + frac * (next - &data[lo]),
+        None => data.swap_remove(lo),
+    })
+}
+
+/// Each entry with how often it occurs, in `compare` order. Entries equal
+/// under `compare`, like `2` and `2.0`, count as one, kept as the first seen.
+fn counts(data: VecDeque<Values>) -> Vec<(Values, usize)> {
+    let mut data = Vec::from(data);
+    data.sort_by(compare);
+    let mut ret: Vec<(Values, usize)> = vec![];
+    for x in data {
+        match ret.last_mut() {
+            Some((y, n)) if compare(y, &x) == Ordering::Equal => *n += 1,
+            _ => ret.push((x, 1)),
+        }
+    }
+    ret
+}
+
+/// How often each entry occurs.
+pub fn histogram(data: VecDeque<Values>) -> BTreeMap<Values, Values> {
+    counts(data)
+        .into_iter()
+        .map(|(x, n)| (x, Values::Int(n.into())))
+        .collect()
+}
+
+/// The most frequent entry, the least of them in `compare` order on ties.
+pub fn mode(data: VecDeque<Values>) -> Result<Values, EvalError> {
+    counts(data)
+        .into_iter()
+        .min_by(|(_, n), (_, m)| m.cmp(n))
+        .map(|(x, _)| x)
+        .ok_or(EvalError::EmptyCollection)
+}
+
+/// The least or, if `greatest`, the greatest entry in `compare` order.
+pub fn extreme(data: VecDeque<Values>, greatest: bool) -> Result<Values, EvalError> {
+    let ret = if greatest {
+        data.into_iter().max_by(compare)
+    } else {
+        data.into_iter().min_by(compare)
+    };
+    ret.ok_or(EvalError::EmptyCollection)
+}
+
+/// Entries of a `List` or `Vector` as values.
+pub fn entries(value: Values) -> VecDeque<Values> {
+    match value {
+        Values::List(l) => l,
+        Values::Vector(v) => v.into_iter().map(Values::Float).collect(),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,260 @@
+use malachite::Rational;
+use malachite::num::arithmetic::traits::Pow;
+use malachite::num::basic::traits::One;
+
+use super::ast::Type;
+use super::eval::{EvalError, Values};
+
+/// Powers of the SI base units, in the order of `BASE_UNITS`.
+pub type Dimension = [i32; 7];
+
+pub const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+const DIMENSIONLESS: Dimension = [0; 7];
+
+/// Units every session has, as (name, size in base units as numerator and
+/// denominator, dimension).
+pub const BUILTIN: &[(&str, (u64, u64), Dimension)] = &[
+    ("m", (1, 1), [1, 0, 0, 0, 0, 0, 0]),
+    ("km", (1000, 1), [1, 0, 0, 0, 0, 0, 0]),
+    ("cm", (1, 100), [1, 0, 0, 0, 0, 0, 0]),
+    ("mm", (1, 1000), [1, 0, 0, 0, 0, 0, 0]),
+    ("kg", (1, 1), [0, 1, 0, 0, 0, 0, 0]),
+    ("g", (1, 1000), [0, 1, 0, 0, 0, 0, 0]),
+    ("mg", (1, 1000000), [0, 1, 0, 0, 0, 0, 0]),
+    ("s", (1, 1), [0, 0, 1, 0, 0, 0, 0]),
+    ("ms", (1, 1000), [0, 0, 1, 0, 0, 0, 0]),
+    ("h", (3600, 1), [0, 0, 1, 0, 0, 0, 0]),
+    ("A", (1, 1), [0, 0, 0, 1, 0, 0, 0]),
+    ("K", (1, 1), [0, 0, 0, 0, 1, 0, 0]),
+    ("mol", (1, 1), [0, 0, 0, 0, 0, 1, 0]),
+    ("cd", (1, 1), [0, 0, 0, 0, 0, 0, 1]),
+    ("L", (1, 1000), [3, 0, 0, 0, 0, 0, 0]),
+    ("Hz", (1, 1), [0, 0, -1, 0, 0, 0, 0]),
+    ("N", (1, 1), [1, 1, -2, 0, 0, 0, 0]),
+    ("Pa", (1, 1), [-1, 1, -2, 0, 0, 0, 0]),
+    ("J", (1, 1), [2, 1, -2, 0, 0, 0, 0]),
+    ("W", (1, 1), [2, 1, -3, 0, 0, 0, 0]),
+    ("C", (1, 1), [0, 0, 1, 1, 0, 0, 0]),
+    ("V", (1, 1), [2, 1, -3, -1, 0, 0, 0]),
+];
+
+/// Operand types of `mul` and `div` that take a `Quantity`.
+pub const OPERANDS: [[Type; 2]; 5] = [
+    [Type::Quantity, Type::Quantity],
+    [Type::Quantity, Type::Integer],
+    [Type::Integer, Type::Quantity],
+    [Type::Quantity, Type::Float],
+    [Type::Float, Type::Quantity],
+];
+
+/// Powers a unit may be raised to before results are refused.
+const MAX_POWER: i32 = 1 << 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    /// Size in base units.
+    pub scale: Rational,
+    pub dim: Dimension,
+}
+
+impl Unit {
+    pub fn builtin((name, (n, d), dim): &(&str, (u64, u64), Dimension)) -> Self {
+        Unit {
+            name: name.to_string(),
+            scale: Rational::from_unsigneds(*n, *d),
+            dim: *dim,
+        }
+    }
+
+    /// The unit `name` the size of `value`, an `Int`, `Rat` or `Quantity`.
+    pub fn from_value(name: &str, value: Values) -> Result<Self, EvalError> {
+        let q = quantity(value)?;
+        Ok(Unit {
+            name: name.to_string(),
+            scale: q.value,
+            dim: q.dim,
+        })
+    }
+
+    /// `unit <name> = <size>` with the size in base units, so it reads back
+    /// without needing the units it was defined with.
+    pub fn definition(&self) -> String {
+        let size = Quantity {
+            value: self.scale.clone(),
+            dim: self.dim,
+            shown: vec![],
+            scale: Rational::ONE,
+        };
+        format!("unit {} = {}", self.name, size.format(|x| x.to_string()))
+    }
+}
+
+/// A number with a dimension.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Quantity {
+    /// Magnitude in base units.
+    pub value: Rational,
+    pub dim: Dimension,
+    /// Units it is shown in with their powers, base units if there are none.
+    pub shown: Vec<(String, i32)>,
+    /// Size of `shown` in base units.
+    pub scale: Rational,
+}
+
+impl Quantity {
+    /// E.g. `7/2 km h^-1`, the magnitude written by `number`.
+    pub fn format(&self, number: impl Fn(&Rational) -> String) -> String {
+        let units: Vec<_> = if self.shown.is_empty() {
+            BASE_UNITS
+                .iter()
+                .zip(self.dim)
+                .filter(|(_, power)| *power != 0)
+                .map(|(name, power)| power_repr(name, power))
+                .collect()
+        } else {
+            self.shown
+                .iter()
+                .map(|(name, power)| power_repr(name, *power))
+                .collect()
+        };
+        let magnitude = number(&(&self.value / &self.scale));
+        if units.is_empty() {
+            return magnitude;
+        }
+        format!("{magnitude} {}", units.join(" "))
+    }
+}
+
+/// `name` or `name^power` as written in the source.
+pub fn power_repr(name: &str, power: i32) -> String {
+    match power {
+        1 => name.to_string(),
+        _ => format!("{name}^{power}"),
+    }
+}
+
+/// `value` as a `Quantity`, numbers being dimensionless.
+fn quantity(value: Values) -> Result<Quantity, EvalError> {
+    let value = match value {
+        Values::Quantity(q) => return Ok(*q),
+        Values::Int(i) => Rational::from(i),
+        Values::Float(f) => f,
+        other => return Err(EvalError::UnitExpectsNumberButGot(other)),
+    };
+    Ok(Quantity {
+        value,
+        dim: DIMENSIONLESS,
+        shown: vec![],
+        scale: Rational::ONE,
+    })
+}
+
+/// `q` as a value, a plain `Rat` once the units cancel out.
+fn simplify(q: Quantity) -> Values {
+    if q.dim == DIMENSIONLESS {
+        Values::Float(q.value)
+    } else {
+        Values::Quantity(Box::new(q))
+    }
+}
+
+/// `a * b^sign` for `sign` 1 or -1.
+fn product(a: Quantity, b: Quantity, sign: i32) -> Result<Values, EvalError> {
+    let mut dim = a.dim;
+    for (x, y) in dim.iter_mut().zip(b.dim) {
+        *x = x
+            .checked_add(y * sign)
+            .filter(|x| x.abs() <= MAX_POWER)
+            .ok_or(EvalError::PrimitiveEvalErr)?;
+    }
+    let mut shown = a.shown;
+    for (name, power) in b.shown {
+        match shown.iter_mut().find(|(x, _)| *x == name) {
+            Some((_, p)) => *p += power * sign,
+            None => shown.push((name, power * sign)),
+        }
+    }
+    shown.retain(|(_, power)| *power != 0);
+    let (value, scale) = if sign < 0 {
+        if b.value == 0u32 {
+            return Err(EvalError::DivisionByZero);
+        }
+        (a.value / b.value, a.scale / b.scale)
+    } else {
+        (a.value * b.value, a.scale * b.scale)
+    };
+    Ok(simplify(Quantity {
+        value,
+        dim,
+        shown,
+        scale,
+    }))
+}
+
+pub fn mul(a: Values, b: Values) -> Result<Values, EvalError> {
+    product(quantity(a)?, quantity(b)?, 1)
+}
+
+pub fn div(a: Values, b: Values) -> Result<Values, EvalError> {
+    product(quantity(a)?, quantity(b)?, -1)
+}
+
+/// `a + b` or `a - b` for `sign` 1 or -1, shown in the units of `a`.
+pub fn add(a: Quantity, b: Quantity, sign: i32) -> Result<Values, EvalError> {
+    if a.dim != b.dim {
+        return Err(EvalError::IncompatibleDimensions(Box::new((
+            Values::Quantity(Box::new(a)),
+            Values::Quantity(Box::new(b)),
+        ))));
+    }
+    let value = if sign < 0 {
+        &a.value - b.value
+    } else {
+        &a.value + b.value
+    };
+    Ok(Values::Quantity(Box::new(Quantity { value, ..a })))
+}
+
+/// `value` times `unit^power`, as written `3.5 km` or `2 kg s^-2`.
+pub fn apply(value: Values, unit: &Unit, power: i32) -> Result<Values, EvalError> {
+    let mut dim = unit.dim;
+    for x in dim.iter_mut() {
+        *x = x
+            .checked_mul(power)
+            .filter(|x| x.abs() <= MAX_POWER)
+            .ok_or(EvalError::PrimitiveEvalErr)?;
+    }
+    let scale = (&unit.scale).pow(i64::from(power));
+    let factor = Quantity {
+        value: scale.clone(),
+        dim,
+        shown: vec![(unit.name.clone(), power)],
+        scale,
+    };
+    product(quantity(value)?, factor, 1)
+}
+
+/// `value` shown in the units of `target`, which must have its dimension.
+pub fn convert(value: Values, target: Values) -> Result<Values, EvalError> {
+    let (value, target) = (quantity(value)?, quantity(target)?);
+    if value.dim != target.dim {
+        return Err(EvalError::IncompatibleDimensions(Box::new((
+            simplify(value),
+            simplify(target),
+        ))));
+    }
+    Ok(simplify(Quantity {
+        shown: target.shown,
+        scale: target.scale,
+        ..value
+    }))
+}
+
+/// The product of `units`, each raised to its power, `1` for none.
+pub fn target<'a>(mut units: impl Iterator<Item = (&'a Unit, i32)>) -> Result<Values, EvalError> {
+    units.try_fold(Values::Int(1.into()), |acc, (unit, power)| {
+        apply(acc, unit, power)
+    })
+}
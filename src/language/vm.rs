@@ -1,113 +1,99 @@
 use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::sync::Arc;
 
-use pest::error::Error;
+use pest::Span;
+use pest::error::{Error, ErrorVariant, InputLocation};
+use pest::iterators::{Pair, Pairs};
 
 use super::ast::*;
 use super::env::{CallType, Env};
-use super::parse::{Parse, ParseCtx, Rule, StackParser};
+use super::format::format_source;
+use super::math::DEFAULT_PRECISION;
+use super::module::ModuleLoader;
+use super::number::{IntFormat, NumberFormat};
+use super::parse::{Parse, ParseCtx, Rule, StackParser, clarify_error};
+use super::random;
+use super::serialize::{definition_item, env_items, overload_item, type_item};
+use super::session::Session;
+use super::units::{BUILTIN, Unit};
 use crate::language::ast::stack::Stack;
 use crate::language::eval::*;
 use crate::language::repr::Representation;
 use pest::Parser;
 
-#[derive(Default, Clone)]
+fn parse_error(message: String, span: Span<'_>) -> Box<Error<Rule>> {
+    Box::new(Error::new_from_span(
+        ErrorVariant::CustomError { message },
+        span,
+    ))
+}
+
+/// Enum variants as (tag, field types).
+pub type Variants = Vec<(String, Vec<String>)>;
+/// Protocol overloads as (signature, source of a user written overload).
+pub type Overloads = Vec<(String, Option<String>)>;
+
+/// `source` formatted for display, or as it is if it doesn't parse.
+fn pretty(source: String) -> String {
+    format_source(&source).unwrap_or(source)
+}
+
+const PRELUDE: &str = include_str!("../prelude.stk");
+
+#[derive(Clone)]
 pub struct VM {
-    pub stack: Vec<Values>,
+    pub stack: ValueStack,
     pub var_map: ChainMap,
-    pub env: Env,
+    /// Shared with undo snapshots, cloned on the first definition after one
+    /// was taken.
+    pub env: Rc<Env>,
     pub exprs: Vec<Stack>,
     pub parse_ctx: ParseCtx,
+    pub modules: ModuleLoader,
+    /// How `Rat` values on the stack are displayed.
+    pub number_format: NumberFormat,
+    /// How `Int` values on the stack are displayed.
+    pub int_format: IntFormat,
+    /// Significant digits of `sqrt`, `exp`, `ln`, `sin`, `cos`, `pi` and
+    /// irrational `pow` results.
+    pub precision: usize,
+    /// State of the generator behind `rand_int`, `shuffle` and the like, set
+    /// by `seed` and saved with the session so draws reproduce.
+    pub rng: u64,
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        let mut ret = Self {
+            stack: Default::default(),
+            var_map: Default::default(),
+            env: Default::default(),
+            exprs: Default::default(),
+            parse_ctx: Default::default(),
+            modules: Default::default(),
+            number_format: Default::default(),
+            int_format: Default::default(),
+            precision: DEFAULT_PRECISION,
+            rng: random::DEFAULT_SEED,
+        };
+        ret.parse_full_program(PRELUDE)
+            .expect("the prelude should always parse");
+        ret.eval().expect("the prelude should always evaluate");
+        ret
+    }
 }
 
 impl VM {
+    fn env_mut(&mut self) -> &mut Env {
+        Rc::make_mut(&mut self.env)
+    }
+
     pub fn parse_full_program(&mut self, source: &str) -> Result<(), Box<Error<Rule>>> {
         let pairs = StackParser::parse(Rule::defsAndExprs, source)?;
         for pair in pairs {
-            match pair.as_rule() {
-                Rule::expr => self.exprs.push(Stack {
-                    elems: pair
-                        .into_inner()
-                        .map(|x| Ast::parse(x, &mut self.parse_ctx))
-                        .collect(),
-                }),
-                Rule::def => {
-                    let mut def = pair.into_inner();
-                    let fun_name = self.parse_ctx.insert_fun(def.next().unwrap().as_str());
-                    let expr = Stack {
-                        elems: def.map(|x| Ast::parse(x, &mut self.parse_ctx)).collect(),
-                    };
-                    self.env.data.insert(fun_name, expr);
-                }
-                Rule::protocol_def => {
-                    let mut def = pair.into_inner();
-                    let fun_name = self.parse_ctx.insert_fun(def.next().unwrap().as_str());
-                    let typs: Vec<Type> = def
-                        .next()
-                        .unwrap()
-                        .into_inner()
-                        .map(|x| Type::parse(x, &mut self.parse_ctx))
-                        .collect();
-                    let expr = Stack {
-                        elems: def.map(|x| Ast::parse(x, &mut self.parse_ctx)).collect(),
-                    };
-                    if let std::collections::hash_map::Entry::Vacant(e) =
-                        self.env.protocol_arity.entry(fun_name)
-                    {
-                        e.insert((typs.len(), None));
-                        let mut map = HashMap::new();
-                        map.insert(typs, (vec![], CallType::Stack(expr)));
-                        self.env.protocol_data.insert(fun_name, map);
-                    } else {
-                        if self.env.protocol_arity[&fun_name].0 != typs.len() {
-                            todo!("Error handling")
-                        }
-                        match self.env.protocol_data.get_mut(&fun_name) {
-                            Some(r) => {
-                                r.insert(typs, (vec![], CallType::Stack(expr)));
-                            }
-                            None => {
-                                unreachable!()
-                            }
-                        }
-                    }
-                }
-                Rule::structDef => {
-                    let mut def = pair.into_inner();
-                    let struct_name = self.parse_ctx.insert_type(def.next().unwrap().as_str());
-                    let types: Vec<_> = def.map(|x| Type::parse(x, &mut self.parse_ctx)).collect();
-                    self.env.typ_data.insert((struct_name, None), types);
-                }
-                Rule::enumDef => {
-                    let mut def = pair.into_inner();
-                    let enum_name = self.parse_ctx.insert_type(def.next().unwrap().as_str());
-
-                    def.for_each(|variant| {
-                        let mut def = variant.into_inner();
-                        let variant_name = self.parse_ctx.insert_tag(def.next().unwrap().as_str());
-                        let types: Vec<_> =
-                            def.map(|x| Type::parse(x, &mut self.parse_ctx)).collect();
-                        self.env
-                            .typ_data
-                            .insert((enum_name, Some(variant_name)), types);
-                        match self.env.type_variants.entry(enum_name) {
-                            std::collections::hash_map::Entry::Occupied(mut vec) => {
-                                vec.get_mut().insert(variant_name);
-                            }
-                            std::collections::hash_map::Entry::Vacant(vacant) => {
-                                let mut set = HashSet::new();
-                                set.insert(variant_name);
-                                vacant.insert(set);
-                            }
-                        }
-                    });
-                }
-
-                Rule::EOI => (),
-                x => {
-                    dbg!(x);
-                    unreachable!();
-                }
-            }
+            self.parse_top_level(pair)?;
         }
         Ok(())
     }
@@ -116,6 +102,51 @@ impl VM {
         let pair = StackParser::parse(Rule::justExprOrDef, source)?
             .next()
             .unwrap();
+        self.parse_top_level(pair)
+    }
+
+    /// Parses editor input, either a single expression or definition without
+    /// a trailing `;` or a whole program. When neither parses, the error that
+    /// got further into `source` is the one reported.
+    pub fn parse_input(&mut self, source: &str) -> Result<(), Box<Error<Rule>>> {
+        let snippet_err = match self.parse_snippet(source) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let program_err = match self.parse_full_program(source) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        let reach = |err: &Error<Rule>| match err.location {
+            InputLocation::Pos(pos) => pos,
+            InputLocation::Span((start, _)) => start,
+        };
+        let err = if reach(&program_err) > reach(&snippet_err) {
+            program_err
+        } else {
+            snippet_err
+        };
+        Err(Box::new(clarify_error(*err, source)))
+    }
+
+    /// Parses `source` as [`VM::parse_input`] would without changing the VM.
+    pub fn check(&self, source: &str) -> Result<(), Box<Error<Rule>>> {
+        let mut scratch = VM {
+            stack: Default::default(),
+            var_map: Default::default(),
+            env: self.env.clone(),
+            exprs: vec![],
+            parse_ctx: self.parse_ctx.clone(),
+            modules: self.modules.clone(),
+            number_format: self.number_format,
+            int_format: self.int_format,
+            precision: self.precision,
+            rng: self.rng,
+        };
+        scratch.parse_input(source)
+    }
+
+    fn parse_top_level(&mut self, pair: Pair<'_, Rule>) -> Result<(), Box<Error<Rule>>> {
         match pair.as_rule() {
             Rule::expr => self.exprs.push(Stack {
                 elems: pair
@@ -123,32 +154,190 @@ impl VM {
                     .map(|x| Ast::parse(x, &mut self.parse_ctx))
                     .collect(),
             }),
+            Rule::def => {
+                let span = pair.as_span();
+                let mut def = pair.into_inner();
+                let name = def.next().unwrap().as_str();
+                self.check_not_unit(name, span)?;
+                let fun_name = self.parse_ctx.define_fun(name);
+                let expr = Stack {
+                    elems: def.map(|x| Ast::parse(x, &mut self.parse_ctx)).collect(),
+                };
+                self.env_mut().data.insert(fun_name, expr);
+            }
+            Rule::unitDef => {
+                let span = pair.as_span();
+                let mut def = pair.into_inner();
+                def.next();
+                let name = def.next().unwrap().as_str();
+                if BUILTIN.iter().any(|(x, ..)| *x == name) {
+                    return Err(parse_error(format!("{name} is a built-in unit"), span));
+                }
+                // A unit would take over calls of the same name.
+                if self.parse_ctx.fun_id(name).is_some_and(|id| {
+                    self.env.data.contains_key(&id) || self.env.protocol_arity.contains_key(&id)
+                }) {
+                    return Err(parse_error(format!("{name} is already defined"), span));
+                }
+                let expr = Stack {
+                    elems: def.map(|x| Ast::parse(x, &mut self.parse_ctx)).collect(),
+                };
+                let mut values = ValueStack::default();
+                let size = expr
+                    .eval(&mut values, &self.env, &mut self.var_map)
+                    .and_then(|_| match values.len() {
+                        1 => Unit::from_value(name, values.pop().unwrap()),
+                        _ => Err(EvalError::PrimitiveEvalErr),
+                    })
+                    .map_err(|err| {
+                        parse_error(
+                            format!(
+                                "the size of unit {name} should be one number or quantity: {}",
+                                err.get_repr(&self.parse_ctx)
+                            ),
+                            span,
+                        )
+                    })?;
+                let id = self.parse_ctx.insert_unit(name);
+                self.env_mut().units.insert(id, size);
+            }
+            Rule::moduleDef => {
+                let mut def = pair.into_inner();
+                def.next();
+                let name = def.next().unwrap().as_str().to_string();
+                self.define_module(&name, def)?;
+            }
+            Rule::importStmt => {
+                let span = pair.as_span();
+                let name = pair.into_inner().nth(1).unwrap().as_str();
+                self.load_module(name, span)?;
+                self.parse_ctx.import_module(name);
+            }
+            Rule::useStmt => {
+                let span = pair.as_span();
+                let qualified = pair.into_inner().nth(1).unwrap().as_str();
+                let (module, fun) = qualified.split_once("::").unwrap();
+                self.load_module(module, span)?;
+                if !self.parse_ctx.use_fun(module, fun) {
+                    return Err(parse_error(
+                        format!("module {module} has no definition named {fun}"),
+                        span,
+                    ));
+                }
+            }
+            Rule::interfaceDef => {
+                let mut def = pair.into_inner();
+                def.next();
+                let name = self.parse_ctx.define_type(def.next().unwrap().as_str());
+                let methods = def.map(|x| self.parse_ctx.lookup_fun(x.as_str())).collect();
+                self.env_mut().interfaces.insert(name, methods);
+            }
+            Rule::implDef => {
+                let span = pair.as_span();
+                let mut def = pair.into_inner();
+                def.next();
+                let interface_name = def.next().unwrap().as_str();
+                let interface = self.parse_ctx.lookup_type(interface_name);
+                def.next();
+                let typ = Type::parse(def.next().unwrap(), &mut self.parse_ctx);
+                let Some(methods) = self.env.interfaces.get(&interface).cloned() else {
+                    return Err(parse_error(
+                        format!("unknown interface {interface_name}"),
+                        span,
+                    ));
+                };
+                // The block is checked on its own before any of its overloads
+                // is registered, so a rejected impl leaves nothing behind.
+                let key = Env::interface_key(&typ);
+                let overloads: Vec<_> = def.collect();
+                let mut provided = HashSet::new();
+                for overload in overloads.iter() {
+                    let mut inner = overload.clone().into_inner();
+                    let name = inner.next().unwrap().as_str();
+                    let id = self.parse_ctx.lookup_fun(name);
+                    if !methods.contains(&id) {
+                        return Err(parse_error(
+                            format!("{name} is not a method of {interface_name}"),
+                            overload.as_span(),
+                        ));
+                    }
+                    let mut types = inner
+                        .next()
+                        .unwrap()
+                        .into_inner()
+                        .map(|x| Type::parse(x, &mut self.parse_ctx));
+                    if types.any(|x| Env::interface_key(&x) == key) {
+                        provided.insert(id);
+                    }
+                }
+                let missing: Vec<_> = self
+                    .env
+                    .missing_methods(interface, &typ)
+                    .into_iter()
+                    .filter(|x| !provided.contains(x))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(parse_error(
+                        format!(
+                            "{} doesn't implement {interface_name}, missing: {}",
+                            typ.get_repr(&self.parse_ctx),
+                            missing
+                                .iter()
+                                .map(|x| self.parse_ctx.lookup_call_name(*x))
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        ),
+                        span,
+                    ));
+                }
+                let saved = self.env.clone();
+                for overload in overloads {
+                    if let Err(err) = self.parse_top_level(overload) {
+                        self.env = saved;
+                        return Err(err);
+                    }
+                }
+                self.env_mut()
+                    .interface_impls
+                    .entry(interface)
+                    .or_default()
+                    .insert(Env::interface_key(&typ));
+            }
             Rule::protocol_def => {
+                let span = pair.as_span();
                 let mut def = pair.into_inner();
-                let fun_name = self.parse_ctx.insert_fun(def.next().unwrap().as_str());
+                let name = def.next().unwrap().as_str();
+                self.check_not_unit(name, span)?;
+                let fun_name = self.parse_ctx.define_fun(name);
                 let typs: Vec<Type> = def
                     .next()
                     .unwrap()
                     .into_inner()
                     .map(|x| Type::parse(x, &mut self.parse_ctx))
                     .collect();
-                dbg!(&typs);
-                dbg!(&def);
                 let expr = Stack {
                     elems: def.map(|x| Ast::parse(x, &mut self.parse_ctx)).collect(),
                 };
                 if let std::collections::hash_map::Entry::Vacant(e) =
-                    self.env.protocol_arity.entry(fun_name)
+                    self.env_mut().protocol_arity.entry(fun_name)
                 {
                     e.insert((typs.len(), None));
                     let mut map = HashMap::new();
                     map.insert(typs, (vec![], CallType::Stack(expr)));
-                    self.env.protocol_data.insert(fun_name, map);
+                    self.env_mut().protocol_data.insert(fun_name, map);
                 } else {
-                    if self.env.protocol_arity[&fun_name].0 != typs.len() {
-                        todo!("Error handling")
+                    let arity = self.env.protocol_arity[&fun_name].0;
+                    if arity != typs.len() {
+                        return Err(parse_error(
+                            format!(
+                                "{} takes {arity} arguments but this overload takes {}",
+                                self.parse_ctx.lookup_call_name(fun_name),
+                                typs.len()
+                            ),
+                            span,
+                        ));
                     }
-                    match self.env.protocol_data.get_mut(&fun_name) {
+                    match self.env_mut().protocol_data.get_mut(&fun_name) {
                         Some(r) => {
                             r.insert(typs, (vec![], CallType::Stack(expr)));
                         }
@@ -158,37 +347,28 @@ impl VM {
                     }
                 }
             }
-
-            Rule::def => {
-                let mut def = pair.into_inner();
-                let fun_name = self.parse_ctx.insert_fun(def.next().unwrap().as_str());
-                let expr = Stack {
-                    elems: def.map(|x| Ast::parse(x, &mut self.parse_ctx)).collect(),
-                };
-                self.env.data.insert(fun_name, expr);
-            }
             Rule::structDef => {
-                let mut def = pair.into_inner();
-                let struct_name = self.parse_ctx.insert_type(def.next().unwrap().as_str());
-                dbg!(&def);
-                let types: Vec<_> = def
-                    .map(|x| Type::parse(dbg!(x), &mut self.parse_ctx))
-                    .collect();
-                dbg!(&types);
-                self.env.typ_data.insert((struct_name, None), types);
+                let mut def = pair.into_inner().peekable();
+                let struct_name = self.parse_ctx.define_type(def.next().unwrap().as_str());
+                let params = self.parse_type_params(&mut def);
+                let types: Vec<_> = def.map(|x| Type::parse(x, &mut self.parse_ctx)).collect();
+                self.env_mut().typ_data.insert((struct_name, None), types);
+                self.env_mut().type_params.insert(struct_name, params);
             }
             Rule::enumDef => {
-                let mut def = pair.into_inner();
-                let enum_name = self.parse_ctx.insert_type(def.next().unwrap().as_str());
+                let mut def = pair.into_inner().peekable();
+                let enum_name = self.parse_ctx.define_type(def.next().unwrap().as_str());
+                let params = self.parse_type_params(&mut def);
+                self.env_mut().type_params.insert(enum_name, params);
 
                 def.for_each(|variant| {
                     let mut def = variant.into_inner();
                     let variant_name = self.parse_ctx.insert_tag(def.next().unwrap().as_str());
                     let types: Vec<_> = def.map(|x| Type::parse(x, &mut self.parse_ctx)).collect();
-                    self.env
+                    self.env_mut()
                         .typ_data
                         .insert((enum_name, Some(variant_name)), types);
-                    match self.env.type_variants.entry(enum_name) {
+                    match self.env_mut().type_variants.entry(enum_name) {
                         std::collections::hash_map::Entry::Occupied(mut vec) => {
                             vec.get_mut().insert(variant_name);
                         }
@@ -210,54 +390,345 @@ impl VM {
         Ok(())
     }
 
-    pub fn eval(&mut self) -> Result<(), EvalError> {
-        for expr in &self.exprs {
-            match expr.eval(&mut self.stack, &self.env, &mut self.var_map) {
-                Ok(_) => {}
-                Err(err) => {
-                    self.exprs.clear();
-                    return Err(err);
+    /// Defines every item under `module::`. Names are collected up front so
+    /// items can refer to each other regardless of order. Overloads of a
+    /// protocol that already exists outside the module, like `add`, extend it
+    /// instead.
+    fn define_module(
+        &mut self,
+        module: &str,
+        items: Pairs<'_, Rule>,
+    ) -> Result<(), Box<Error<Rule>>> {
+        let mut members = vec![];
+        let mut protocols = vec![];
+        let mut types = vec![];
+        for item in items.clone() {
+            let rule = item.as_rule();
+            let mut inner = item.into_inner();
+            match rule {
+                Rule::def => members.push(inner.next().unwrap().as_str()),
+                Rule::protocol_def => protocols.push(inner.next().unwrap().as_str()),
+                Rule::structDef | Rule::enumDef => types.push(inner.next().unwrap().as_str()),
+                Rule::interfaceDef => {
+                    inner.next();
+                    types.push(inner.next().unwrap().as_str());
+                    protocols.extend(inner.map(|x| x.as_str()));
                 }
-            };
+                Rule::implDef => protocols.extend(
+                    inner
+                        .filter(|x| x.as_rule() == Rule::protocol_def)
+                        .map(|x| x.into_inner().next().unwrap().as_str()),
+                ),
+                _ => (),
+            }
+        }
+        members.extend(protocols.into_iter().filter(|name| {
+            !self
+                .parse_ctx
+                .fun_id(name)
+                .is_some_and(|id| self.env.protocol_arity.contains_key(&id))
+        }));
+        self.parse_ctx.enter_module(module, &members, &types);
+        let ret = items
+            .into_iter()
+            .try_for_each(|item| self.parse_top_level(item));
+        self.parse_ctx.exit_module();
+        ret
+    }
+
+    fn load_module(&mut self, module: &str, span: Span<'_>) -> Result<(), Box<Error<Rule>>> {
+        if self.parse_ctx.has_module(module) {
+            return Ok(());
+        }
+        let Some(source) = self.modules.source(module) else {
+            return Err(parse_error(format!("unknown module {module}"), span));
+        };
+        let items = StackParser::parse(Rule::moduleFile, &source)
+            .map_err(|err| parse_error(format!("in module {module}:\n{err}"), span))?;
+        self.define_module(module, items)
+            .map_err(|err| parse_error(format!("in module {module}:\n{err}"), span))
+    }
+
+    /// Refuses definitions named like a unit, which could never be called as
+    /// the unit is looked up first.
+    fn check_not_unit(&self, name: &str, span: Span<'_>) -> Result<(), Box<Error<Rule>>> {
+        match self.parse_ctx.lookup_unit(name) {
+            Some(_) => Err(parse_error(format!("{name} is a unit"), span)),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_type_params(&mut self, def: &mut Peekable<Pairs<'_, Rule>>) -> Vec<usize> {
+        match def.next_if(|x| x.as_rule() == Rule::typeParams) {
+            Some(params) => params
+                .into_inner()
+                .map(|x| self.parse_ctx.insert_type(x.as_str()))
+                .collect(),
+            None => vec![],
         }
+    }
+
+    pub fn eval(&mut self) -> Result<(), EvalError> {
+        self.env.precision.set(self.precision);
+        self.env.rng.set(self.rng);
+        let result = self.eval_exprs();
         self.exprs.clear();
+        let number_format = self.env.requested_format.take();
+        let int_format = self.env.requested_int_format.take();
+        // A failed program leaves the settings as they were.
+        result?;
+        self.precision = self.env.precision.get();
+        self.rng = self.env.rng.get();
+        if let Some(format) = number_format {
+            self.number_format = format;
+        }
+        if let Some(format) = int_format {
+            self.int_format = format;
+        }
         Ok(())
     }
 
-    pub fn get_definitons(&self) -> Vec<(String, Vec<String>)> {
-        let mut ret = vec![];
-        for (x, y) in self.env.data.iter() {
-            ret.push((
-                self.parse_ctx.lookup_call_name(*x),
-                y.elems
-                    .iter()
-                    .map(|x| x.get_repr(&self.parse_ctx))
-                    .collect(),
-            ))
+    fn eval_exprs(&mut self) -> Result<(), EvalError> {
+        for expr in &self.exprs {
+            expr.eval(&mut self.stack, &self.env, &mut self.var_map)?;
+        }
+        Ok(())
+    }
+
+    /// `value` as shown on the stack, in the session's number formats.
+    pub fn display(&self, value: &Values) -> String {
+        value.display(&self.parse_ctx, self.number_format, self.int_format)
+    }
+
+    /// Parses and evaluates `source` on an empty stack, expecting exactly one
+    /// value. The value stack is left as it was.
+    pub fn parse_value(&mut self, source: &str) -> Result<Values, String> {
+        let pairs = StackParser::parse(Rule::justExpr, source).map_err(|err| err.to_string())?;
+        // Evaluated in a copy so neither the stack nor the settings change.
+        // Only the names it interned are kept, the value refers to them.
+        let stack = std::mem::take(&mut self.stack);
+        let mut scratch = self.clone();
+        self.stack = stack;
+        for pair in pairs {
+            scratch
+                .parse_top_level(pair)
+                .map_err(|err| err.to_string())?;
         }
+        let result = scratch.eval();
+        self.parse_ctx = scratch.parse_ctx;
+        result.map_err(|err| err.get_repr(&self.parse_ctx))?;
+        match scratch.stack.len() {
+            1 => Ok(scratch.stack.pop().unwrap()),
+            n => Err(format!("expected exactly one value but got {n}")),
+        }
+    }
+
+    pub fn save_session(&self, history: &[Arc<String>]) -> Session {
+        let mut modules: Vec<_> = self
+            .modules
+            .registry
+            .iter()
+            .map(|(name, source)| (name.clone(), source.clone()))
+            .collect();
+        modules.sort();
+        Session {
+            modules,
+            definitions: env_items(&self.env, &self.parse_ctx)
+                .into_iter()
+                .map(Arc::new)
+                .collect(),
+            stack: self
+                .stack
+                .iter()
+                .map(|x| x.get_repr(&self.parse_ctx))
+                .collect(),
+            history: history.to_vec(),
+            number_format: Some(self.number_format),
+            int_format: Some(self.int_format),
+            precision: Some(self.precision),
+            seed: Some(self.rng),
+        }
+    }
+
+    /// Merges a saved session into this one: modules are registered,
+    /// definitions re-parsed and the saved stack pushed on top of the current
+    /// one. Nothing changes if any part fails.
+    pub fn append_session(&mut self, session: &Session) -> Result<(), String> {
+        let mut next = self.clone();
+        for (name, source) in session.modules.iter() {
+            next.modules.registry.insert(name.clone(), source.clone());
+        }
+        if let Some(format) = session.number_format {
+            next.number_format = format;
+        }
+        if let Some(format) = session.int_format {
+            next.int_format = format;
+        }
+        if let Some(precision) = session.precision {
+            next.precision = precision;
+        }
+        if let Some(seed) = session.seed {
+            next.rng = seed;
+        }
+        for source in session.definitions.iter() {
+            next.parse_snippet(source)
+                .map_err(|err| format!("in definition:\n{err}"))?;
+        }
+        for value in session.stack.iter() {
+            next.parse_snippet(value)
+                .map_err(|err| format!("in stack value:\n{err}"))?;
+            next.eval().map_err(|err| {
+                format!("in stack value {value}: {}", err.get_repr(&next.parse_ctx))
+            })?;
+        }
+        *self = next;
+        Ok(())
+    }
+
+    pub fn open_session(session: &Session) -> Result<Self, String> {
+        let mut ret = Self::default();
+        ret.append_session(session)?;
+        Ok(ret)
+    }
+
+    /// Definitions sorted by name, as (name, body items, editable source).
+    pub fn get_definitons(&self) -> Vec<(String, Vec<String>, String)> {
+        let mut ret: Vec<_> = self
+            .env
+            .data
+            .iter()
+            .map(|(id, body)| {
+                (
+                    self.parse_ctx.lookup_call_name(*id),
+                    self.body_items(body),
+                    pretty(definition_item(&self.env, &self.parse_ctx, *id).unwrap_or_default()),
+                )
+            })
+            .collect();
+        ret.sort();
         ret
     }
 
-    pub fn get_structs(&self) -> Vec<(String, Vec<String>)> {
+    /// Definitions sorted by name with their body items, like
+    /// `get_definitons` without formatting the editable source.
+    pub fn get_definition_bodies(&self) -> Vec<(String, Vec<String>)> {
+        let mut ret: Vec<_> = self
+            .env
+            .data
+            .iter()
+            .map(|(id, body)| (self.parse_ctx.lookup_call_name(*id), self.body_items(body)))
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    fn body_items(&self, body: &Stack) -> Vec<String> {
+        body.elems
+            .iter()
+            .map(|x| x.get_repr(&self.parse_ctx))
+            .collect()
+    }
+
+    fn call_id(&self, name: &str) -> Option<usize> {
+        self.env
+            .data
+            .keys()
+            .chain(self.env.protocol_data.keys())
+            .find(|id| self.parse_ctx.lookup_call_name(**id) == name)
+            .copied()
+    }
+
+    /// Removes the definition `name`. Anything still calling it fails with an
+    /// unknown call from then on.
+    pub fn delete_definition(&mut self, name: &str) -> bool {
+        match self.call_id(name) {
+            Some(id) if self.env.data.contains_key(&id) => {
+                self.env_mut().data.remove(&id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Definitions and protocol overloads whose body calls `name`, sorted.
+    pub fn where_used(&self, name: &str) -> Vec<String> {
+        let Some(id) = self.call_id(name) else {
+            return vec![];
+        };
+        let calls_it = |body: &Stack| {
+            let mut calls = HashSet::new();
+            body.get_calls(&mut calls);
+            calls.contains(&id)
+        };
+        let mut ret: Vec<_> = self
+            .env
+            .data
+            .iter()
+            .filter(|(_, body)| calls_it(body))
+            .map(|(caller, _)| self.parse_ctx.lookup_call_name(*caller))
+            .collect();
+        for (caller, impls) in self.env.protocol_data.iter() {
+            for (types, (_, call)) in impls.iter() {
+                if let CallType::Stack(body) = call
+                    && calls_it(body)
+                {
+                    ret.push(format!(
+                        "{}({})",
+                        self.parse_ctx.lookup_call_name(*caller),
+                        types
+                            .iter()
+                            .map(|x| x.get_repr(&self.parse_ctx))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ));
+                }
+            }
+        }
+        ret.sort();
+        ret
+    }
+
+    fn type_display_name(&self, type_name: usize) -> String {
+        let name = self.parse_ctx.lookup_type_name(type_name);
+        match self.env.type_params.get(&type_name) {
+            Some(params) if !params.is_empty() => format!(
+                "{name}<{}>",
+                params
+                    .iter()
+                    .map(|x| self.parse_ctx.lookup_type_name(*x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => name,
+        }
+    }
+
+    /// Structs sorted by name, as (name, fields, editable source).
+    pub fn get_structs(&self) -> Vec<(String, Vec<String>, String)> {
         let mut ret = vec![];
 
         for ((type_name, maybe_variant), y) in self.env.typ_data.iter() {
-            let name = self.parse_ctx.lookup_type_name(*type_name);
+            let name = self.type_display_name(*type_name);
             match maybe_variant {
                 Some(_variant) => {}
                 None => {
                     let def = y.iter().map(|x| x.get_repr(&self.parse_ctx)).collect();
-                    ret.push((name, def));
+                    let source = pretty(
+                        type_item(&self.env, &self.parse_ctx, *type_name).unwrap_or_default(),
+                    );
+                    ret.push((name, def, source));
                 }
             }
         }
+        ret.sort();
         ret
     }
 
-    pub fn get_enums(&self) -> Vec<(String, Vec<(String, Vec<String>)>)> {
-        let mut map: HashMap<String, Vec<(String, Vec<String>)>> = HashMap::new();
+    /// Enums sorted by name, as (name, variants, editable source).
+    pub fn get_enums(&self) -> Vec<(String, Variants, String)> {
+        let mut map: HashMap<String, (Variants, usize)> = HashMap::new();
         for ((type_name, maybe_variant), y) in self.env.typ_data.iter() {
-            let name = self.parse_ctx.lookup_type_name(*type_name);
+            let name = self.type_display_name(*type_name);
             let def = y.iter().map(|x| x.get_repr(&self.parse_ctx)).collect();
 
             match maybe_variant {
@@ -266,44 +737,228 @@ impl VM {
                     let value = (variant_name, def);
                     match map.entry(name) {
                         std::collections::hash_map::Entry::Occupied(mut occ) => {
-                            occ.get_mut().push(value);
+                            occ.get_mut().0.push(value);
                         }
                         std::collections::hash_map::Entry::Vacant(vac) => {
-                            vac.insert(vec![value]);
+                            vac.insert((vec![value], *type_name));
                         }
                     }
                 }
                 None => (),
             }
         }
-        map.into_iter().collect()
+        let mut ret: Vec<_> = map
+            .into_iter()
+            .map(|(name, (mut variants, id))| {
+                variants.sort();
+                let source = pretty(type_item(&self.env, &self.parse_ctx, id).unwrap_or_default());
+                (name, variants, source)
+            })
+            .collect();
+        ret.sort();
+        ret
     }
 
-    pub fn get_protocols(&self) -> Vec<(String, Vec<String>)> {
-        self.env
-            .protocol_data
+    pub fn get_modules(&self) -> Vec<(String, Vec<String>)> {
+        let mut ret = self.parse_ctx.get_modules();
+        for name in self.modules.registry.keys() {
+            if !self.parse_ctx.has_module(name) {
+                ret.push((name.to_string(), vec![]));
+            }
+        }
+        ret
+    }
+
+    pub fn get_interfaces(&self) -> Vec<(String, Vec<String>, Vec<String>)> {
+        let mut ret: Vec<_> = self
+            .env
+            .interfaces
             .iter()
-            .map(|(fun_id, implement)| {
+            .map(|(name, methods)| {
                 (
-                    self.parse_ctx.lookup_call_name(*fun_id),
-                    implement
+                    self.parse_ctx.lookup_type_name(*name),
+                    methods
                         .iter()
-                        .map(|(types, (return_types, _call_type))| {
-                            let ins = types
-                                .iter()
-                                .map(|t| t.get_repr(&self.parse_ctx))
-                                .collect::<Vec<_>>()
-                                .join(" ");
-                            let outs = return_types
-                                .iter()
-                                .map(|t| t.get_repr(&self.parse_ctx))
-                                .collect::<Vec<_>>()
-                                .join(" ");
-                            format!("{ins} -> {outs}")
+                        .map(|x| self.parse_ctx.lookup_call_name(*x))
+                        .collect(),
+                    self.env
+                        .interface_impls
+                        .get(name)
+                        .map(|impls| {
+                            let mut impls: Vec<_> =
+                                impls.iter().map(|x| x.get_repr(&self.parse_ctx)).collect();
+                            impls.sort();
+                            impls
                         })
-                        .collect::<Vec<_>>(),
+                        .unwrap_or_default(),
                 )
             })
-            .collect::<Vec<_>>()
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    /// Protocols sorted by name, with one (signature, editable source) per
+    /// overload. Built-in overloads have no source.
+    pub fn get_protocols(&self) -> Vec<(String, Overloads)> {
+        let mut ret: Vec<_> = self
+            .env
+            .protocol_data
+            .iter()
+            .map(|(fun_id, implement)| {
+                let mut overloads: Vec<_> = implement
+                    .iter()
+                    .map(|(types, (return_types, _call_type))| {
+                        (
+                            self.signature(types, return_types),
+                            overload_item(&self.env, &self.parse_ctx, *fun_id, types).map(pretty),
+                        )
+                    })
+                    .collect();
+                overloads.sort();
+                (self.parse_ctx.lookup_call_name(*fun_id), overloads)
+            })
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    /// Protocols sorted by name with the signature of each overload, like
+    /// `get_protocols` without formatting the editable source.
+    pub fn get_protocol_signatures(&self) -> Vec<(String, Vec<String>)> {
+        let mut ret: Vec<_> = self
+            .env
+            .protocol_data
+            .iter()
+            .map(|(fun_id, implement)| {
+                let mut signatures: Vec<_> = implement
+                    .iter()
+                    .map(|(types, (return_types, _))| self.signature(types, return_types))
+                    .collect();
+                signatures.sort();
+                (self.parse_ctx.lookup_call_name(*fun_id), signatures)
+            })
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    /// E.g. `Int Int -> Int`.
+    fn signature(&self, types: &[Type], return_types: &[Type]) -> String {
+        let repr = |types: &[Type]| {
+            types
+                .iter()
+                .map(|t| t.get_repr(&self.parse_ctx))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        format!("{} -> {}", repr(types), repr(return_types))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(vm: &mut VM, source: &str) -> Vec<String> {
+        vm.parse_full_program(source).unwrap();
+        vm.eval().unwrap();
+        let values = std::mem::take(&mut vm.stack);
+        values.iter().map(|x| vm.display(x)).collect()
+    }
+
+    #[test]
+    fn module_collision() {
+        let mut vm = VM::default();
+        let source = "
+            module a { struct Point { Int }; area(Point) = | Point(x) => x x mul, ; };
+            module b { struct Point { Int Int }; area(Point) = | Point(x y) => x y mul, ; };
+            a::Point(3) a::area b::Point(2 5) b::area;
+        ";
+        assert_eq!(run(&mut vm, source), ["9", "10"]);
+        assert_eq!(run(&mut vm, "import b; Point(3 4) area;"), ["12"]);
+        assert_eq!(run(&mut vm, "a::Point(2);"), ["a::Point(2)"]);
+        vm.parse_full_program("b::Point(1 2) a::area;").unwrap();
+        assert!(vm.eval().is_err());
+    }
+
+    #[test]
+    fn rejected_impl() {
+        let mut vm = VM::default();
+        vm.parse_full_program("interface Shape { area perimeter }; struct Sq { Int };")
+            .unwrap();
+        for source in [
+            "impl Shape for Sq { area(Sq) = | Sq(x) => x x mul, ; };",
+            "impl Shape for Sq { area(Int) = 1; perimeter(Sq) = 4; };",
+            "impl Shape for Sq { area(Sq) = 1; perimeter(Sq) = 4; volume(Sq) = 1; };",
+        ] {
+            assert!(vm.parse_full_program(source).is_err(), "{source}");
+            for name in ["area", "perimeter", "volume"] {
+                let id = vm.parse_ctx.fun_id(name);
+                assert!(id.is_none_or(|x| !vm.env.protocol_data.contains_key(&x)));
+            }
+            assert!(vm.env.interface_impls.is_empty());
+        }
+        let source = "impl Shape for Sq { area(Sq) = | Sq(x) => x x mul, ; perimeter(Sq) = 4; };";
+        assert_eq!(run(&mut vm, &format!("{source} Sq(3) area;")), ["9"]);
+    }
+
+    #[test]
+    fn int_literals() {
+        let mut vm = VM::default();
+        for source in ["0x;", "0xff_;", "1__0;", "1_;", "0b12;", "1/2_;"] {
+            assert!(vm.parse_full_program(source).is_err(), "{source}");
+        }
+        let source = "1_000 0xff 0o17 0b1_0 0 -0x1;";
+        assert_eq!(run(&mut vm, source), ["1000", "255", "15", "2", "0", "-1"]);
+    }
+
+    #[test]
+    fn int_display_words() {
+        let mut vm = VM::default();
+        let source = "IntFormat::Octal() int_format; 255 hex 9 List(2 bin);";
+        assert_eq!(
+            run(&mut vm, source),
+            ["Hex(0xff)", "0o11", "List(Bin(0b10))"]
+        );
+        let source = "Hex(0xff) | Hex(x) => x 1 add, ;";
+        assert_eq!(run(&mut vm, source), ["0o400"]);
+    }
+
+    #[test]
+    fn unit_names() {
+        let mut vm = VM::default();
+        let source = "twice = |f| {f apply f apply}; unit furlong = 201.168 m;";
+        vm.parse_full_program(source).unwrap();
+        // A unit named like a definition or protocol.
+        for source in ["unit add = 3;", "unit dup = 2;", "unit twice = 2;"] {
+            assert!(vm.parse_full_program(source).is_err(), "{source}");
+        }
+        // A definition or overload named like a unit.
+        for source in ["s = 5;", "m(Int) = 1;", "furlong = 1;", "furlong(Int) = 1;"] {
+            assert!(vm.parse_full_program(source).is_err(), "{source}");
+        }
+        let source = "3 [1 add] twice 1 furlong to m;";
+        assert_eq!(run(&mut vm, source), ["5", "25146/125 m"]);
+    }
+
+    #[test]
+    fn settings_after_failure() {
+        let mut vm = VM::default();
+        let source = "50 precision 7 seed IntFormat::Hex() int_format 1 0 div;";
+        vm.parse_full_program(source).unwrap();
+        assert!(vm.eval().is_err());
+        assert_eq!(vm.precision, DEFAULT_PRECISION);
+        assert_eq!(vm.rng, random::DEFAULT_SEED);
+        assert_eq!(vm.int_format, IntFormat::Decimal);
+        assert_eq!(run(&mut vm, "255;"), ["255"]);
+
+        vm.parse_full_program("1 2;").unwrap();
+        vm.eval().unwrap();
+        let value = vm.parse_value("IntFormat::Hex() int_format 7 seed 255");
+        assert_eq!(value, Ok(Values::Int(255.into())));
+        assert_eq!(vm.int_format, IntFormat::Decimal);
+        assert_eq!(vm.rng, random::DEFAULT_SEED);
+        assert_eq!(run(&mut vm, ""), ["1", "2"]);
     }
 }
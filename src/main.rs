@@ -1,20 +1,102 @@
 use crate::language::repr::Representation;
 use dioxus::prelude::*;
+use language::eval::Values;
+use language::format::format_source;
+use language::highlight::{Token, highlight};
+use language::math::MAX_PRECISION;
+use language::number::{IntFormat, MAX_PLACES, NumberFormat};
+use language::parse::Rule;
+use language::session::Session;
 use language::vm::VM;
+use pest::error::{InputLocation, LineColLocation};
+use std::ops::Range;
+use std::rc::Rc;
 use std::sync::Arc;
+use undo::Undo;
 
+mod complete;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+mod history;
 mod language;
+mod storage;
+mod undo;
 
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut args: Vec<String> = std::env::args().skip(1).collect();
+        if args.first().is_some_and(|x| x == "--fmt") {
+            std::process::exit(headless::format(&args.split_off(1)));
+        }
+        if !args.is_empty() {
+            std::process::exit(headless::run(&args));
+        }
+    }
     launch(App);
 }
 
+const SESSION_FILE: &str = "session.stks";
+
+#[cfg(target_arch = "wasm32")]
+fn save_session_file(text: String) -> Result<(), String> {
+    let download = document::eval(&format!(
+        r#"
+        const text = await dioxus.recv();
+        const link = document.createElement("a");
+        link.href = URL.createObjectURL(new Blob([text], {{ type: "text/plain" }}));
+        link.download = "{SESSION_FILE}";
+        link.click();
+        URL.revokeObjectURL(link.href);
+        "#
+    ));
+    download
+        .send(text)
+        .map_err(|err| format!("couldn't download the session: {err}"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_session_file(text: String) -> Result<(), String> {
+    std::fs::write(SESSION_FILE, text)
+        .map_err(|err| format!("couldn't write {SESSION_FILE}: {err}"))
+}
+
+async fn read_session_file(evt: FormEvent) -> Result<Session, String> {
+    let files = evt.files().ok_or("no file selected")?;
+    let name = files.files().into_iter().next().ok_or("no file selected")?;
+    let text = files
+        .read_file_to_string(&name)
+        .await
+        .ok_or_else(|| format!("couldn't read {name}"))?;
+    Session::from_text(&text).map_err(|err| format!("{name}: {err}"))
+}
+
+const UNDO_LIMIT: usize = 100;
+
+/// Applies a direct edit of the value stack and records it as an undo step.
+/// Entries are moved around as they are, so an edit doesn't copy values.
+fn edit_stack(mut vm: Signal<VM>, mut undo: Signal<Undo>, f: impl FnOnce(&mut Vec<Rc<Values>>)) {
+    vm.with_mut(|vm| f(vm.stack.entries_mut()));
+    undo.with_mut(|u| u.record(&vm.read()));
+}
+
+/// Where the entry shown in the row keyed `key` is now, if it's still there.
+fn index_of(stack: &[Rc<Values>], key: usize) -> Option<usize> {
+    stack.iter().position(|x| Rc::as_ptr(x) as usize == key)
+}
+
+fn copy_to_clipboard(text: String) {
+    let js = document::eval("navigator.clipboard.writeText(await dioxus.recv());");
+    let _ = js.send(text);
+}
+
 #[derive(Clone, Copy, PartialEq)]
 enum SidebarTab {
     Protocols,
     Definitions,
     Structs,
     Enums,
+    Modules,
     Examples,
     Tutorial,
 }
@@ -25,81 +107,252 @@ fn App() -> Element {
     let mut err = use_signal(String::new);
     let mut vm = use_signal(VM::default);
     let mut history = use_signal(Vec::<Arc<String>>::new);
-    let mut history_idx = use_signal(|| None::<usize>);
+    let mut history_open = use_signal(|| false);
+    let mut restored = use_signal(|| false);
+    let mut undo = use_signal(|| Undo::new(&vm.read(), UNDO_LIMIT));
     let mut has_err = use_signal(|| false);
     let mut sidebar_open = use_signal(|| false);
     let mut sidebar_pinned = use_signal(|| false);
     let mut active_tab = use_signal(|| SidebarTab::Examples);
+    // Caret as a byte offset into `content`, for completions.
+    let mut cursor = use_signal(|| 0usize);
+    let mut completion_idx = use_signal(|| 0usize);
+    let mut completion_closed = use_signal(|| false);
+    // Parse error of the editor contents, re-checked as they change.
+    let live_err = use_memo(move || {
+        let text = content();
+        if text.trim().is_empty() {
+            return None;
+        }
+        vm.read().check(&text).err().map(|err| *err)
+    });
 
     let mut eval = move |_| {
         if *has_err.read() {
             err.set(String::new());
         }
         has_err.set(false);
-        history_idx.set(None);
 
         let prog = content.read().clone();
 
         vm.with_mut(|vm| {
-            let parse_snippet_result = vm.parse_snippet(&prog);
-            let parse_program_result = vm.parse_full_program(&prog);
-
-            match (parse_snippet_result, parse_program_result) {
-                (Ok(_), _) | (_, Ok(_)) => {
-                    let old_stack = vm.stack.clone();
-                    match vm.eval() {
-                        Ok(_) => {
-                            history.with_mut(|h| h.push(Arc::new(prog)));
-                            content.set(String::new());
-                        }
-                        Err(error) => {
-                            has_err.set(true);
-                            err.set(error.get_repr(&vm.parse_ctx));
-                            vm.stack = old_stack;
-                        }
+            // A failed run leaves the stack and definitions as they were.
+            match vm.parse_input(&prog) {
+                Ok(()) => match vm.eval() {
+                    Ok(_) => {
+                        undo.with_mut(|u| u.record(vm));
+                        history.with_mut(|h| history::push(h, Arc::new(prog)));
+                        content.set(String::new());
                     }
-                }
-                (Err(err1), Err(err2)) => {
+                    Err(error) => {
+                        has_err.set(true);
+                        err.set(error.get_repr(&vm.parse_ctx));
+                        undo.read().restore(vm);
+                    }
+                },
+                Err(parse_err) => {
                     has_err.set(true);
-                    err.set(format!("{}\n--- OR ---\n{}", err1, err2));
+                    err.set(parse_err.to_string());
+                    undo.read().restore(vm);
                 }
             }
         });
     };
 
-    let mut prev = move |_| {
-        let hist = history.read();
-        if hist.is_empty() {
-            return;
+    use_hook(move || {
+        spawn(async move {
+            if let Some(text) = storage::load(storage::SESSION_KEY).await {
+                let restore = Session::from_text(&text)
+                    .and_then(|session| VM::open_session(&session).map(|vm| (vm, session)));
+                match restore {
+                    Ok((new_vm, session)) => {
+                        undo.set(Undo::new(&new_vm, UNDO_LIMIT));
+                        vm.set(new_vm);
+                        history.set(session.history);
+                    }
+                    Err(error) => {
+                        has_err.set(true);
+                        err.set(format!("couldn't restore the last session: {error}"));
+                    }
+                }
+            }
+            if let Some(text) = storage::load(storage::EDITOR_KEY).await {
+                content.set(text);
+            }
+            restored.set(true);
+        });
+    });
+
+    // Nothing is written back until the stored state has been restored,
+    // otherwise the first render would overwrite it with an empty session.
+    use_effect(move || {
+        if restored() {
+            let text = vm.read().save_session(&history.read()).to_text();
+            storage::save(storage::SESSION_KEY, text);
+        }
+    });
+    use_effect(move || {
+        if restored() {
+            storage::save(storage::EDITOR_KEY, content());
         }
-        let idx = match *history_idx.read() {
-            Some(i) if i == 0 => hist.len() - 1,
-            Some(i) => i - 1,
-            None => hist.len() - 1,
+    });
+
+    let save = move |_| {
+        let text = vm.read().save_session(&history.read()).to_text();
+        if let Err(error) = save_session_file(text) {
+            has_err.set(true);
+            err.set(error);
+        }
+    };
+
+    let load = move |evt: FormEvent, append: bool| async move {
+        let result = match read_session_file(evt).await {
+            Ok(session) if append => vm.with_mut(|vm| vm.append_session(&session)).map(|_| {
+                undo.with_mut(|u| u.record(&vm.read()));
+                session
+            }),
+            Ok(session) => VM::open_session(&session).map(|new_vm| {
+                undo.set(Undo::new(&new_vm, UNDO_LIMIT));
+                vm.set(new_vm);
+                history.set(vec![]);
+                session
+            }),
+            Err(error) => Err(error),
         };
-        history_idx.set(Some(idx));
-        content.set(hist[idx].to_string());
+        match result {
+            Ok(session) => {
+                history.with_mut(|h| {
+                    session
+                        .history
+                        .into_iter()
+                        .for_each(|x| history::push(h, x))
+                });
+                has_err.set(false);
+                err.set(String::new());
+            }
+            Err(error) => {
+                has_err.set(true);
+                err.set(error);
+            }
+        }
     };
 
-    let mut next = move |_| {
-        let hist = history.read();
-        if hist.is_empty() {
-            return;
+    let mut step_back = move |_| {
+        vm.with_mut(|vm| undo.with_mut(|u| u.undo(vm)));
+    };
+    let mut step_forward = move |_| {
+        vm.with_mut(|vm| undo.with_mut(|u| u.redo(vm)));
+    };
+
+    // Alt so the editor keeps Ctrl+Z for its own text undo.
+    let undo_keys = move |evt: KeyboardEvent| {
+        if evt.modifiers().alt() {
+            match evt.key() {
+                Key::Character(c) if c.eq_ignore_ascii_case("z") && evt.modifiers().shift() => {
+                    evt.prevent_default();
+                    step_forward(());
+                }
+                Key::Character(c) if c.eq_ignore_ascii_case("z") => {
+                    evt.prevent_default();
+                    step_back(());
+                }
+                Key::Character(c) if c.eq_ignore_ascii_case("y") => {
+                    evt.prevent_default();
+                    step_forward(());
+                }
+                _ => {}
+            }
+        }
+    };
+
+    let track_cursor = move || {
+        spawn(async move {
+            let mut js = document::eval(
+                "dioxus.send(document.getElementById('editor')?.selectionStart ?? null);",
+            );
+            let text = content.read().clone();
+            let offset = match js.recv::<Option<usize>>().await {
+                Ok(Some(utf16)) => complete::byte_offset(&text, utf16),
+                _ => text.len(),
+            };
+            cursor.set(offset);
+        });
+    };
+
+    let mut accept_completion = move |label: String| {
+        let text = content.read().clone();
+        let (start, word) = complete::word_at(&text, cursor());
+        let end = start + word.len();
+        let new_text = format!("{}{label}{}", &text[..start], &text[end..]);
+        let caret = start + label.len();
+        let caret_utf16 = new_text[..caret].encode_utf16().count();
+        content.set(new_text);
+        cursor.set(caret);
+        completion_closed.set(true);
+        document::eval(&format!(
+            r#"
+            await new Promise(r => setTimeout(r));
+            const el = document.getElementById('editor');
+            if (el) {{ el.focus(); el.setSelectionRange({caret_utf16}, {caret_utf16}); }}
+            "#
+        ));
+    };
+
+    let mut format_editor = move || {
+        let formatted = format_source(&content.read());
+        match formatted {
+            Ok(text) => content.set(text),
+            Err(parse_err) => {
+                has_err.set(true);
+                err.set(parse_err.to_string());
+            }
         }
-        let idx = match *history_idx.read() {
-            Some(i) => (i + 1) % hist.len(),
-            None => 0,
-        };
-        history_idx.set(Some(idx));
-        content.set(hist[idx].to_string());
     };
 
     let keydown = move |evt: KeyboardEvent| {
+        if evt.modifiers().alt()
+            && evt.modifiers().shift()
+            && matches!(evt.key(), Key::Character(ref c) if c.eq_ignore_ascii_case("f"))
+        {
+            evt.prevent_default();
+            format_editor();
+            return;
+        }
+        let open_completions = if completion_closed() {
+            vec![]
+        } else {
+            complete::suggestions(&vm.read(), &content.read(), cursor())
+        };
+        if !open_completions.is_empty() {
+            let selected = completion_idx().min(open_completions.len() - 1);
+            match evt.key() {
+                Key::Tab => {
+                    evt.prevent_default();
+                    accept_completion(open_completions[selected].label.clone());
+                    return;
+                }
+                Key::ArrowDown if evt.modifiers().alt() => {
+                    evt.prevent_default();
+                    completion_idx.set((selected + 1) % open_completions.len());
+                    return;
+                }
+                Key::ArrowUp if evt.modifiers().alt() => {
+                    evt.prevent_default();
+                    completion_idx
+                        .set((selected + open_completions.len() - 1) % open_completions.len());
+                    return;
+                }
+                Key::Escape => {
+                    completion_closed.set(true);
+                    return;
+                }
+                _ => {}
+            }
+        }
         if evt.modifiers().ctrl() {
             match evt.key() {
                 Key::Enter => eval(()),
-                Key::ArrowUp => prev(()),
-                Key::ArrowDown => next(()),
+                Key::ArrowUp => history_open.set(true),
                 _ => {}
             }
         } else if evt.key() == Key::F4 {
@@ -128,6 +381,7 @@ fn App() -> Element {
                 display: flex;
                 overflow: hidden;
             ",
+            onkeydown: undo_keys,
 
             // Sidebar
             div {
@@ -192,6 +446,7 @@ fn App() -> Element {
                         (SidebarTab::Definitions, "Defs"),
                         (SidebarTab::Structs, "Structs"),
                         (SidebarTab::Enums, "Enums"),
+                        (SidebarTab::Modules, "Modules"),
                     ] {
                         button {
                             key: "{name}",
@@ -222,10 +477,11 @@ fn App() -> Element {
                     match *active_tab.read() {
                         SidebarTab::Tutorial => rsx! { Tutorial {} },
                         SidebarTab::Examples => rsx! { Examples { content } },
-                        SidebarTab::Protocols => rsx! { Protocols { vm } },
-                        SidebarTab::Definitions => rsx! { Definitions { vm } },
-                        SidebarTab::Structs => rsx! { Structs { vm } },
-                        SidebarTab::Enums => rsx! { Enums { vm } },
+                        SidebarTab::Protocols => rsx! { Protocols { vm, content } },
+                        SidebarTab::Definitions => rsx! { Definitions { vm, undo, content } },
+                        SidebarTab::Structs => rsx! { Structs { vm, content } },
+                        SidebarTab::Enums => rsx! { Enums { vm, content } },
+                        SidebarTab::Modules => rsx! { Modules { vm } },
                     }
                 }
             }
@@ -303,6 +559,36 @@ fn App() -> Element {
                             "▶ Run"
                         }
 
+                        button {
+                            style: format!("
+                                padding: 0.5rem;
+                                background: {};
+                                border: 1px solid #30363d;
+                                border-radius: 6px;
+                                color: #f0f6fc;
+                                font-size: 0.75rem;
+                                cursor: pointer;
+                            ", if history_open() { "#30363d" } else { "#21262d" }),
+                            title: "Search history (Ctrl+Up)",
+                            onclick: move |_| history_open.toggle(),
+                            "History"
+                        }
+
+                        button {
+                            style: "
+                                padding: 0.5rem;
+                                background: #21262d;
+                                border: 1px solid #30363d;
+                                border-radius: 6px;
+                                color: #f0f6fc;
+                                font-size: 0.75rem;
+                                cursor: pointer;
+                            ",
+                            title: "Format the editor contents (Alt+Shift+F)",
+                            onclick: move |_| format_editor(),
+                            "Format"
+                        }
+
                         div {
                             style: "display: flex; gap: 0.25rem;",
                             button {
@@ -314,8 +600,10 @@ fn App() -> Element {
                                     color: #f0f6fc;
                                     cursor: pointer;
                                 ",
-                                onclick: move |_| prev(()),
-                                "↑"
+                                disabled: !undo.read().can_undo(),
+                                title: "Undo (Alt+Z)",
+                                onclick: move |_| step_back(()),
+                                "↶"
                             }
                             button {
                                 style: "
@@ -326,35 +614,144 @@ fn App() -> Element {
                                     color: #f0f6fc;
                                     cursor: pointer;
                                 ",
-                                onclick: move |_| next(()),
-                                "↓"
+                                disabled: !undo.read().can_redo(),
+                                title: "Redo (Alt+Shift+Z)",
+                                onclick: move |_| step_forward(()),
+                                "↷"
+                            }
+                        }
+
+                        div {
+                            style: "display: flex; gap: 0.25rem;",
+                            button {
+                                style: "
+                                    padding: 0.5rem;
+                                    background: #21262d;
+                                    border: 1px solid #30363d;
+                                    border-radius: 6px;
+                                    color: #f0f6fc;
+                                    font-size: 0.75rem;
+                                    cursor: pointer;
+                                ",
+                                title: "Save definitions, stack and history",
+                                onclick: save,
+                                "Save"
+                            }
+                            for (label, append) in [("Open", false), ("Append", true)] {
+                                label {
+                                    key: "{label}",
+                                    style: "
+                                        padding: 0.5rem;
+                                        background: #21262d;
+                                        border: 1px solid #30363d;
+                                        border-radius: 6px;
+                                        color: #f0f6fc;
+                                        font-size: 0.75rem;
+                                        cursor: pointer;
+                                    ",
+                                    title: if append { "Merge a saved session into this one" } else { "Replace this session with a saved one" },
+                                    "{label}"
+                                    input {
+                                        r#type: "file",
+                                        accept: ".stks,.txt",
+                                        style: "display: none;",
+                                        onchange: move |evt| load(evt, append),
+                                    }
+                                }
                             }
                         }
 
                         div {
                             style: "flex: 1; text-align: right; font-size: 0.75rem; color: #8b949e;",
-                            "Ctrl+Enter to run • F4 to execute"
+                            "Ctrl+Enter to run • F4 to execute • Ctrl+↑ history • Tab completes"
                         }
                     }
 
-                    // Code editor
-                    textarea {
-                        style: "
-                            flex: 1;
-                            padding: 1rem;
-                            background: #0d1117;
-                            border: none;
-                            font-family: 'SF Mono', Monaco, 'Cascadia Code', 'Roboto Mono', monospace;
-                            font-size: 14px;
-                            line-height: 1.5;
-                            color: #f0f6fc;
-                            resize: none;
-                            outline: none;
-                        ",
-                        placeholder: "Enter your code here...",
-                        value: "{content}",
-                        oninput: move |e| content.set(e.value()),
-                        onkeydown: keydown,
+                    if history_open() {
+                        HistoryPanel { history, content, open: history_open }
+                    }
+
+                    // Code editor: a transparent textarea over its highlighted copy
+                    div {
+                        style: "flex: 1; position: relative; background: #0d1117; overflow: hidden;",
+                        Highlight { content, error: live_err }
+                        textarea {
+                            style: "
+                                position: absolute;
+                                inset: 0;
+                                width: 100%;
+                                height: 100%;
+                                box-sizing: border-box;
+                                padding: 1rem;
+                                background: transparent;
+                                border: none;
+                                font-family: 'SF Mono', Monaco, 'Cascadia Code', 'Roboto Mono', monospace;
+                                font-size: 14px;
+                                line-height: 1.5;
+                                color: transparent;
+                                caret-color: #f0f6fc;
+                                white-space: pre-wrap;
+                                overflow-wrap: break-word;
+                                resize: none;
+                                outline: none;
+                            ",
+                            id: "editor",
+                            spellcheck: "false",
+                            onscroll: move |_| {
+                                document::eval(
+                                    "const e = document.getElementById('editor');
+                                     const h = document.getElementById('editor-highlight');
+                                     if (e && h) { h.scrollTop = e.scrollTop; h.scrollLeft = e.scrollLeft; }",
+                                );
+                            },
+                            placeholder: "Enter your code here...",
+                            value: "{content}",
+                            oninput: move |e| {
+                                content.set(e.value());
+                                completion_closed.set(false);
+                                completion_idx.set(0);
+                                track_cursor();
+                            },
+                            onkeydown: keydown,
+                            onkeyup: move |e| {
+                                if matches!(e.key(), Key::ArrowLeft | Key::ArrowRight | Key::ArrowUp | Key::ArrowDown | Key::Home | Key::End) {
+                                    completion_closed.set(true);
+                                    track_cursor();
+                                }
+                            },
+                            onclick: move |_| {
+                                completion_closed.set(true);
+                                track_cursor();
+                            },
+                        }
+                    }
+
+                    if let Some(parse_err) = live_err() {
+                        div {
+                            style: "
+                                padding: 0.25rem 1rem;
+                                background: #161b22;
+                                border-top: 1px solid #21262d;
+                                font-family: monospace;
+                                font-size: 0.75rem;
+                                color: #f85149;
+                            ",
+                            {
+                                let (line, col) = match parse_err.line_col {
+                                    LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+                                };
+                                format!("{line}:{col}: {}", parse_err.variant.message())
+                            }
+                        }
+                    }
+
+                    CompletionPanel {
+                        vm,
+                        content,
+                        cursor,
+                        selected: completion_idx,
+                        closed: completion_closed,
+                        on_accept: accept_completion,
                     }
 
                     // Error display
@@ -402,14 +799,18 @@ fn App() -> Element {
                             font-weight: 600;
                             font-size: 0.875rem;
                             color: #f0f6fc;
+                            display: flex;
+                            justify-content: space-between;
+                            align-items: center;
                         ",
                         "Stack"
+                        NumberSettings { vm, undo }
                     }
 
                     // Stack content
                     div {
                         style: "flex: 1; overflow-y: auto;",
-                        Stack { vm }
+                        Stack { vm, undo }
                     }
                 }
             }
@@ -418,7 +819,13 @@ fn App() -> Element {
 }
 
 #[component]
-fn Stack(vm: Signal<VM>) -> Element {
+fn Stack(vm: Signal<VM>, undo: Signal<Undo>) -> Element {
+    // Rows are told apart by their entry rather than their place, so an
+    // edit or drag in progress follows its value when the stack changes and
+    // ends when the value is gone.
+    let mut editing = use_signal(|| None::<(Rc<Values>, String)>);
+    let mut edit_err = use_signal(String::new);
+    let mut dragging = use_signal(|| None::<Rc<Values>>);
     let vm_ref = vm.read();
 
     if vm_ref.stack.is_empty() {
@@ -438,39 +845,581 @@ fn Stack(vm: Signal<VM>) -> Element {
         };
     }
 
+    let len = vm_ref.stack.len();
+    // The canonical repr is what gets edited and copied, so it reads back exactly.
+    let entries: Vec<(usize, usize, Rc<Values>, String, String)> = vm_ref
+        .stack
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let key = Rc::as_ptr(x) as usize;
+            (
+                i,
+                key,
+                x.clone(),
+                x.get_repr(&vm_ref.parse_ctx),
+                vm_ref.display(x),
+            )
+        })
+        .collect();
+
+    let mut commit = move || {
+        let Some((entry, text)) = editing() else {
+            return;
+        };
+        let parsed = vm.with_mut(|vm| vm.parse_value(&text));
+        match parsed {
+            Ok(value) => {
+                editing.set(None);
+                edit_err.set(String::new());
+                edit_stack(vm, undo, |stack| {
+                    if let Some(i) = stack.iter().position(|x| Rc::ptr_eq(x, &entry)) {
+                        stack[i] = Rc::new(value);
+                    }
+                });
+            }
+            Err(error) => edit_err.set(error),
+        }
+    };
+
+    let action_style = "
+        padding: 0.125rem 0.375rem;
+        background: none;
+        border: 1px solid #30363d;
+        border-radius: 4px;
+        color: #8b949e;
+        font-size: 0.75rem;
+        cursor: pointer;
+    ";
+
     rsx! {
         div {
             style: "padding: 1rem;",
             div {
                 style: "display: flex; flex-direction: column-reverse; gap: 0.5rem;",
-                for (i, value) in vm_ref.stack.iter().enumerate() {
+                for (i, key, entry, repr, shown) in entries.into_iter() {
                     div {
-                        key: "{i}",
-                        style: "
+                        key: "{key}",
+                        draggable: "true",
+                        ondragstart: {
+                            let entry = entry.clone();
+                            move |_| dragging.set(Some(entry.clone()))
+                        },
+                        ondragover: move |evt| evt.prevent_default(),
+                        ondrop: move |evt| {
+                            evt.prevent_default();
+                            let Some(from) = dragging.take() else {
+                                return;
+                            };
+                            edit_stack(vm, undo, |stack| {
+                                let from = stack.iter().position(|x| Rc::ptr_eq(x, &from));
+                                if let (Some(from), Some(to)) = (from, index_of(stack, key)) {
+                                    let value = stack.remove(from);
+                                    stack.insert(to, value);
+                                }
+                            });
+                        },
+                        style: format!("
                             padding: 0.75rem;
                             background: #161b22;
                             border: 1px solid #21262d;
                             border-radius: 6px;
                             border-left: 3px solid #58a6ff;
-                        ",
+                            cursor: grab;
+                            opacity: {};
+                        ", if dragging().is_some_and(|x| Rc::as_ptr(&x) as usize == key) { "0.5" } else { "1" }),
                         div {
                             style: "
                                 font-size: 0.75rem;
                                 color: #8b949e;
                                 margin-bottom: 0.5rem;
                                 font-weight: 500;
+                                display: flex;
+                                justify-content: space-between;
+                                align-items: center;
                             ",
                             "#{i}"
+                            div {
+                                style: "display: flex; gap: 0.25rem;",
+                                button {
+                                    style: action_style,
+                                    title: "Duplicate",
+                                    onclick: move |_| edit_stack(vm, undo, |stack| {
+                                        // A copy of its own, so each row keeps a distinct key.
+                                        if let Some(i) = index_of(stack, key) {
+                                            stack.insert(i + 1, Rc::new((*stack[i]).clone()));
+                                        }
+                                    }),
+                                    "dup"
+                                }
+                                if len > 1 {
+                                    button {
+                                        style: action_style,
+                                        title: "Swap with the neighbouring value",
+                                        onclick: move |_| edit_stack(vm, undo, |stack| {
+                                            if let Some(i) = index_of(stack, key) {
+                                                let other = if i + 1 < stack.len() { i + 1 } else { i.saturating_sub(1) };
+                                                stack.swap(i, other);
+                                            }
+                                        }),
+                                        "swap"
+                                    }
+                                }
+                                button {
+                                    style: action_style,
+                                    title: "Edit",
+                                    onclick: {
+                                        let entry = entry.clone();
+                                        let repr = repr.clone();
+                                        move |_| {
+                                            edit_err.set(String::new());
+                                            editing.set(Some((entry.clone(), repr.clone())));
+                                        }
+                                    },
+                                    "edit"
+                                }
+                                button {
+                                    style: action_style,
+                                    title: "Copy to clipboard",
+                                    onclick: {
+                                        let repr = repr.clone();
+                                        move |_| copy_to_clipboard(repr.clone())
+                                    },
+                                    "copy"
+                                }
+                                button {
+                                    style: action_style,
+                                    title: "Drop",
+                                    onclick: move |_| edit_stack(vm, undo, |stack| {
+                                        if let Some(i) = index_of(stack, key) {
+                                            stack.remove(i);
+                                        }
+                                    }),
+                                    "✕"
+                                }
+                            }
                         }
-                        div {
-                            style: "
-                                font-family: monospace;
-                                font-size: 0.875rem;
-                                color: #f0f6fc;
-                                word-break: break-all;
-                                line-height: 1.4;
-                            ",
-                            "{value.get_repr(&vm_ref.parse_ctx)}"
+                        match editing() {
+                            Some((edited, text)) if Rc::ptr_eq(&edited, &entry) => rsx! {
+                                input {
+                                    style: "
+                                        width: 100%;
+                                        box-sizing: border-box;
+                                        padding: 0.25rem;
+                                        background: #0d1117;
+                                        border: 1px solid #58a6ff;
+                                        border-radius: 4px;
+                                        color: #f0f6fc;
+                                        font-family: monospace;
+                                        font-size: 0.875rem;
+                                        outline: none;
+                                    ",
+                                    autofocus: true,
+                                    value: "{text}",
+                                    oninput: move |e| editing.with_mut(|x| {
+                                        if let Some((_, text)) = x {
+                                            *text = e.value();
+                                        }
+                                    }),
+                                    onkeydown: move |evt: KeyboardEvent| match evt.key() {
+                                        Key::Enter => commit(),
+                                        Key::Escape => editing.set(None),
+                                        _ => {}
+                                    },
+                                }
+                                if !edit_err.read().is_empty() {
+                                    pre {
+                                        style: "
+                                            font-size: 0.75rem;
+                                            color: #ffa198;
+                                            white-space: pre-wrap;
+                                            margin: 0.25rem 0 0 0;
+                                        ",
+                                        "{edit_err}"
+                                    }
+                                }
+                            },
+                            _ => rsx! {
+                                div {
+                                    style: "
+                                        font-family: monospace;
+                                        font-size: 0.875rem;
+                                        color: #f0f6fc;
+                                        word-break: break-all;
+                                        line-height: 1.4;
+                                    ",
+                                    ondoubleclick: {
+                                        let entry = entry.clone();
+                                        let repr = repr.clone();
+                                        move |_| {
+                                            edit_err.set(String::new());
+                                            editing.set(Some((entry.clone(), repr.clone())));
+                                        }
+                                    },
+                                    "{shown}"
+                                }
+                            },
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Places offered when switching to a mode that takes some.
+const DEFAULT_PLACES: usize = 4;
+
+/// Changes made here are undo steps, so a failed run, which restores the
+/// last step, keeps them.
+#[component]
+fn NumberSettings(vm: Signal<VM>, undo: Signal<Undo>) -> Element {
+    let format = vm.read().number_format;
+    let int_format = vm.read().int_format;
+    let precision = vm.read().precision;
+    let places = match format {
+        NumberFormat::Fixed(places) | NumberFormat::Scientific(places) => Some(places),
+        _ => None,
+    };
+    let control_style = "
+        padding: 0.125rem 0.25rem;
+        background: #0d1117;
+        border: 1px solid #30363d;
+        border-radius: 4px;
+        color: #f0f6fc;
+        font-size: 0.75rem;
+    ";
+
+    rsx! {
+        div {
+            style: "display: flex; gap: 0.25rem; font-weight: normal;",
+            select {
+                style: control_style,
+                title: "How rationals are displayed",
+                onchange: move |e| {
+                    let places = places.unwrap_or(DEFAULT_PLACES);
+                    let format = match e.value().as_str() {
+                        "fixed" => NumberFormat::Fixed(places),
+                        "scientific" => NumberFormat::Scientific(places),
+                        "repeating" => NumberFormat::Repeating,
+                        "mixed" => NumberFormat::Mixed,
+                        _ => NumberFormat::Fraction,
+                    };
+                    vm.write().number_format = format;
+                    undo.with_mut(|u| u.record(&vm.read()));
+                },
+                option { value: "fraction", selected: format == NumberFormat::Fraction, "Fraction" }
+                option { value: "fixed", selected: matches!(format, NumberFormat::Fixed(_)), "Fixed" }
+                option {
+                    value: "scientific",
+                    selected: matches!(format, NumberFormat::Scientific(_)),
+                    "Scientific"
+                }
+                option { value: "repeating", selected: format == NumberFormat::Repeating, "Repeating" }
+                option { value: "mixed", selected: format == NumberFormat::Mixed, "Mixed" }
+            }
+            if let Some(places) = places {
+                input {
+                    style: format!("{control_style} width: 3rem;"),
+                    r#type: "number",
+                    min: "0",
+                    max: "{MAX_PLACES}",
+                    title: "Decimal places",
+                    value: "{places}",
+                    oninput: move |e| {
+                        let Ok(places) = e.value().parse::<usize>() else {
+                            return;
+                        };
+                        let places = places.min(MAX_PLACES);
+                        vm.with_mut(|vm| {
+                            vm.number_format = match vm.number_format {
+                                NumberFormat::Scientific(_) => NumberFormat::Scientific(places),
+                                _ => NumberFormat::Fixed(places),
+                            };
+                        });
+                        undo.with_mut(|u| u.record(&vm.read()));
+                    },
+                }
+            }
+            select {
+                style: control_style,
+                title: "How integers are displayed",
+                onchange: move |e| {
+                    vm.write().int_format = e.value().parse().unwrap_or_default();
+                    undo.with_mut(|u| u.record(&vm.read()));
+                },
+                for (value, label) in [
+                    (IntFormat::Decimal, "Dec"),
+                    (IntFormat::Hex, "Hex"),
+                    (IntFormat::Octal, "Oct"),
+                    (IntFormat::Binary, "Bin"),
+                ] {
+                    option { value: "{value}", selected: int_format == value, "{label}" }
+                }
+            }
+            input {
+                style: format!("{control_style} width: 3.5rem;"),
+                r#type: "number",
+                min: "1",
+                max: "{MAX_PRECISION}",
+                title: "Significant digits of sqrt, exp, ln, sin, cos, pi and pow",
+                value: "{precision}",
+                oninput: move |e| {
+                    if let Ok(digits) = e.value().parse::<usize>() {
+                        vm.write().precision = digits.clamp(1, MAX_PRECISION);
+                        undo.with_mut(|u| u.record(&vm.read()));
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[component]
+fn HistoryPanel(
+    history: Signal<Vec<Arc<String>>>,
+    content: Signal<String>,
+    open: Signal<bool>,
+) -> Element {
+    let mut query = use_signal(String::new);
+    let mut selected = use_signal(|| 0usize);
+    let matches = history::search(&history.read(), &query.read());
+
+    let mut pick = move |idx: usize| {
+        if let Some(entry) = history.read().get(idx) {
+            content.set(entry.to_string());
+        }
+        open.set(false);
+    };
+
+    let keydown = {
+        let matches = matches.clone();
+        move |evt: KeyboardEvent| match evt.key() {
+            Key::ArrowDown => {
+                evt.prevent_default();
+                selected.set((selected() + 1).min(matches.len().saturating_sub(1)));
+            }
+            Key::ArrowUp => {
+                evt.prevent_default();
+                selected.set(selected().saturating_sub(1));
+            }
+            Key::Enter => {
+                evt.prevent_default();
+                if let Some(idx) = matches.get(selected()) {
+                    pick(*idx);
+                }
+            }
+            Key::Escape => open.set(false),
+            _ => {}
+        }
+    };
+
+    rsx! {
+        div {
+            style: "
+                max-height: 40%;
+                display: flex;
+                flex-direction: column;
+                background: #161b22;
+                border-bottom: 1px solid #21262d;
+            ",
+            input {
+                style: "
+                    margin: 0.5rem;
+                    padding: 0.5rem;
+                    background: #0d1117;
+                    border: 1px solid #30363d;
+                    border-radius: 6px;
+                    color: #f0f6fc;
+                    font-family: monospace;
+                    font-size: 0.875rem;
+                    outline: none;
+                ",
+                placeholder: "Search history...",
+                autofocus: true,
+                value: "{query}",
+                oninput: move |e| {
+                    query.set(e.value());
+                    selected.set(0);
+                },
+                onkeydown: keydown,
+            }
+            div {
+                style: "overflow-y: auto;",
+                if matches.is_empty() {
+                    div {
+                        style: "padding: 0.5rem 1rem; font-size: 0.75rem; color: #6e7681;",
+                        "No matching entries"
+                    }
+                }
+                for (i, idx) in matches.into_iter().enumerate() {
+                    div {
+                        key: "{idx}",
+                        class: "example-item",
+                        style: format!("
+                            padding: 0.5rem 1rem;
+                            font-family: monospace;
+                            font-size: 0.75rem;
+                            white-space: pre-wrap;
+                            cursor: pointer;
+                            border-left: 3px solid {};
+                            background: {};
+                        ",
+                            if i == selected() { "#58a6ff" } else { "transparent" },
+                            if i == selected() { "#1c2128" } else { "transparent" }
+                        ),
+                        onclick: move |_| pick(idx),
+                        "{history.read()[idx]}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splits the editor text into coloured runs, underlining `error`.
+fn highlight_runs(text: &str, error: Option<Range<usize>>) -> Vec<(String, &'static str, bool)> {
+    let mut runs = vec![];
+    for (span, token) in highlight(text) {
+        let mut cuts = vec![span.start, span.end];
+        if let Some(error) = &error {
+            cuts.extend(
+                [error.start, error.end]
+                    .into_iter()
+                    .filter(|x| span.contains(x)),
+            );
+        }
+        cuts.sort();
+        cuts.dedup();
+        for run in cuts.windows(2) {
+            let underlined = error
+                .as_ref()
+                .is_some_and(|error| error.start <= run[0] && run[1] <= error.end);
+            runs.push((text[run[0]..run[1]].to_string(), token.color(), underlined));
+        }
+    }
+    // Keeps an error at the very end visible, and the last line's height.
+    let at_end = error.is_some_and(|error| error.start >= text.len());
+    runs.push((" ".to_string(), Token::Plain.color(), at_end));
+    runs
+}
+
+#[component]
+fn Highlight(content: Signal<String>, error: Memo<Option<pest::error::Error<Rule>>>) -> Element {
+    let text = content.read();
+    let error_span = error.read().as_ref().map(|err| match err.location {
+        InputLocation::Pos(pos) => {
+            let end = text[pos.min(text.len())..]
+                .chars()
+                .next()
+                .map_or(pos, |c| pos + c.len_utf8());
+            pos..end
+        }
+        InputLocation::Span((start, end)) => start..end,
+    });
+    let runs = highlight_runs(&text, error_span);
+
+    rsx! {
+        pre {
+            id: "editor-highlight",
+            "aria-hidden": "true",
+            style: "
+                position: absolute;
+                inset: 0;
+                margin: 0;
+                padding: 1rem;
+                box-sizing: border-box;
+                font-family: 'SF Mono', Monaco, 'Cascadia Code', 'Roboto Mono', monospace;
+                font-size: 14px;
+                line-height: 1.5;
+                white-space: pre-wrap;
+                overflow-wrap: break-word;
+                overflow: hidden;
+                pointer-events: none;
+            ",
+            for (i, (run, color, underlined)) in runs.into_iter().enumerate() {
+                span {
+                    key: "{i}",
+                    style: if underlined {
+                        format!("color: {color}; text-decoration: underline wavy #f85149;")
+                    } else {
+                        format!("color: {color};")
+                    },
+                    "{run}"
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn CompletionPanel(
+    vm: Signal<VM>,
+    content: Signal<String>,
+    cursor: Signal<usize>,
+    selected: Signal<usize>,
+    closed: Signal<bool>,
+    on_accept: EventHandler<String>,
+) -> Element {
+    let vm_ref = vm.read();
+    let text = content.read();
+    let suggestions = if closed() {
+        vec![]
+    } else {
+        complete::suggestions(&vm_ref, &text, cursor())
+    };
+    let signature = complete::signature(&vm_ref, &text, cursor());
+    if suggestions.is_empty() && signature.is_none() {
+        return rsx! {};
+    }
+    let selected = selected().min(suggestions.len().saturating_sub(1));
+
+    rsx! {
+        div {
+            style: "
+                max-height: 30%;
+                display: flex;
+                flex-direction: column;
+                background: #161b22;
+                border-top: 1px solid #21262d;
+                font-family: monospace;
+                font-size: 0.75rem;
+            ",
+            if let Some(signature) = signature {
+                div {
+                    style: "padding: 0.375rem 1rem; color: #8b949e; white-space: nowrap; overflow: hidden; text-overflow: ellipsis;",
+                    title: "{signature}",
+                    "{signature}"
+                }
+            }
+            div {
+                style: "overflow-y: auto;",
+                for (i, suggestion) in suggestions.into_iter().enumerate() {
+                    div {
+                        key: "{suggestion.label}",
+                        class: "example-item",
+                        style: format!("
+                            display: flex;
+                            gap: 0.75rem;
+                            padding: 0.25rem 1rem;
+                            cursor: pointer;
+                            border-left: 3px solid {};
+                            background: {};
+                        ",
+                            if i == selected { "#58a6ff" } else { "transparent" },
+                            if i == selected { "#1c2128" } else { "transparent" }
+                        ),
+                        onmousedown: move |evt| evt.prevent_default(),
+                        onclick: {
+                            let label = suggestion.label.clone();
+                            move |_| on_accept.call(label.clone())
+                        },
+                        span { style: "color: #f0f6fc;", "{suggestion.label}" }
+                        span { style: "color: #6e7681;", "{suggestion.kind.label()}" }
+                        span {
+                            style: "flex: 1; color: #8b949e; white-space: nowrap; overflow: hidden; text-overflow: ellipsis;",
+                            "{suggestion.detail}"
                         }
                     }
                 }
@@ -527,12 +1476,12 @@ fn Tutorial() -> Element {
                     style: "font-size: 0.75rem; margin-bottom: 0.5rem;",
                     code {
                         style: "background: #21262d; padding: 0.5rem; border-radius: 3px; display: block;",
-                        "dup = |_x| {{_x _x}};"
+                        "square = |x| {{x x mul}};"
                     }
                 }
                 p {
                     style: "font-size: 0.75rem; color: #8b949e;",
-                    "Defines a function that duplicates the top stack value."
+                    "Defines a function that squares the top stack value. Helpers like dup, swap and over come with the prelude."
                 }
             }
 
@@ -578,8 +1527,84 @@ fn Tutorial() -> Element {
     }
 }
 
+/// Keeps the sidebar `items` whose name fuzzily matches `query` or whose
+/// source contains it. Items arrive sorted by name.
+fn sidebar_filter<T>(
+    items: Vec<T>,
+    query: &str,
+    descending: bool,
+    text: impl Fn(&T) -> (&str, &str),
+) -> Vec<T> {
+    let needle = query.trim().to_lowercase();
+    let mut ret: Vec<T> = items
+        .into_iter()
+        .filter(|item| {
+            let (name, source) = text(item);
+            needle.is_empty()
+                || history::fuzzy_score(&needle, name).is_some()
+                || source.to_lowercase().contains(&needle)
+        })
+        .collect();
+    if descending {
+        ret.reverse();
+    }
+    ret
+}
+
+#[component]
+fn SidebarSearch(query: Signal<String>, descending: Signal<bool>, placeholder: String) -> Element {
+    rsx! {
+        div {
+            style: "display: flex; gap: 0.5rem;",
+            input {
+                style: "
+                    flex: 1;
+                    padding: 0.375rem 0.5rem;
+                    background: #0d1117;
+                    border: 1px solid #30363d;
+                    border-radius: 6px;
+                    color: #f0f6fc;
+                    font-family: monospace;
+                    font-size: 0.75rem;
+                    outline: none;
+                ",
+                placeholder: "{placeholder}",
+                value: "{query}",
+                oninput: move |e| query.set(e.value()),
+            }
+            button {
+                style: "
+                    padding: 0.25rem 0.5rem;
+                    background: #21262d;
+                    border: 1px solid #30363d;
+                    border-radius: 6px;
+                    color: #f0f6fc;
+                    font-size: 0.75rem;
+                    cursor: pointer;
+                ",
+                title: "Sort by name",
+                onclick: move |_| descending.toggle(),
+                if descending() { "Z–A" } else { "A–Z" }
+            }
+        }
+    }
+}
+
+const SIDEBAR_ACTION_STYLE: &str = "
+    padding: 0.125rem 0.375rem;
+    background: none;
+    border: 1px solid #30363d;
+    border-radius: 4px;
+    color: #8b949e;
+    font-size: 0.75rem;
+    cursor: pointer;
+";
+
 #[component]
-fn Definitions(vm: Signal<VM>) -> Element {
+fn Definitions(vm: Signal<VM>, undo: Signal<Undo>, content: Signal<String>) -> Element {
+    let query = use_signal(String::new);
+    let descending = use_signal(|| false);
+    let mut used_by = use_signal(|| None::<String>);
     let vm_ref = vm.read();
     let defs = vm_ref.get_definitons();
 
@@ -593,10 +1618,21 @@ fn Definitions(vm: Signal<VM>) -> Element {
         };
     }
 
+    let defs = sidebar_filter(defs, &query.read(), descending(), |(name, _, source)| {
+        (name, source)
+    });
+    let callers = used_by().map(|name| vm_ref.where_used(&name));
+
+    let mut delete = move |name: String| {
+        vm.with_mut(|vm| vm.delete_definition(&name));
+        undo.with_mut(|u| u.record(&vm.read()));
+    };
+
     rsx! {
         div {
             style: "display: flex; flex-direction: column; gap: 0.75rem;",
-            for (name, body) in defs.iter() {
+            SidebarSearch { query, descending, placeholder: "Search definitions..." }
+            for (name, body, source) in defs.into_iter() {
                 div {
                     key: "{name}",
                     style: "
@@ -607,8 +1643,43 @@ fn Definitions(vm: Signal<VM>) -> Element {
                         border-left: 3px solid #a5a5a5;
                     ",
                     div {
-                        style: "font-weight: 600; color: #f0f6fc; margin-bottom: 0.5rem; font-size: 0.875rem;",
-                        "{name}"
+                        style: "display: flex; align-items: center; gap: 0.25rem; margin-bottom: 0.5rem;",
+                        div {
+                            style: "flex: 1; font-weight: 600; color: #f0f6fc; font-size: 0.875rem; cursor: pointer;",
+                            title: "Edit in the editor",
+                            onclick: {
+                                let source = source.clone();
+                                move |_| content.set(source.clone())
+                            },
+                            "{name}"
+                        }
+                        button {
+                            style: SIDEBAR_ACTION_STYLE,
+                            title: "Show callers",
+                            onclick: {
+                                let name = name.clone();
+                                move |_| {
+                                    let shown = used_by() == Some(name.clone());
+                                    used_by.set(if shown { None } else { Some(name.clone()) });
+                                }
+                            },
+                            "used by"
+                        }
+                        button {
+                            style: SIDEBAR_ACTION_STYLE,
+                            title: "Edit in the editor",
+                            onclick: move |_| content.set(source.clone()),
+                            "edit"
+                        }
+                        button {
+                            style: SIDEBAR_ACTION_STYLE,
+                            title: "Delete",
+                            onclick: {
+                                let name = name.clone();
+                                move |_| delete(name.clone())
+                            },
+                            "✕"
+                        }
                     }
                     div {
                         style: "font-family: monospace; font-size: 0.75rem; color: #8b949e;",
@@ -616,6 +1687,18 @@ fn Definitions(vm: Signal<VM>) -> Element {
                             div { key: "{i}", "{item}" }
                         }
                     }
+                    if used_by() == Some(name.clone()) {
+                        div {
+                            style: "margin-top: 0.5rem; font-size: 0.75rem; color: #8b949e;",
+                            match callers.as_ref().filter(|x| !x.is_empty()) {
+                                Some(callers) => rsx! {
+                                    "Used by: "
+                                    span { style: "font-family: monospace; color: #f0f6fc;", "{callers.join(\", \")}" }
+                                },
+                                None => rsx! { "Not used by any definition" },
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -623,7 +1706,9 @@ fn Definitions(vm: Signal<VM>) -> Element {
 }
 
 #[component]
-fn Structs(vm: Signal<VM>) -> Element {
+fn Structs(vm: Signal<VM>, content: Signal<String>) -> Element {
+    let query = use_signal(String::new);
+    let descending = use_signal(|| false);
     let vm_ref = vm.read();
     let structs = vm_ref.get_structs();
 
@@ -637,10 +1722,15 @@ fn Structs(vm: Signal<VM>) -> Element {
         };
     }
 
+    let structs = sidebar_filter(structs, &query.read(), descending(), |(name, _, source)| {
+        (name, source)
+    });
+
     rsx! {
         div {
             style: "display: flex; flex-direction: column; gap: 0.75rem;",
-            for (name, body) in structs.iter() {
+            SidebarSearch { query, descending, placeholder: "Search structs..." }
+            for (name, body, source) in structs.into_iter() {
                 div {
                     key: "{name}",
                     style: "
@@ -649,7 +1739,10 @@ fn Structs(vm: Signal<VM>) -> Element {
                         border: 1px solid #21262d;
                         border-radius: 6px;
                         border-left: 3px solid #f79000;
+                        cursor: pointer;
                     ",
+                    title: "Edit in the editor",
+                    onclick: move |_| content.set(source.clone()),
                     div {
                         style: "font-weight: 600; color: #f79000; margin-bottom: 0.5rem; font-size: 0.875rem;",
                         "struct {name}"
@@ -667,7 +1760,9 @@ fn Structs(vm: Signal<VM>) -> Element {
 }
 
 #[component]
-fn Enums(vm: Signal<VM>) -> Element {
+fn Enums(vm: Signal<VM>, content: Signal<String>) -> Element {
+    let query = use_signal(String::new);
+    let descending = use_signal(|| false);
     let vm_ref = vm.read();
     let enums = vm_ref.get_enums();
 
@@ -681,10 +1776,15 @@ fn Enums(vm: Signal<VM>) -> Element {
         };
     }
 
+    let enums = sidebar_filter(enums, &query.read(), descending(), |(name, _, source)| {
+        (name, source)
+    });
+
     rsx! {
         div {
             style: "display: flex; flex-direction: column; gap: 0.75rem;",
-            for (name, variants) in enums.iter() {
+            SidebarSearch { query, descending, placeholder: "Search enums..." }
+            for (name, variants, source) in enums.into_iter() {
                 div {
                     key: "{name}",
                     style: "
@@ -693,7 +1793,10 @@ fn Enums(vm: Signal<VM>) -> Element {
                         border: 1px solid #21262d;
                         border-radius: 6px;
                         border-left: 3px solid #a855f7;
+                        cursor: pointer;
                     ",
+                    title: "Edit in the editor",
+                    onclick: move |_| content.set(source.clone()),
                     div {
                         style: "font-weight: 600; color: #a855f7; margin-bottom: 0.5rem; font-size: 0.875rem;",
                         "enum {name}"
@@ -726,6 +1829,98 @@ fn Enums(vm: Signal<VM>) -> Element {
     }
 }
 
+#[component]
+fn Modules(vm: Signal<VM>) -> Element {
+    let mut name = use_signal(String::new);
+    let mut source = use_signal(String::new);
+    let modules = vm.read().get_modules();
+
+    let register = move |_| {
+        let module = name.read().trim().to_string();
+        if module.is_empty() {
+            return;
+        }
+        let code = source.read().clone();
+        vm.with_mut(|vm| vm.modules.registry.insert(module, Arc::new(code)));
+        name.set(String::new());
+        source.set(String::new());
+    };
+
+    rsx! {
+        div {
+            style: "display: flex; flex-direction: column; gap: 0.75rem;",
+            div {
+                style: "
+                    padding: 0.75rem;
+                    background: #161b22;
+                    border: 1px solid #21262d;
+                    border-radius: 6px;
+                    display: flex;
+                    flex-direction: column;
+                    gap: 0.5rem;
+                ",
+                div {
+                    style: "font-weight: 600; color: #f0f6fc; font-size: 0.875rem;",
+                    "Register module"
+                }
+                input {
+                    style: "background: #0d1117; border: 1px solid #30363d; color: #f0f6fc; padding: 0.25rem; font-family: monospace;",
+                    placeholder: "name",
+                    value: "{name}",
+                    oninput: move |e| name.set(e.value()),
+                }
+                textarea {
+                    style: "background: #0d1117; border: 1px solid #30363d; color: #f0f6fc; padding: 0.25rem; font-family: monospace; min-height: 6rem;",
+                    placeholder: "sq = |x| {{x x mul}};",
+                    value: "{source}",
+                    oninput: move |e| source.set(e.value()),
+                }
+                button {
+                    style: "
+                        padding: 0.25rem 0.5rem;
+                        background: #21262d;
+                        border: 1px solid #30363d;
+                        border-radius: 6px;
+                        color: #f0f6fc;
+                        cursor: pointer;
+                    ",
+                    onclick: register,
+                    "Register"
+                }
+                p {
+                    style: "font-size: 0.75rem; color: #8b949e; margin: 0;",
+                    "Registered modules can be loaded with import name or use name::fun."
+                }
+            }
+            for (module, members) in modules.iter() {
+                div {
+                    key: "{module}",
+                    style: "
+                        padding: 0.75rem;
+                        background: #161b22;
+                        border: 1px solid #21262d;
+                        border-radius: 6px;
+                        border-left: 3px solid #3fb950;
+                    ",
+                    div {
+                        style: "font-weight: 600; color: #3fb950; margin-bottom: 0.5rem; font-size: 0.875rem;",
+                        "module {module}"
+                    }
+                    if members.is_empty() {
+                        div { style: "font-size: 0.75rem; color: #6e7681;", "not loaded yet" }
+                    }
+                    div {
+                        style: "font-family: monospace; font-size: 0.75rem; color: #8b949e; margin-left: 0.5rem;",
+                        for member in members.iter() {
+                            div { key: "{member}", "{module}::{member}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 fn Examples(content: Signal<String>) -> Element {
     let examples = [
@@ -763,11 +1958,25 @@ fn Examples(content: Signal<String>) -> Element {
         (
             "Functions",
             vec![
-                ("Simple Function", "dup = |x| {x x};"),
-                ("Using Function", "5 dup"),
-                ("Swap Function", "swap = |x y| {y x};\n1 2 swap"),
-                ("Drop Function", "drop = |x| {};\n42 drop"),
-                ("Over Function", "over = |x y| {y x y};\n1 2 over"),
+                ("Simple Function", "square = |x| {x x mul};"),
+                ("Using Function", "5 square"),
+                (
+                    "Recursive Function",
+                    "fact = | 0 => 1, | n => n n 1 sub fact mul, ;\n10 fact;",
+                ),
+            ],
+        ),
+        (
+            "Prelude",
+            vec![
+                ("Dup / Drop", "5 dup 6 drop"),
+                ("Swap / Over", "1 2 swap over"),
+                ("Rot / Nip / Tuck", "1 2 3 rot nip tuck"),
+                ("Dip", "1 2 [10 add] dip"),
+                ("Keep", "5 [1 add] keep"),
+                ("Bi / Tri", "3 [2 mul] [1 add] bi"),
+                ("Option", "Option::Some(4) [1 add] map 0 unwrap_or"),
+                ("Result", "Result::Err(2) [3 mul] map_err is_err"),
             ],
         ),
         (
@@ -797,6 +2006,14 @@ fn Examples(content: Signal<String>) -> Element {
                     "Break/Return",
                     "1 while true { dup 10 ge ? { break } dup 1 add }",
                 ),
+                (
+                    "Throw / Catch",
+                    "5 try { 1 2 throw } catch | x => x 10 add,",
+                ),
+                (
+                    "Catch Built-in Errors",
+                    "try { 1 missing } catch\n  | Error::UndefinedCall() => 0,\n  | e => e throw,",
+                ),
             ],
         ),
         (
@@ -810,15 +2027,20 @@ fn Examples(content: Signal<String>) -> Element {
                 ),
                 (
                     "Enum Definition",
-                    "enum Option {\n  | Some('T)\n  | None()\n};",
+                    "enum Shape {\n  | Circle(Rat)\n  | Rect(Rat Rat)\n};",
                 ),
                 (
                     "Enum Usage",
-                    "enum Option { | Some('T) | None() };\nOption::Some(42);",
+                    "enum Shape { | Circle(Rat) | Rect(Rat Rat) };\nShape::Circle(1.5);",
                 ),
                 (
                     "Enum Pattern",
-                    "enum Option { | Some('T) | None() };\nOption::Some(42) | Option::Some(x) => x,",
+                    "Option::Some(42) | Option::Some(x) => x, | Option::None() => 0,",
+                ),
+                ("Generic Struct", "struct Pair<'a> { 'a 'a };\nPair(1 2);"),
+                (
+                    "Generic Enum",
+                    "enum Tree<'a> {\n  | Node(Tree<'a> 'a Tree<'a>)\n  | Leaf()\n};\nTree::Node(Tree::Leaf() 1 Tree::Leaf());",
                 ),
             ],
         ),
@@ -829,6 +2051,21 @@ fn Examples(content: Signal<String>) -> Element {
                     "Some 'functions' can be used on different types",
                     "1 2 add \n0.1 0.3 add\n true false or\n 3 2 or\n",
                 ),
+                (
+                    "Interfaces",
+                    "interface Numeric { add sub mul };
+struct Money { Int };
+
+impl Numeric for Money {
+    add(Money Money) = | Money(x) Money(y) => Money(x y add), ;
+    sub(Money Money) = | Money(x) Money(y) => Money(x y sub), ;
+    mul(Money Int) = | Money(x) y => Money(x y mul), ;
+};
+impl Numeric for Int {};
+
+double('a: Numeric) = |x| {x x add};
+Money(2) double 3 double;",
+                ),
                 (
                     "Defining addition on a custom Type",
                     "struct Dummy{Int} ;
@@ -839,19 +2076,23 @@ Dummy(1) Dummy(2) add;",
                 ),
             ],
         ),
+        (
+            "Modules",
+            vec![
+                (
+                    "Define a Module",
+                    "module geometry {\n  square = |x| {x x mul};\n  cube = |x| {x x square mul};\n};",
+                ),
+                ("Qualified Call", "3 geometry::cube"),
+                ("Import", "import geometry;\n4 square;"),
+                ("Use One Name", "use geometry::cube;\n2 cube;"),
+            ],
+        ),
         (
             "Algorithms",
             vec![(
                 "Fibonacci",
-                "drop = |x| {};
-
-drop2 = |x y| { };
-
-dup = |x| {x x} ;
-
-swap = |x y|{ y x};
-
-fib_step = | x y z |{
+                "fib_step = | x y z |{
   x y add
   x
   z 1 sub
@@ -929,11 +2170,15 @@ fib = | n |{ 1 1 n }
 }
 
 #[component]
-fn Protocols(vm: Signal<VM>) -> Element {
+fn Protocols(vm: Signal<VM>, content: Signal<String>) -> Element {
+    let query = use_signal(String::new);
+    let descending = use_signal(|| false);
+    let mut used_by = use_signal(|| None::<String>);
     let vm_ref = vm.read();
     let defs = vm_ref.get_protocols();
+    let interfaces = vm_ref.get_interfaces();
 
-    if defs.is_empty() {
+    if defs.is_empty() && interfaces.is_empty() {
         return rsx! {
             div {
                 style: "text-center py-8 color: #6e7681;",
@@ -943,9 +2188,46 @@ fn Protocols(vm: Signal<VM>) -> Element {
         };
     }
 
+    let interfaces = sidebar_filter(interfaces, &query.read(), descending(), |(name, _, _)| {
+        (name, "")
+    });
+    let defs = sidebar_filter(defs, &query.read(), descending(), |(name, _)| (name, ""));
+    let callers = used_by().map(|name| vm_ref.where_used(&name));
+
     rsx! {
         div {
             style: "display: flex; flex-direction: column; gap: 0.75rem;",
+            SidebarSearch { query, descending, placeholder: "Search protocols..." }
+            for (name, methods, implementors) in interfaces.into_iter() {
+                div {
+                    key: "interface {name}",
+                    style: "
+                        padding: 0.75rem;
+                        background: #161b22;
+                        border: 1px solid #21262d;
+                        border-radius: 6px;
+                        border-left: 3px solid #58a6ff;
+                    ",
+                    div {
+                        style: "font-weight: 600; color: #58a6ff; margin-bottom: 0.5rem; font-size: 0.875rem;",
+                        "interface {name}"
+                    }
+                    div {
+                        style: "font-family: monospace; font-size: 0.75rem; color: #8b949e; margin-bottom: 0.5rem;",
+                        "{methods.join(\" \")}"
+                    }
+                    if implementors.is_empty() {
+                        div { style: "font-size: 0.75rem; color: #6e7681;", "no implementations" }
+                    }
+                    for implementor in implementors.into_iter() {
+                        div {
+                            key: "{implementor}",
+                            style: "font-family: monospace; font-size: 0.75rem; color: #f0f6fc;",
+                            "impl for {implementor}"
+                        }
+                    }
+                }
+            }
             for (name, signatures) in defs.into_iter() {
                 div {
                     key: "{name}",
@@ -957,15 +2239,48 @@ fn Protocols(vm: Signal<VM>) -> Element {
                         border-left: 3px solid #a5a5a5;
                     ",
                     div {
-                        style: "font-weight: 600; color: #f0f6fc; margin-bottom: 0.5rem; font-size: 0.875rem;",
-                        "{name}"
+                        style: "display: flex; align-items: center; margin-bottom: 0.5rem;",
+                        div {
+                            style: "flex: 1; font-weight: 600; color: #f0f6fc; font-size: 0.875rem;",
+                            "{name}"
+                        }
+                        button {
+                            style: SIDEBAR_ACTION_STYLE,
+                            title: "Show callers",
+                            onclick: {
+                                let name = name.clone();
+                                move |_| {
+                                    let shown = used_by() == Some(name.clone());
+                                    used_by.set(if shown { None } else { Some(name.clone()) });
+                                }
+                            },
+                            "used by"
+                        }
                     }
-                    for (i,signature) in signatures.into_iter().enumerate(){
-                     div {
-                        key: "{i}",
-                        "{signature}"
-
+                    for (i, (signature, source)) in signatures.into_iter().enumerate() {
+                        div {
+                            key: "{i}",
+                            style: if source.is_some() { "cursor: pointer; color: #f0f6fc;" } else { "" },
+                            title: if source.is_some() { "Edit in the editor" } else { "built in" },
+                            onclick: move |_| {
+                                if let Some(source) = &source {
+                                    content.set(source.clone());
+                                }
+                            },
+                            "{signature}"
+                        }
                     }
+                    if used_by() == Some(name.clone()) {
+                        div {
+                            style: "margin-top: 0.5rem; font-size: 0.75rem; color: #8b949e;",
+                            match callers.as_ref().filter(|x| !x.is_empty()) {
+                                Some(callers) => rsx! {
+                                    "Used by: "
+                                    span { style: "font-family: monospace; color: #f0f6fc;", "{callers.join(\", \")}" }
+                                },
+                                None => rsx! { "Not used by any definition" },
+                            }
+                        }
                     }
                 }
             }
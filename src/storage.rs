@@ -0,0 +1,27 @@
+use dioxus::prelude::*;
+
+pub const SESSION_KEY: &str = "stackulator.session";
+pub const EDITOR_KEY: &str = "stackulator.editor";
+
+/// Writes `value` to the browser's `localStorage`. Persistence is best effort:
+/// disabled storage or an exceeded quota are ignored.
+pub fn save(key: &str, value: String) {
+    let js = document::eval(&format!(
+        r#"
+        const value = await dioxus.recv();
+        try {{ localStorage.setItem({key:?}, value); }} catch (_) {{}}
+        "#
+    ));
+    let _ = js.send(value);
+}
+
+pub async fn load(key: &str) -> Option<String> {
+    let mut js = document::eval(&format!(
+        r#"
+        let value = null;
+        try {{ value = localStorage.getItem({key:?}); }} catch (_) {{}}
+        dioxus.send(value);
+        "#
+    ));
+    js.recv::<Option<String>>().await.ok().flatten()
+}
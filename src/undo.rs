@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::language::env::Env;
+use crate::language::eval::ValueStack;
+use crate::language::number::{IntFormat, NumberFormat};
+use crate::language::parse::Scopes;
+use crate::language::vm::VM;
+
+/// The parts of a `VM` that undo restores. Stack entries, the `Env` and the
+/// parser's scopes, which hold modules, `use` aliases and units, are shared
+/// with the VM and the neighbouring snapshots, so taking or restoring one
+/// never copies values and a change shows as a different pointer.
+struct Snapshot {
+    stack: ValueStack,
+    env: Rc<Env>,
+    scopes: Rc<Scopes>,
+    settings: Settings,
+}
+
+impl Snapshot {
+    fn of(vm: &VM) -> Self {
+        Snapshot {
+            stack: vm.stack.clone(),
+            env: vm.env.clone(),
+            scopes: vm.parse_ctx.scopes().clone(),
+            settings: Settings::of(vm),
+        }
+    }
+}
+
+/// Display modes, precision and the random generator's state, so undoing a
+/// draw gives the same draw again.
+#[derive(Clone, Copy, PartialEq)]
+struct Settings {
+    number_format: NumberFormat,
+    int_format: IntFormat,
+    precision: usize,
+    rng: u64,
+}
+
+impl Settings {
+    fn of(vm: &VM) -> Self {
+        Settings {
+            number_format: vm.number_format,
+            int_format: vm.int_format,
+            precision: vm.precision,
+            rng: vm.rng,
+        }
+    }
+}
+
+/// Bounded timeline of VM states. `current` is the state the VM is in;
+/// everything after it can be redone.
+pub struct Undo {
+    states: VecDeque<Snapshot>,
+    current: usize,
+    limit: usize,
+}
+
+impl Undo {
+    pub fn new(vm: &VM, limit: usize) -> Self {
+        let mut states = VecDeque::new();
+        states.push_back(Snapshot::of(vm));
+        Self {
+            states,
+            current: 0,
+            limit: limit.max(1),
+        }
+    }
+
+    /// Records the VM's state as a new step, dropping anything that could
+    /// have been redone. Does nothing if the VM didn't change.
+    pub fn record(&mut self, vm: &VM) {
+        let last = &self.states[self.current];
+        let unchanged_stack = last.stack.len() == vm.stack.len()
+            && last
+                .stack
+                .entries()
+                .iter()
+                .zip(vm.stack.entries())
+                .all(|(a, b)| Rc::ptr_eq(a, b));
+        if unchanged_stack
+            && Rc::ptr_eq(&last.env, &vm.env)
+            && Rc::ptr_eq(&last.scopes, vm.parse_ctx.scopes())
+            && last.settings == Settings::of(vm)
+        {
+            return;
+        }
+
+        self.states.truncate(self.current + 1);
+        self.states.push_back(Snapshot::of(vm));
+        if self.states.len() > self.limit {
+            self.states.pop_front();
+        }
+        self.current = self.states.len() - 1;
+    }
+
+    /// Puts the VM back into the current state, e.g. after a failed
+    /// evaluation.
+    pub fn restore(&self, vm: &mut VM) {
+        let snapshot = &self.states[self.current];
+        vm.stack = snapshot.stack.clone();
+        vm.env = snapshot.env.clone();
+        vm.parse_ctx.set_scopes(snapshot.scopes.clone());
+        let settings = snapshot.settings;
+        vm.number_format = settings.number_format;
+        vm.int_format = settings.int_format;
+        vm.precision = settings.precision;
+        vm.rng = settings.rng;
+    }
+
+    pub fn undo(&mut self, vm: &mut VM) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        self.current -= 1;
+        self.restore(vm);
+        true
+    }
+
+    pub fn redo(&mut self, vm: &mut VM) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        self.current += 1;
+        self.restore(vm);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.current + 1 < self.states.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(vm: &mut VM, undo: &mut Undo, source: &str) {
+        vm.parse_full_program(source).unwrap();
+        vm.eval().unwrap();
+        undo.record(vm);
+    }
+
+    #[test]
+    fn shares_untouched_entries() {
+        let mut vm = VM::default();
+        let mut undo = Undo::new(&vm, 10);
+        run(&mut vm, &mut undo, "List(1 2 3) 2;");
+        run(&mut vm, &mut undo, "1 add;");
+        let [before, after] = [&undo.states[1].stack, &undo.states[2].stack];
+        assert!(Rc::ptr_eq(&before.entries()[0], &after.entries()[0]));
+        assert!(Rc::ptr_eq(&after.entries()[0], &vm.stack.entries()[0]));
+
+        run(&mut vm, &mut undo, "1 drop;");
+        assert_eq!(undo.states.len(), 3);
+    }
+
+    #[test]
+    fn restores_scopes() {
+        let mut vm = VM::default();
+        let mut undo = Undo::new(&vm, 10);
+        run(
+            &mut vm,
+            &mut undo,
+            "unit furlong = 201.168 m; module geo { sq = |x| {x x mul}; };",
+        );
+        run(&mut vm, &mut undo, "use geo::sq;");
+        assert!(undo.undo(&mut vm));
+        assert!(vm.parse_ctx.get_aliases().is_empty());
+        assert!(undo.undo(&mut vm));
+        assert!(vm.parse_ctx.lookup_unit("furlong").is_none());
+        assert!(!vm.parse_ctx.has_module("geo"));
+        assert!(undo.redo(&mut vm));
+        assert!(vm.parse_ctx.lookup_unit("furlong").is_some());
+    }
+}